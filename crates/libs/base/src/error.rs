@@ -10,6 +10,8 @@ pub enum Error {
     InvalidValue(#[from] InvalidValue),
     #[error(transparent)]
     PersistenceError(#[from] PersistenceError),
+    #[error(transparent)]
+    VersionConflict(#[from] VersionConflict),
     #[error("{0}")]
     Forbiden(&'static str)
 }
@@ -21,3 +21,15 @@ impl PersistenceError {
         Self(s.into())
     }
 }
+
+/// The stored record changed since it was read, or appeared or vanished
+/// in the meantime.
+#[derive(Debug, Error)]
+#[error("Version conflict on {0}")]
+pub struct VersionConflict(String);
+
+impl VersionConflict {
+    pub fn new(s: impl Into<String>) -> Self {
+        Self(s.into())
+    }
+}
@@ -0,0 +1,228 @@
+use std::{
+    any::Any,
+    fmt::Debug,
+    sync::{Arc, RwLock},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::vo::Version;
+
+/// Gives access to the concrete type behind a `dyn` [`DomainEvent`].
+pub trait AsAny {
+    fn as_any(&self) -> &dyn Any;
+}
+
+impl<T: Any> AsAny for T {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// Something that happened to a record, such as a user being created or a
+/// role losing a privilege.
+pub trait DomainEvent: AsAny + Debug + Send + Sync {
+    /// A stable name such as `UserCreated`.
+    fn name(&self) -> &'static str;
+
+    /// The [`crate::Model::NAME`] of the record the event belongs to.
+    fn aggregate_type(&self) -> &'static str;
+
+    /// The id of the record the event belongs to.
+    fn aggregate(&self) -> String;
+
+    /// The event's fields, as stored in the outbox.
+    fn payload(&self) -> serde_json::Value;
+
+    /// The version the record got through the change.
+    fn version(&self) -> Version;
+}
+
+impl<'e> dyn DomainEvent + 'e {
+    pub fn downcast_ref<E: DomainEvent + 'static>(&self) -> Option<&E> {
+        self.as_any().downcast_ref()
+    }
+}
+
+/// A stored domain event as broadcast to every app instance once its
+/// transaction committed. `id` is the event's outbox entry.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Change {
+    pub id: i64,
+    pub aggregate_type: String,
+    pub aggregate: String,
+    pub event: String,
+    pub version: Version,
+    /// Left out when too large for a notification.
+    pub payload: Option<serde_json::Value>,
+}
+
+#[doc(hidden)]
+pub use serde_json as __serde_json;
+
+/// Implements [`DomainEvent`] for serializable structs with `id` and
+/// `version` fields, all belonging to the model named first.
+#[macro_export]
+macro_rules! domain_event {
+    ($model: ty: $($name: ident),+ $(,)?) => {
+        $(
+            impl $crate::event::DomainEvent for $name {
+                fn name(&self) -> &'static str {
+                    stringify!($name)
+                }
+
+                fn aggregate_type(&self) -> &'static str {
+                    <$model as $crate::model::Model>::NAME
+                }
+
+                fn aggregate(&self) -> String {
+                    self.id.value().to_string()
+                }
+
+                fn payload(&self) -> $crate::event::__serde_json::Value {
+                    $crate::event::__serde_json::to_value(self).expect("domain events serialize to JSON")
+                }
+
+                fn version(&self) -> $crate::vo::Version {
+                    self.version
+                }
+            }
+        )+
+    };
+}
+
+type Handler = Arc<dyn Fn(&dyn DomainEvent) + Send + Sync>;
+
+/// Delivers published events to in-process subscribers, in the order they
+/// subscribed. Handlers run synchronously on the publishing task and may
+/// subscribe further handlers, which see the next publish.
+#[derive(Default, Clone)]
+pub struct EventBus {
+    handlers: Arc<RwLock<Vec<Handler>>>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Calls `handler` for every event of type `E`.
+    pub fn subscribe<E: DomainEvent + 'static>(&self, handler: impl Fn(&E) + Send + Sync + 'static) {
+        self.subscribe_all(move |event| {
+            if let Some(event) = event.downcast_ref::<E>() {
+                handler(event)
+            }
+        });
+    }
+
+    /// Calls `handler` for every event.
+    pub fn subscribe_all(&self, handler: impl Fn(&dyn DomainEvent) + Send + Sync + 'static) {
+        self.handlers
+            .write()
+            .expect("event handlers poisoned")
+            .push(Arc::new(handler));
+    }
+
+    pub fn publish(&self, events: &[Arc<dyn DomainEvent>]) {
+        let handlers = self.handlers.read().expect("event handlers poisoned").clone();
+        for event in events {
+            for handler in &handlers {
+                handler(event.as_ref());
+            }
+        }
+    }
+}
+
+impl Debug for EventBus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EventBus").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use ulid::Ulid;
+
+    use super::*;
+    use crate::{model::Model, vo::ID};
+
+    struct Door;
+
+    impl Model for Door {
+        type ID = Ulid;
+        const NAME: &'static str = "Door";
+    }
+
+    #[derive(Debug, Serialize)]
+    struct DoorOpened {
+        id: ID<Door>,
+        version: Version,
+    }
+
+    #[derive(Debug, Serialize)]
+    struct DoorClosed {
+        id: ID<Door>,
+        version: Version,
+    }
+
+    crate::domain_event!(Door: DoorOpened, DoorClosed);
+
+    fn events() -> (ID<Door>, Vec<Arc<dyn DomainEvent>>) {
+        let (id, version) = (ID::new(Ulid::new()), Version::now(Ulid::nil()));
+        (id, vec![Arc::new(DoorOpened { id, version }), Arc::new(DoorClosed { id, version })])
+    }
+
+    #[test]
+    fn domain_events_describe_their_record() {
+        let (id, events) = events();
+        let opened = events[0].as_ref();
+        assert_eq!(opened.name(), "DoorOpened");
+        assert_eq!(opened.aggregate_type(), "Door");
+        assert_eq!(opened.aggregate(), id.value().to_string());
+        assert_eq!(opened.payload()["id"], serde_json::json!(id.value().to_string()));
+        assert!(opened.downcast_ref::<DoorOpened>().is_some());
+        assert!(opened.downcast_ref::<DoorClosed>().is_none());
+    }
+
+    #[test]
+    fn handlers_see_events_in_order_of_subscription() {
+        let bus = EventBus::new();
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        for label in ["first", "second"] {
+            let seen = seen.clone();
+            bus.subscribe_all(move |event| seen.lock().unwrap().push(format!("{label} {}", event.name())));
+        }
+        bus.publish(&events().1);
+        assert_eq!(
+            *seen.lock().unwrap(),
+            ["first DoorOpened", "second DoorOpened", "first DoorClosed", "second DoorClosed"]
+        );
+    }
+
+    #[test]
+    fn typed_handlers_only_see_their_events() {
+        let bus = EventBus::new();
+        let closed = Arc::new(Mutex::new(0));
+        let counter = closed.clone();
+        bus.subscribe(move |_: &DoorClosed| *counter.lock().unwrap() += 1);
+        bus.publish(&events().1);
+        assert_eq!(*closed.lock().unwrap(), 1);
+    }
+
+    #[test]
+    fn handlers_subscribed_while_publishing_see_the_next_publish() {
+        let bus = EventBus::new();
+        let seen = Arc::new(Mutex::new(0));
+        let (inner_bus, inner_seen) = (bus.clone(), seen.clone());
+        bus.subscribe(move |_: &DoorOpened| {
+            let seen = inner_seen.clone();
+            inner_bus.subscribe(move |_: &DoorClosed| *seen.lock().unwrap() += 1);
+        });
+        bus.publish(&events().1);
+        assert_eq!(*seen.lock().unwrap(), 0);
+        bus.publish(&events().1[1..]);
+        assert_eq!(*seen.lock().unwrap(), 1);
+    }
+}
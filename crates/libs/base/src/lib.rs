@@ -1,6 +1,8 @@
 pub mod model;
 pub mod vo;
 pub mod error;
+pub mod event;
+pub mod repository;
 pub mod source;
 
 pub use error::Error;
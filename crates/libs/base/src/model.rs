@@ -2,12 +2,17 @@ use std::{
     fmt::{Debug, Display},
     hash::Hash,
     str::FromStr,
+    sync::Arc,
 };
 
 use serde::{de::DeserializeOwned, Serialize};
 use ulid::Ulid;
 
-use crate::vo::{Identifiable, Version, ID};
+use crate::{
+    error::InvalidValue,
+    event::DomainEvent,
+    vo::{Identifiable, Version, ID},
+};
 
 pub trait Model {
     type ID: Serialize
@@ -33,17 +38,42 @@ pub trait RecordUpdate: Clone + PartialEq + Eq {
     fn new(model: &Self::Model, version: Version) -> Self;
 
     fn apply(self, state: &mut Self::Model);
+
+    /// Describes how `self` differs from `previous`, the snapshot taken
+    /// before the callback ran. A change no event can describe fails the
+    /// update before it is applied.
+    fn events(
+        &self,
+        _previous: &Self,
+        _id: &ID<Self::Model>,
+        _version: Version,
+    ) -> Result<Vec<Arc<dyn DomainEvent>>, InvalidValue> {
+        Ok(Vec::new())
+    }
 }
 
 pub struct Record<M: Model> {
     id: ID<M>,
     state: M,
     version: Version,
+    origin: Option<Version>,
+    events: Vec<Arc<dyn DomainEvent>>,
 }
 
 impl<M: Model> Record<M> {
     pub fn new(id: ID<M>, state: M, version: Version) -> Self {
-        Self { id, state, version }
+        Self { id, state, version, origin: None, events: Vec::new() }
+    }
+
+    /// A record as read from a source, remembering the version it had there.
+    pub fn loaded(id: ID<M>, state: M, version: Version) -> Self {
+        Self { id, state, version, origin: Some(version), events: Vec::new() }
+    }
+
+    /// The version the record had when it was read, `None` for a record
+    /// that has not been stored yet.
+    pub fn origin(&self) -> Option<Version> {
+        self.origin
     }
 
     pub fn id(&self) -> ID<M> {
@@ -58,29 +88,52 @@ impl<M: Model> Record<M> {
         self.version
     }
 
+    /// Events raised since the record was created or loaded.
+    pub fn events(&self) -> &[Arc<dyn DomainEvent>] {
+        &self.events
+    }
+
+    pub fn record_event(&mut self, event: impl DomainEvent + 'static) {
+        self.events.push(Arc::new(event));
+    }
+
+    pub fn take_events(&mut self) -> Vec<Arc<dyn DomainEvent>> {
+        std::mem::take(&mut self.events)
+    }
+
     pub fn update<U: RecordUpdate<Model = M>>(
         &mut self,
         author: Ulid,
-        callback: impl for<'r> Fn(&'r mut U) -> Result<(), crate::Error>,
+        callback: impl for<'r> FnOnce(&'r mut U) -> Result<(), crate::Error>,
     ) -> Result<bool, crate::Error> {
+        self.update_with(author, callback).map(|(changed, ())| changed)
+    }
+
+    /// Like [`Record::update`], also handing back what `callback` returned.
+    pub fn update_with<U: RecordUpdate<Model = M>, T>(
+        &mut self,
+        author: Ulid,
+        callback: impl for<'r> FnOnce(&'r mut U) -> Result<T, crate::Error>,
+    ) -> Result<(bool, T), crate::Error> {
         let version = Version::now(author);
         let state = U::new(self.state(), version);
         let mut update = state.clone();
 
-        callback(&mut update)?;
+        let output = callback(&mut update)?;
         if state != update {
+            self.events.extend(update.events(&state, &self.id, version)?);
             update.apply(&mut self.state);
             self.version = version;
-            Ok(true)
+            Ok((true, output))
         } else {
-            Ok(false)
+            Ok((false, output))
         }
     }
 
     pub async fn update_async<U: RecordUpdate<Model = M>, Fut: std::future::Future<Output = Result<(), crate::Error>>>(
         &mut self,
         author: Ulid,
-        callback: impl for<'r> Fn(&'r mut U) -> Fut,
+        callback: impl for<'r> FnOnce(&'r mut U) -> Fut,
     ) -> Result<bool, crate::Error> {
         let version = Version::now(author);
         let state = U::new(self.state(), version);
@@ -88,6 +141,7 @@ impl<M: Model> Record<M> {
 
         callback(&mut update).await?;
         if state != update {
+            self.events.extend(update.events(&state, &self.id, version)?);
             update.apply(&mut self.state);
             self.version = version;
             Ok(true)
@@ -0,0 +1,463 @@
+use std::marker::PhantomData;
+
+use ulid::Ulid;
+
+use crate::{
+    error::{InvalidValue, PersistenceError, VersionConflict},
+    event::EventBus,
+    model::{Model, Record, RecordUpdate},
+    source::{Read, Source, Transaction, Write, BMC},
+    vo::{Version, ID},
+};
+
+/// Whoever changes a record: stamped on its versions and, where the write
+/// BMCs check authorization, the subject of those checks.
+pub trait Author: Clone + Send + Sync {
+    fn id(&self) -> Ulid;
+}
+
+impl Author for Ulid {
+    fn id(&self) -> Ulid {
+        *self
+    }
+}
+
+/// A read model that carries everything needed to rebuild its record.
+pub trait View {
+    type Model: Model;
+
+    fn into_record(self) -> Record<Self::Model>;
+}
+
+/// Reads with the wrapped BMC and turns the view it finds into a record.
+pub struct Load<B>(pub B);
+
+impl<B, V> BMC for Load<B>
+where
+    B: BMC<Output = Option<V>>,
+    V: View,
+{
+    type Output = Option<Record<V::Model>>;
+}
+
+impl<S, B, V> Read<S> for Load<B>
+where
+    S: Source,
+    B: Read<S, Output = Option<V>>,
+    V: View + Send,
+{
+    fn read(
+        self,
+        connection: S::Connection<'_>,
+    ) -> impl std::future::Future<Output = Result<Self::Output, PersistenceError>> + Send {
+        let read = self.0.read(connection);
+        async move { Ok(read.await?.map(View::into_record)) }
+    }
+}
+
+/// Binds a [`Model`] to the BMCs that store it in `S`.
+pub trait Persist<S: Source>: Model + Sized {
+    type Author: Author;
+    type Get: Read<S, Output = Option<Record<Self>>>;
+    /// Reads the stored version and locks the row until the transaction ends.
+    type Lock: Read<S, Output = Option<Version>>;
+    type Save: Write<S>;
+    type Delete: Write<S>;
+
+    fn get(id: ID<Self>) -> Self::Get;
+
+    fn lock(id: ID<Self>) -> Self::Lock;
+
+    fn save(record: Record<Self>, author: Self::Author) -> Self::Save;
+
+    fn delete(id: ID<Self>, author: Self::Author) -> Self::Delete;
+}
+
+/// Loads and stores records of `M` without call sites spelling out BMCs.
+/// Saves are optimistic: they fail with [`VersionConflict`] when the stored
+/// version is not the one the record was read at. The events a saved record
+/// raised go to the outbox with it and are published on `events` once its
+/// transaction committed.
+pub struct Repository<'s, S, M> {
+    source: &'s S,
+    events: &'s EventBus,
+    model: PhantomData<fn() -> M>,
+}
+
+impl<'s, S: Source, M: Persist<S>> Repository<'s, S, M> {
+    pub fn new(source: &'s S, events: &'s EventBus) -> Self {
+        Self {
+            source,
+            events,
+            model: PhantomData,
+        }
+    }
+
+    pub async fn get(&self, id: ID<M>) -> Result<Option<Record<M>>, PersistenceError> {
+        self.source.read(M::get(id)).await
+    }
+
+    pub async fn save(&self, record: Record<M>, author: M::Author) -> crate::error::Result<()> {
+        let mut tx = self.source.begin().await?;
+        let stored = tx.read(M::lock(record.id())).await?;
+        if stored != record.origin() {
+            return Err(conflict::<M>(&record.id()).into());
+        }
+        let events = record.events().to_vec();
+        tx.write(M::save(record, author)).await?;
+        tx.commit().await?;
+        self.events.publish(&events);
+        Ok(())
+    }
+
+    pub async fn delete(&self, id: ID<M>, author: M::Author) -> crate::error::Result<()> {
+        self.source.write(M::delete(id, author)).await
+    }
+
+    /// Loads the record, applies `callback` through `U` and stores the result
+    /// if anything changed, all in one transaction. Returns the record as
+    /// stored afterwards along with what `callback` returned.
+    pub async fn modify<U: RecordUpdate<Model = M>, T>(
+        &self,
+        id: ID<M>,
+        author: M::Author,
+        callback: impl for<'r> FnOnce(&'r mut U) -> crate::error::Result<T>,
+    ) -> crate::error::Result<(Record<M>, T)> {
+        let mut tx = self.source.begin().await?;
+        let stored = tx
+            .read(M::lock(id.clone()))
+            .await?
+            .ok_or_else(|| not_found::<M>(&id))?;
+        let mut record = tx.read(M::get(id.clone())).await?.ok_or_else(|| not_found::<M>(&id))?;
+        if record.origin() != Some(stored) {
+            return Err(conflict::<M>(&id).into());
+        }
+        let (changed, output) = record.update_with(author.id(), callback)?;
+        if !changed {
+            return Ok((record, output));
+        }
+        let events = record.events().to_vec();
+        tx.write(M::save(record, author)).await?;
+        let record = tx.read(M::get(id.clone())).await?.ok_or_else(|| not_found::<M>(&id))?;
+        tx.commit().await?;
+        self.events.publish(&events);
+        Ok((record, output))
+    }
+}
+
+fn not_found<M: Model>(id: &ID<M>) -> InvalidValue {
+    InvalidValue::new(format!("{} {} (not found)", M::NAME, id.value()))
+}
+
+fn conflict<M: Model>(id: &ID<M>) -> VersionConflict {
+    VersionConflict::new(format!("{} {}", M::NAME, id.value()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        future::Future,
+        sync::{Arc, Mutex},
+    };
+
+    use futures_lite::future::block_on;
+    use serde::Serialize;
+
+    use super::*;
+    use crate::{error::Error, event::DomainEvent};
+
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    struct Counter {
+        value: i64,
+    }
+
+    impl Model for Counter {
+        type ID = Ulid;
+        const NAME: &'static str = "Counter";
+    }
+
+    #[derive(Debug, Serialize)]
+    struct Counted {
+        id: ID<Counter>,
+        value: i64,
+        version: Version,
+    }
+
+    crate::domain_event!(Counter: Counted);
+
+    #[derive(Clone, PartialEq, Eq)]
+    struct CounterUpdate {
+        value: i64,
+    }
+
+    impl RecordUpdate for CounterUpdate {
+        type Model = Counter;
+
+        fn new(model: &Counter, _version: Version) -> Self {
+            Self { value: model.value }
+        }
+
+        fn apply(self, state: &mut Counter) {
+            state.value = self.value;
+        }
+
+        fn events(
+            &self,
+            _previous: &Self,
+            id: &ID<Counter>,
+            version: Version,
+        ) -> Result<Vec<Arc<dyn DomainEvent>>, InvalidValue> {
+            Ok(vec![Arc::new(Counted { id: *id, value: self.value, version })])
+        }
+    }
+
+    type Rows = HashMap<Ulid, (Counter, Version)>;
+
+    /// Keeps counters in memory; a transaction works on a copy that replaces
+    /// the rows on commit.
+    #[derive(Default)]
+    struct Memory(Mutex<Rows>);
+
+    struct MemoryTransaction<'s> {
+        source: &'s Memory,
+        rows: Mutex<Rows>,
+    }
+
+    impl Source for Memory {
+        type Connection<'r> = &'r Mutex<Rows>;
+        type Transaction<'s> = MemoryTransaction<'s>;
+
+        fn read<B: Read<Self>>(&self, bmc: B) -> impl Future<Output = Result<B::Output, PersistenceError>> + Send {
+            bmc.read(&self.0)
+        }
+
+        fn write<B: Write<Self>>(&self, bmc: B) -> impl Future<Output = Result<(), Error>> + Send {
+            bmc.write(&self.0)
+        }
+
+        fn begin(&self) -> impl Future<Output = Result<MemoryTransaction<'_>, PersistenceError>> + Send {
+            let rows = Mutex::new(self.0.lock().unwrap().clone());
+            async move { Ok(MemoryTransaction { source: self, rows }) }
+        }
+    }
+
+    impl Transaction<Memory> for MemoryTransaction<'_> {
+        fn read<B: Read<Memory>>(&mut self, bmc: B) -> impl Future<Output = Result<B::Output, PersistenceError>> + Send {
+            bmc.read(&self.rows)
+        }
+
+        fn write<B: Write<Memory>>(&mut self, bmc: B) -> impl Future<Output = Result<(), Error>> + Send {
+            bmc.write(&self.rows)
+        }
+
+        fn commit(self) -> impl Future<Output = Result<(), PersistenceError>> + Send {
+            *self.source.0.lock().unwrap() = self.rows.into_inner().unwrap();
+            async { Ok(()) }
+        }
+    }
+
+    struct GetCounter(ID<Counter>);
+
+    impl BMC for GetCounter {
+        type Output = Option<Record<Counter>>;
+    }
+
+    impl Read<Memory> for GetCounter {
+        fn read(
+            self,
+            rows: <Memory as Source>::Connection<'_>,
+        ) -> impl Future<Output = Result<Self::Output, PersistenceError>> + Send {
+            let record = rows
+                .lock()
+                .unwrap()
+                .get(&self.0.value())
+                .map(|(state, version)| Record::loaded(self.0, state.clone(), *version));
+            async move { Ok(record) }
+        }
+    }
+
+    struct LockCounter(ID<Counter>);
+
+    impl BMC for LockCounter {
+        type Output = Option<Version>;
+    }
+
+    impl Read<Memory> for LockCounter {
+        fn read(
+            self,
+            rows: <Memory as Source>::Connection<'_>,
+        ) -> impl Future<Output = Result<Self::Output, PersistenceError>> + Send {
+            let version = rows.lock().unwrap().get(&self.0.value()).map(|(_, version)| *version);
+            async move { Ok(version) }
+        }
+    }
+
+    struct SaveCounter(Record<Counter>);
+
+    impl Write<Memory> for SaveCounter {
+        fn write(self, rows: <Memory as Source>::Connection<'_>) -> impl Future<Output = Result<(), Error>> + Send {
+            let row = (self.0.state().clone(), self.0.version());
+            rows.lock().unwrap().insert(self.0.id().value(), row);
+            async { Ok(()) }
+        }
+    }
+
+    struct DeleteCounter(ID<Counter>);
+
+    impl Write<Memory> for DeleteCounter {
+        fn write(self, rows: <Memory as Source>::Connection<'_>) -> impl Future<Output = Result<(), Error>> + Send {
+            rows.lock().unwrap().remove(&self.0.value());
+            async { Ok(()) }
+        }
+    }
+
+    impl Persist<Memory> for Counter {
+        type Author = Ulid;
+        type Get = GetCounter;
+        type Lock = LockCounter;
+        type Save = SaveCounter;
+        type Delete = DeleteCounter;
+
+        fn get(id: ID<Self>) -> GetCounter {
+            GetCounter(id)
+        }
+
+        fn lock(id: ID<Self>) -> LockCounter {
+            LockCounter(id)
+        }
+
+        fn save(record: Record<Self>, _author: Ulid) -> SaveCounter {
+            SaveCounter(record)
+        }
+
+        fn delete(id: ID<Self>, _author: Ulid) -> DeleteCounter {
+            DeleteCounter(id)
+        }
+    }
+
+    /// Collects the values of the published [`Counted`] events.
+    fn counted(events: &EventBus) -> Arc<Mutex<Vec<i64>>> {
+        let values = Arc::new(Mutex::new(Vec::new()));
+        let collected = values.clone();
+        events.subscribe(move |event: &Counted| collected.lock().unwrap().push(event.value));
+        values
+    }
+
+    fn counter(value: i64) -> Record<Counter> {
+        Record::new(ID::new(Ulid::new()), Counter { value }, Version::now(Ulid::new()))
+    }
+
+    #[test]
+    fn saved_records_can_be_read_back_and_publish_their_events() {
+        let (source, events) = (Memory::default(), EventBus::new());
+        let published = counted(&events);
+        let repository = Repository::<_, Counter>::new(&source, &events);
+        let mut record = counter(1);
+        let id = record.id();
+        record.update(Ulid::new(), |update: &mut CounterUpdate| {
+            update.value = 2;
+            Ok(())
+        })
+        .unwrap();
+        block_on(repository.save(record, Ulid::new())).unwrap();
+        let stored = block_on(repository.get(id)).unwrap().unwrap();
+        assert_eq!(stored.state().value, 2);
+        assert!(stored.origin().is_some());
+        assert_eq!(*published.lock().unwrap(), [2]);
+    }
+
+    #[test]
+    fn saving_a_stale_record_conflicts() {
+        let (source, events) = (Memory::default(), EventBus::new());
+        let repository = Repository::<_, Counter>::new(&source, &events);
+        let record = counter(1);
+        let id = record.id();
+        block_on(repository.save(record, Ulid::new())).unwrap();
+        let mut first = block_on(repository.get(id)).unwrap().unwrap();
+        let mut second = block_on(repository.get(id)).unwrap().unwrap();
+        for (record, value) in [(&mut first, 2), (&mut second, 3)] {
+            record.update(Ulid::new(), |update: &mut CounterUpdate| {
+                update.value = value;
+                Ok(())
+            })
+            .unwrap();
+        }
+        block_on(repository.save(first, Ulid::new())).unwrap();
+        let error = block_on(repository.save(second, Ulid::new())).unwrap_err();
+        assert!(matches!(error, Error::VersionConflict(_)));
+        assert_eq!(block_on(repository.get(id)).unwrap().unwrap().state().value, 2);
+    }
+
+    #[test]
+    fn new_records_cannot_replace_stored_ones() {
+        let (source, events) = (Memory::default(), EventBus::new());
+        let repository = Repository::<_, Counter>::new(&source, &events);
+        let record = counter(1);
+        let duplicate = Record::new(record.id(), Counter { value: 5 }, Version::now(Ulid::new()));
+        block_on(repository.save(record, Ulid::new())).unwrap();
+        assert!(block_on(repository.save(duplicate, Ulid::new())).is_err());
+    }
+
+    #[test]
+    fn modify_stores_the_change_and_returns_the_callback_value() {
+        let (source, events) = (Memory::default(), EventBus::new());
+        let published = counted(&events);
+        let repository = Repository::<_, Counter>::new(&source, &events);
+        let record = counter(1);
+        let id = record.id();
+        block_on(repository.save(record, Ulid::new())).unwrap();
+        let (stored, previous) = block_on(repository.modify(id, Ulid::new(), |update: &mut CounterUpdate| {
+            let previous = update.value;
+            update.value += 10;
+            Ok(previous)
+        }))
+        .unwrap();
+        assert_eq!((stored.state().value, previous), (11, 1));
+        assert_eq!(block_on(repository.get(id)).unwrap().unwrap().state().value, 11);
+        assert_eq!(*published.lock().unwrap(), [11]);
+    }
+
+    #[test]
+    fn modify_without_a_change_writes_nothing() {
+        let (source, events) = (Memory::default(), EventBus::new());
+        let published = counted(&events);
+        let repository = Repository::<_, Counter>::new(&source, &events);
+        let record = counter(1);
+        let (id, version) = (record.id(), record.version());
+        block_on(repository.save(record, Ulid::new())).unwrap();
+        let (stored, ()) = block_on(repository.modify(id, Ulid::new(), |_: &mut CounterUpdate| Ok(()))).unwrap();
+        assert_eq!(stored.version(), version);
+        assert!(published.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn modify_fails_for_missing_records_and_failed_callbacks() {
+        let (source, events) = (Memory::default(), EventBus::new());
+        let published = counted(&events);
+        let repository = Repository::<_, Counter>::new(&source, &events);
+        let missing = block_on(repository.modify(ID::new(Ulid::new()), Ulid::new(), |_: &mut CounterUpdate| Ok(())));
+        assert!(matches!(missing, Err(Error::InvalidValue(_))));
+        let record = counter(1);
+        let id = record.id();
+        block_on(repository.save(record, Ulid::new())).unwrap();
+        let failed = block_on(repository.modify(id, Ulid::new(), |update: &mut CounterUpdate| {
+            update.value = 5;
+            Err::<(), _>(InvalidValue::new("Counter").into())
+        }));
+        assert!(failed.is_err());
+        assert_eq!(block_on(repository.get(id)).unwrap().unwrap().state().value, 1);
+        assert!(published.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn deleted_records_are_gone() {
+        let (source, events) = (Memory::default(), EventBus::new());
+        let repository = Repository::<_, Counter>::new(&source, &events);
+        let record = counter(1);
+        let id = record.id();
+        block_on(repository.save(record, Ulid::new())).unwrap();
+        block_on(repository.delete(id, Ulid::new())).unwrap();
+        assert!(block_on(repository.get(id)).unwrap().is_none());
+    }
+}
@@ -1,10 +1,19 @@
 use crate::error::PersistenceError;
 
+mod corrupt;
+mod maintenance;
+mod outbox;
 mod postgres;
+pub use corrupt::*;
+pub use maintenance::*;
+pub use outbox::*;
 pub use postgres::*;
 
 pub trait Source: Sized {
     type Connection<'r>: 'r;
+    type Transaction<'s>: Transaction<Self>
+    where
+        Self: 's;
 
     fn read<BMC: Read<Self>>(
         &self,
@@ -13,7 +22,22 @@ pub trait Source: Sized {
     fn write<BMC: Write<Self>>(
         &self,
         bmc: BMC,
-    ) -> impl std::future::Future<Output = Result<(), PersistenceError>> + Send;
+    ) -> impl std::future::Future<Output = Result<(), crate::Error>> + Send;
+    /// Starts a transaction in which several BMCs can run before committing.
+    fn begin(&self) -> impl std::future::Future<Output = Result<Self::Transaction<'_>, PersistenceError>> + Send;
+}
+
+/// Work on a [`Source`] that is rolled back unless committed.
+pub trait Transaction<S: Source>: Send {
+    fn read<BMC: Read<S>>(
+        &mut self,
+        bmc: BMC,
+    ) -> impl std::future::Future<Output = Result<BMC::Output, PersistenceError>> + Send;
+    fn write<BMC: Write<S>>(
+        &mut self,
+        bmc: BMC,
+    ) -> impl std::future::Future<Output = Result<(), crate::Error>> + Send;
+    fn commit(self) -> impl std::future::Future<Output = Result<(), PersistenceError>> + Send;
 }
 
 pub trait BMC {
@@ -31,5 +55,5 @@ pub trait Write<S: Source>: Send {
     fn write(
         self,
         connection: S::Connection<'_>,
-    ) -> impl std::future::Future<Output = Result<(), PersistenceError>> + Send;
+    ) -> impl std::future::Future<Output = Result<(), crate::Error>> + Send;
 }
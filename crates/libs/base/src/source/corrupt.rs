@@ -0,0 +1,27 @@
+use serde::Serialize;
+use ulid::Ulid;
+
+use super::BMC;
+
+/// A row that failed validation when it was read. It stays hidden from
+/// every query until it is repaired.
+#[derive(Debug, Clone, Serialize)]
+pub struct CorruptRecord {
+    pub id: Ulid,
+    pub model: String,
+    pub description: String,
+}
+
+pub struct ListCorruptRecords {
+    pub model: Option<String>,
+}
+
+impl BMC for ListCorruptRecords {
+    type Output = Vec<CorruptRecord>;
+}
+
+/// Clears the mark on a row so that it is validated again on its next read.
+/// Rows that are still invalid get marked again.
+pub struct RepairCorruptRecord {
+    pub id: Ulid,
+}
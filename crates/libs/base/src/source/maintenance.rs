@@ -0,0 +1,29 @@
+use chrono::NaiveDateTime;
+use serde::Serialize;
+
+use super::BMC;
+
+/// What one maintenance task did on one run.
+#[derive(Debug, Clone, Serialize)]
+pub struct MaintenanceRun {
+    pub id: i64,
+    pub task: String,
+    pub affected: i64,
+    pub ran_at: NaiveDateTime,
+}
+
+/// Logs a run of a task that isn't itself a write BMC.
+pub struct RecordMaintenanceRun {
+    pub task: String,
+    pub affected: i64,
+}
+
+/// The most recent runs, newest first.
+pub struct ListMaintenanceRuns {
+    pub task: Option<String>,
+    pub limit: i64,
+}
+
+impl BMC for ListMaintenanceRuns {
+    type Output = Vec<MaintenanceRun>;
+}
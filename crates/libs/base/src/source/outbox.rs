@@ -0,0 +1,168 @@
+use std::time::Duration;
+
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use ulid::Ulid;
+
+use crate::{error::InvalidValue, event::Change, vo::Version};
+
+use super::BMC;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[repr(i16)]
+pub enum OutboxStatus {
+    Pending = 0,
+    Delivered = 1,
+    /// Gave up after too many failed attempts.
+    Dead = 2,
+}
+
+impl TryFrom<i16> for OutboxStatus {
+    type Error = InvalidValue;
+
+    fn try_from(value: i16) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(OutboxStatus::Pending),
+            1 => Ok(OutboxStatus::Delivered),
+            2 => Ok(OutboxStatus::Dead),
+            _ => Err(InvalidValue::new(format!("OutboxStatus ({value})"))),
+        }
+    }
+}
+
+/// A domain event waiting to be delivered, or the record of its delivery.
+#[derive(Debug, Clone, Serialize)]
+pub struct OutboxEntry {
+    pub id: i64,
+    pub aggregate_type: String,
+    pub aggregate_id: String,
+    pub event: String,
+    pub payload: serde_json::Value,
+    pub version: Version,
+    pub status: OutboxStatus,
+    pub attempts: i32,
+    pub next_attempt_at: NaiveDateTime,
+    pub last_error: Option<String>,
+}
+
+/// Where an entry goes to within one target, and how that went so far.
+#[derive(Debug, Clone, Serialize)]
+pub struct OutboxDelivery {
+    pub outbox_id: i64,
+    pub target: String,
+    /// Empty for targets with a single receiver.
+    pub recipient: String,
+    pub status: OutboxStatus,
+    pub attempts: i32,
+    pub next_attempt_at: NaiveDateTime,
+    pub last_error: Option<String>,
+}
+
+/// Leases up to `limit` due entries for `lease` under `claim`, taking only
+/// the oldest pending entry of each aggregate so that aggregates are
+/// delivered in order. [`ClaimedOutbox`] reads them back.
+pub struct ClaimOutbox {
+    pub claim: Ulid,
+    pub limit: i64,
+    pub lease: Duration,
+}
+
+/// The entries leased under `claim`, oldest first.
+pub struct ClaimedOutbox {
+    pub claim: Ulid,
+}
+
+impl BMC for ClaimedOutbox {
+    type Output = Vec<OutboxEntry>;
+}
+
+/// Starts tracking the delivery of an entry to each of `recipients`, given
+/// as target and recipient; those already tracked are left alone.
+pub struct AddOutboxDeliveries {
+    pub id: i64,
+    pub recipients: Vec<(String, String)>,
+}
+
+pub struct ListOutboxDeliveries {
+    pub id: i64,
+}
+
+impl BMC for ListOutboxDeliveries {
+    type Output = Vec<OutboxDelivery>;
+}
+
+pub struct MarkOutboxDelivered {
+    pub id: i64,
+    pub target: String,
+    pub recipient: String,
+}
+
+/// Records a failed attempt; without `retry_at` the delivery becomes a dead
+/// letter.
+pub struct MarkOutboxFailed {
+    pub id: i64,
+    pub target: String,
+    pub recipient: String,
+    pub error: String,
+    pub retry_at: Option<NaiveDateTime>,
+}
+
+/// Ends a dispatch round of the entry and releases its lease. The entry is
+/// delivered once every delivery is, and a dead letter once none is pending
+/// but some are dead; it is due again with its earliest pending delivery.
+pub struct SettleOutbox {
+    pub id: i64,
+}
+
+pub struct ListOutbox {
+    pub status: Option<OutboxStatus>,
+    pub limit: i64,
+}
+
+impl BMC for ListOutbox {
+    type Output = Vec<OutboxEntry>;
+}
+
+/// Up to `limit` entries stored after the entry `after`, oldest first, as
+/// the [`Change`]s they were notified as. Entries pruned meanwhile are gone.
+pub struct ChangesSince {
+    pub after: i64,
+    pub limit: i64,
+}
+
+impl BMC for ChangesSince {
+    type Output = Vec<Change>;
+}
+
+/// Puts a dead letter and its dead deliveries back in the queue for
+/// immediate delivery.
+pub struct RequeueOutbox {
+    pub id: i64,
+}
+
+/// Deletes entries delivered more than `ttl` ago.
+pub struct PruneOutbox {
+    pub ttl: Duration,
+}
+
+impl PruneOutbox {
+    pub const TASK: &'static str = "prune outbox";
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn outbox_status_reads_back_from_its_stored_value() {
+        for status in [OutboxStatus::Pending, OutboxStatus::Delivered, OutboxStatus::Dead] {
+            assert_eq!(OutboxStatus::try_from(status as i16).unwrap(), status);
+        }
+    }
+
+    #[test]
+    fn unknown_outbox_status_is_rejected_instead_of_pending() {
+        assert!(OutboxStatus::try_from(3).is_err());
+        assert!(OutboxStatus::try_from(-1).is_err());
+    }
+}
@@ -1,20 +1,65 @@
+use std::{sync::Arc, time::Duration};
+
 use chrono::NaiveDateTime;
 use futures_lite::FutureExt;
-use sqlx::{postgres::PgPoolOptions, types::Uuid, PgConnection, PgPool};
+use sqlx::{
+    postgres::{PgListener, PgPoolOptions},
+    types::{Json, Uuid},
+    PgConnection, PgPool, Postgres,
+};
 use ulid::Ulid;
 
 use crate::{
     error::{InvalidValue, PersistenceError},
+    event::{Change, DomainEvent},
     vo::Version,
 };
 
-use super::Source;
+use super::{
+    AddOutboxDeliveries, ChangesSince, ClaimOutbox, ClaimedOutbox, CorruptRecord, ListCorruptRecords, ListMaintenanceRuns,
+    ListOutbox, ListOutboxDeliveries, MaintenanceRun, MarkOutboxDelivered, MarkOutboxFailed, OutboxDelivery,
+    OutboxEntry, OutboxStatus, PruneOutbox, Read, RecordMaintenanceRun, RepairCorruptRecord, RequeueOutbox,
+    SettleOutbox, Source, Transaction, Write,
+};
 
 pub struct PgSource(PgPool);
 
+/// Sizing and timeouts of the connection pool behind a [`PgSource`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PoolOptions {
+    pub max_connections: u32,
+    pub min_connections: u32,
+    pub acquire_timeout: Duration,
+    pub idle_timeout: Option<Duration>,
+    pub max_lifetime: Option<Duration>,
+}
+
+impl Default for PoolOptions {
+    fn default() -> Self {
+        Self {
+            max_connections: 5,
+            min_connections: 0,
+            acquire_timeout: Duration::from_secs(30),
+            idle_timeout: Some(Duration::from_secs(10 * 60)),
+            max_lifetime: Some(Duration::from_secs(30 * 60)),
+        }
+    }
+}
+
 impl PgSource {
     pub async fn new(url: &str) -> Result<Self, PersistenceError> {
-        let pool = PgPoolOptions::new().max_connections(5).connect(url).await?;
+        Self::connect(url, &PoolOptions::default()).await
+    }
+
+    pub async fn connect(url: &str, options: &PoolOptions) -> Result<Self, PersistenceError> {
+        let pool = PgPoolOptions::new()
+            .max_connections(options.max_connections)
+            .min_connections(options.min_connections)
+            .acquire_timeout(options.acquire_timeout)
+            .idle_timeout(options.idle_timeout)
+            .max_lifetime(options.max_lifetime)
+            .connect(url)
+            .await?;
         Ok(PgSource(pool))
     }
 
@@ -24,10 +69,23 @@ impl PgSource {
         tx.commit().await?;
         Ok(())
     }
+
+    /// Listens for the [`Change`]s committed by any instance.
+    pub async fn changes(&self) -> Result<ChangeListener, PersistenceError> {
+        let mut listener = PgListener::connect_with(&self.0).await?;
+        listener.listen(CHANGES_CHANNEL).await?;
+        Ok(ChangeListener(listener))
+    }
+
+    /// Applies the pending migrations of `migrator`.
+    pub async fn migrate(&self, migrator: &sqlx::migrate::Migrator) -> Result<(), PersistenceError> {
+        migrator.run(&self.0).await.map_err(|error| PersistenceError::new(error.to_string()))
+    }
 }
 
 impl Source for PgSource {
     type Connection<'r> = &'r mut PgConnection;
+    type Transaction<'s> = PgTransaction<'s>;
 
     async fn read<BMC: super::Read<Self> + Send>(
         &self,
@@ -37,12 +95,34 @@ impl Source for PgSource {
         bmc.read(&mut connection).boxed().await
     }
 
-    async fn write<BMC: super::Write<Self>>(&self, bmc: BMC) -> Result<(), PersistenceError> {
+    async fn write<BMC: super::Write<Self>>(&self, bmc: BMC) -> crate::error::Result<()> {
         let mut tx = self.0.begin().await?;
         bmc.write(&mut tx).boxed().await?;
         tx.commit().await?;
         Ok(())
     }
+
+    async fn begin(&self) -> Result<PgTransaction<'_>, PersistenceError> {
+        Ok(PgTransaction(self.0.begin().await?))
+    }
+}
+
+/// A transaction on a [`PgSource`], rolled back unless committed.
+pub struct PgTransaction<'s>(sqlx::Transaction<'s, Postgres>);
+
+impl Transaction<PgSource> for PgTransaction<'_> {
+    async fn read<BMC: Read<PgSource>>(&mut self, bmc: BMC) -> Result<BMC::Output, PersistenceError> {
+        bmc.read(&mut self.0).boxed().await
+    }
+
+    async fn write<BMC: Write<PgSource>>(&mut self, bmc: BMC) -> crate::error::Result<()> {
+        bmc.write(&mut self.0).boxed().await
+    }
+
+    async fn commit(self) -> Result<(), PersistenceError> {
+        self.0.commit().await?;
+        Ok(())
+    }
 }
 
 pub async fn add_corrupt_record(connection: &mut PgConnection, id: Uuid, model: &'static str, error: InvalidValue) -> Result<(), PersistenceError> {
@@ -58,6 +138,506 @@ pub async fn add_corrupt_record(connection: &mut PgConnection, id: Uuid, model:
     Ok(())
 }
 
+impl Read<PgSource> for ListCorruptRecords {
+    async fn read(self, connection: <PgSource as Source>::Connection<'_>) -> Result<Self::Output, PersistenceError> {
+        let rows = sqlx::query!(
+            r#"
+            select id, model, description from corrupt_record
+            where model is not distinct from coalesce($1, model)
+            order by model, id;
+            "#,
+            self.model
+        )
+        .fetch_all(connection)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| CorruptRecord {
+                id: Ulid::from(row.id),
+                model: row.model,
+                description: row.description,
+            })
+            .collect())
+    }
+}
+
+impl Write<PgSource> for RepairCorruptRecord {
+    async fn write(self, connection: <PgSource as Source>::Connection<'_>) -> crate::error::Result<()> {
+        sqlx::query!(
+            r#"
+            delete from corrupt_record where id = $1;
+            "#,
+            Uuid::from(self.id)
+        )
+        .execute(connection)
+        .await?;
+        Ok(())
+    }
+}
+
+/// Logs that a maintenance `task` affected `affected` rows. Sweeping write
+/// BMCs call it on their own connection so the log commits with the sweep.
+pub async fn add_maintenance_run(connection: &mut PgConnection, task: &str, affected: u64) -> Result<(), PersistenceError> {
+    sqlx::query!(
+        r#"
+        insert into core.maintenance_run (task, affected) values ($1, $2);
+        "#,
+        task,
+        affected as i64
+    )
+    .execute(connection)
+    .await?;
+    Ok(())
+}
+
+impl Write<PgSource> for RecordMaintenanceRun {
+    async fn write(self, connection: <PgSource as Source>::Connection<'_>) -> crate::error::Result<()> {
+        add_maintenance_run(connection, &self.task, self.affected.max(0) as u64).await?;
+        Ok(())
+    }
+}
+
+impl Read<PgSource> for ListMaintenanceRuns {
+    async fn read(self, connection: <PgSource as Source>::Connection<'_>) -> Result<Self::Output, PersistenceError> {
+        let runs = sqlx::query_as!(
+            MaintenanceRun,
+            r#"
+            select id, task, affected, ran_at from core.maintenance_run
+            where task is not distinct from coalesce($1, task)
+            order by id desc
+            limit $2;
+            "#,
+            self.task,
+            self.limit
+        )
+        .fetch_all(connection)
+        .await?;
+        Ok(runs)
+    }
+}
+
+/// Channel the committed [`Change`]s are notified on.
+const CHANGES_CHANNEL: &str = "gnify_changes";
+
+/// Postgres rejects notification payloads of 8000 bytes or more.
+const MAX_NOTIFICATION: usize = 7900;
+
+/// Stores `events` in the outbox on `connection` and notifies them as
+/// [`Change`]s. Write BMCs call it with the events of the record they store
+/// so that all of it commits together; notifications are only sent on commit.
+pub async fn add_outbox_entries(connection: &mut PgConnection, events: &[Arc<dyn DomainEvent>]) -> Result<(), PersistenceError> {
+    for event in events {
+        let payload = event.payload();
+        let id = sqlx::query_scalar!(
+            r#"
+            insert into core.outbox (aggregate_type, aggregate_id, event, payload, version)
+            values ($1, $2, $3, $4, $5::version)
+            returning id;
+            "#,
+            event.aggregate_type(),
+            event.aggregate(),
+            event.name(),
+            payload,
+            RecordVersion::from(event.version()) as RecordVersion
+        )
+        .fetch_one(&mut *connection)
+        .await?;
+        let mut change = Change {
+            id,
+            aggregate_type: event.aggregate_type().to_string(),
+            aggregate: event.aggregate(),
+            event: event.name().to_string(),
+            version: event.version(),
+            payload: Some(payload),
+        };
+        let mut notification = serde_json::to_string(&change).map_err(|error| PersistenceError::new(error.to_string()))?;
+        if notification.len() >= MAX_NOTIFICATION {
+            change.payload = None;
+            notification = serde_json::to_string(&change).map_err(|error| PersistenceError::new(error.to_string()))?;
+        }
+        sqlx::query!(
+            r#"
+            select pg_notify($1, $2);
+            "#,
+            CHANGES_CHANNEL,
+            notification
+        )
+        .execute(&mut *connection)
+        .await?;
+    }
+    Ok(())
+}
+
+/// A dedicated connection receiving the [`Change`]s notified on commit.
+pub struct ChangeListener(PgListener);
+
+impl ChangeListener {
+    /// Waits for the next change. Notifications sent while the connection
+    /// was lost are missed; the outbox still holds them.
+    pub async fn next(&mut self) -> Result<Change, PersistenceError> {
+        let notification = self.0.recv().await?;
+        serde_json::from_str(notification.payload()).map_err(|error| PersistenceError::new(error.to_string()))
+    }
+}
+
+struct OutboxRow {
+    id: i64,
+    aggregate_type: String,
+    aggregate_id: String,
+    event: String,
+    payload: Json<serde_json::Value>,
+    version: RecordVersion,
+    status: i16,
+    attempts: i32,
+    next_attempt_at: NaiveDateTime,
+    last_error: Option<String>,
+}
+
+impl TryFrom<OutboxRow> for OutboxEntry {
+    type Error = InvalidValue;
+
+    fn try_from(row: OutboxRow) -> Result<Self, Self::Error> {
+        Ok(OutboxEntry {
+            id: row.id,
+            aggregate_type: row.aggregate_type,
+            aggregate_id: row.aggregate_id,
+            event: row.event,
+            payload: row.payload.0,
+            version: row.version.try_into()?,
+            status: row.status.try_into()?,
+            attempts: row.attempts,
+            next_attempt_at: row.next_attempt_at,
+            last_error: row.last_error,
+        })
+    }
+}
+
+/// Corrupt records are keyed by UUID; an outbox entry's is made from its id.
+fn outbox_record_id(id: i64) -> Uuid {
+    Uuid::from_u64_pair(0, id as u64)
+}
+
+/// Turns rows into entries, recording those that fail validation as corrupt
+/// and leaving them out.
+async fn outbox_entries(connection: &mut PgConnection, rows: Vec<OutboxRow>) -> Result<Vec<OutboxEntry>, PersistenceError> {
+    let mut entries = Vec::with_capacity(rows.len());
+    for row in rows {
+        let id = row.id;
+        match OutboxEntry::try_from(row) {
+            Ok(entry) => entries.push(entry),
+            Err(iv) => add_corrupt_record(connection, outbox_record_id(id), "core.outbox", iv).await?,
+        }
+    }
+    Ok(entries)
+}
+
+impl Write<PgSource> for ClaimOutbox {
+    async fn write(self, connection: <PgSource as Source>::Connection<'_>) -> crate::error::Result<()> {
+        let now = chrono::Utc::now().naive_utc();
+        sqlx::query!(
+            r#"
+            with head as (
+                select distinct on (aggregate_type, aggregate_id) id, next_attempt_at
+                from core.outbox
+                where status = 0
+                order by aggregate_type, aggregate_id, id
+            ), due as (
+                select o.id from core.outbox o join head h on h.id = o.id
+                where h.next_attempt_at <= $1
+                order by o.id
+                limit $2
+                for update of o skip locked
+            )
+            update core.outbox o set next_attempt_at = $3, claim = $4
+            from due where o.id = due.id;
+            "#,
+            now,
+            self.limit,
+            now + self.lease,
+            Uuid::from(self.claim)
+        )
+        .execute(connection)
+        .await?;
+        Ok(())
+    }
+}
+
+impl Read<PgSource> for ClaimedOutbox {
+    async fn read(self, connection: <PgSource as Source>::Connection<'_>) -> Result<Self::Output, PersistenceError> {
+        let rows = sqlx::query_as!(
+            OutboxRow,
+            r#"
+            select
+                  id
+                , aggregate_type
+                , aggregate_id
+                , event
+                , payload as "payload: Json<serde_json::Value>"
+                , version as "version: RecordVersion"
+                , status
+                , attempts
+                , next_attempt_at
+                , last_error
+            from core.outbox
+            where claim = $1
+            order by id;
+            "#,
+            Uuid::from(self.claim)
+        )
+        .fetch_all(&mut *connection)
+        .await?;
+        outbox_entries(connection, rows).await
+    }
+}
+
+impl Write<PgSource> for AddOutboxDeliveries {
+    async fn write(self, connection: <PgSource as Source>::Connection<'_>) -> crate::error::Result<()> {
+        let (targets, recipients): (Vec<String>, Vec<String>) = self.recipients.into_iter().unzip();
+        sqlx::query!(
+            r#"
+            insert into core.outbox_delivery (outbox_id, target, recipient)
+            select $1, * from unnest($2::text[], $3::text[])
+            on conflict do nothing;
+            "#,
+            self.id,
+            &targets[..],
+            &recipients[..]
+        )
+        .execute(connection)
+        .await?;
+        Ok(())
+    }
+}
+
+impl Read<PgSource> for ListOutboxDeliveries {
+    async fn read(self, connection: <PgSource as Source>::Connection<'_>) -> Result<Self::Output, PersistenceError> {
+        let rows = sqlx::query!(
+            r#"
+            select target, recipient, status, attempts, next_attempt_at, last_error
+            from core.outbox_delivery
+            where outbox_id = $1
+            order by target, recipient;
+            "#,
+            self.id
+        )
+        .fetch_all(&mut *connection)
+        .await?;
+        let mut deliveries = Vec::with_capacity(rows.len());
+        for row in rows {
+            match OutboxStatus::try_from(row.status) {
+                Ok(status) => deliveries.push(OutboxDelivery {
+                    outbox_id: self.id,
+                    target: row.target,
+                    recipient: row.recipient,
+                    status,
+                    attempts: row.attempts,
+                    next_attempt_at: row.next_attempt_at,
+                    last_error: row.last_error,
+                }),
+                Err(iv) => {
+                    let iv = InvalidValue::new(format!("{iv} of the delivery to {} {}", row.target, row.recipient));
+                    add_corrupt_record(connection, outbox_record_id(self.id), "core.outbox_delivery", iv).await?
+                }
+            }
+        }
+        Ok(deliveries)
+    }
+}
+
+impl Write<PgSource> for MarkOutboxDelivered {
+    async fn write(self, connection: <PgSource as Source>::Connection<'_>) -> crate::error::Result<()> {
+        sqlx::query!(
+            r#"
+            update core.outbox_delivery set status = 1, attempts = attempts + 1, last_error = null, delivered_at = $4
+            where outbox_id = $1 and target = $2 and recipient = $3;
+            "#,
+            self.id,
+            self.target,
+            self.recipient,
+            chrono::Utc::now().naive_utc()
+        )
+        .execute(connection)
+        .await?;
+        Ok(())
+    }
+}
+
+impl Write<PgSource> for MarkOutboxFailed {
+    async fn write(self, connection: <PgSource as Source>::Connection<'_>) -> crate::error::Result<()> {
+        let status = match self.retry_at {
+            Some(_) => OutboxStatus::Pending,
+            None => OutboxStatus::Dead,
+        } as i16;
+        sqlx::query!(
+            r#"
+            update core.outbox_delivery set
+                status = $4,
+                attempts = attempts + 1,
+                last_error = $5,
+                next_attempt_at = coalesce($6, next_attempt_at)
+            where outbox_id = $1 and target = $2 and recipient = $3;
+            "#,
+            self.id,
+            self.target,
+            self.recipient,
+            status,
+            self.error,
+            self.retry_at
+        )
+        .execute(connection)
+        .await?;
+        Ok(())
+    }
+}
+
+impl Write<PgSource> for SettleOutbox {
+    async fn write(self, connection: <PgSource as Source>::Connection<'_>) -> crate::error::Result<()> {
+        sqlx::query!(
+            r#"
+            with d as (
+                select
+                      count(*) filter (where status = 0) as pending
+                    , count(*) filter (where status not in (0, 1)) as dead
+                    , min(next_attempt_at) filter (where status = 0) as next_attempt_at
+                    , string_agg(
+                        concat_ws(' ', target, nullif(recipient, '')) || ': ' || last_error,
+                        '; ' order by target, recipient
+                    ) filter (where status <> 1) as last_error
+                from core.outbox_delivery
+                where outbox_id = $1
+            )
+            update core.outbox o set
+                status = case when d.pending > 0 then 0 when d.dead > 0 then 2 else 1 end,
+                attempts = o.attempts + 1,
+                next_attempt_at = coalesce(d.next_attempt_at, o.next_attempt_at),
+                last_error = d.last_error,
+                delivered_at = case when d.pending = 0 and d.dead = 0 then $2::timestamp end,
+                claim = null
+            from d
+            where o.id = $1;
+            "#,
+            self.id,
+            chrono::Utc::now().naive_utc()
+        )
+        .execute(connection)
+        .await?;
+        Ok(())
+    }
+}
+
+impl Read<PgSource> for ListOutbox {
+    async fn read(self, connection: <PgSource as Source>::Connection<'_>) -> Result<Self::Output, PersistenceError> {
+        let rows = sqlx::query_as!(
+            OutboxRow,
+            r#"
+            select
+                  id
+                , aggregate_type
+                , aggregate_id
+                , event
+                , payload as "payload: Json<serde_json::Value>"
+                , version as "version: RecordVersion"
+                , status
+                , attempts
+                , next_attempt_at
+                , last_error
+            from core.outbox
+            where status is not distinct from coalesce($1, status)
+            order by id desc
+            limit $2;
+            "#,
+            self.status.map(|status| status as i16),
+            self.limit
+        )
+        .fetch_all(&mut *connection)
+        .await?;
+        outbox_entries(connection, rows).await
+    }
+}
+
+impl Read<PgSource> for ChangesSince {
+    async fn read(self, connection: <PgSource as Source>::Connection<'_>) -> Result<Self::Output, PersistenceError> {
+        let rows = sqlx::query_as!(
+            OutboxRow,
+            r#"
+            select
+                  id
+                , aggregate_type
+                , aggregate_id
+                , event
+                , payload as "payload: Json<serde_json::Value>"
+                , version as "version: RecordVersion"
+                , status
+                , attempts
+                , next_attempt_at
+                , last_error
+            from core.outbox
+            where id > $1
+            order by id
+            limit $2;
+            "#,
+            self.after,
+            self.limit
+        )
+        .fetch_all(&mut *connection)
+        .await?;
+        Ok(outbox_entries(connection, rows).await?
+            .into_iter()
+            .map(|entry| Change {
+                id: entry.id,
+                aggregate_type: entry.aggregate_type,
+                aggregate: entry.aggregate_id,
+                event: entry.event,
+                version: entry.version,
+                payload: Some(entry.payload),
+            })
+            .collect())
+    }
+}
+
+impl Write<PgSource> for RequeueOutbox {
+    async fn write(self, connection: <PgSource as Source>::Connection<'_>) -> crate::error::Result<()> {
+        let now = chrono::Utc::now().naive_utc();
+        let requeued = sqlx::query!(
+            r#"
+            update core.outbox set status = 0, next_attempt_at = $2 where id = $1 and status = 2;
+            "#,
+            self.id,
+            now
+        )
+        .execute(&mut *connection)
+        .await?;
+        if requeued.rows_affected() > 0 {
+            sqlx::query!(
+                r#"
+                update core.outbox_delivery set status = 0, attempts = 0, next_attempt_at = $2
+                where outbox_id = $1 and status = 2;
+                "#,
+                self.id,
+                now
+            )
+            .execute(connection)
+            .await?;
+        }
+        Ok(())
+    }
+}
+
+impl Write<PgSource> for PruneOutbox {
+    async fn write(self, connection: <PgSource as Source>::Connection<'_>) -> crate::error::Result<()> {
+        let pruned = sqlx::query!(
+            r#"
+            delete from core.outbox where status = 1 and delivered_at < $1;
+            "#,
+            chrono::Utc::now().naive_utc() - self.ttl
+        )
+        .execute(&mut *connection)
+        .await?;
+        add_maintenance_run(connection, PruneOutbox::TASK, pruned.rows_affected()).await?;
+        Ok(())
+    }
+}
+
 #[derive(sqlx::Type, Debug)]
 #[sqlx(type_name = "version")]
 pub struct RecordVersion {
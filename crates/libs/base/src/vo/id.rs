@@ -52,6 +52,12 @@ impl<T: Identifiable> PartialEq for ID<T> {
     }
 }
 
+impl<T: Identifiable> Hash for ID<T> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.0.hash(state)
+    }
+}
+
 impl<T: Identifiable> Debug for ID<T> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_tuple("ID").field(&self.0).finish()
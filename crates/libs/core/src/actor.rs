@@ -0,0 +1,133 @@
+use std::collections::HashSet;
+
+use gnify::{error::error, repository::Author, vo::ID};
+use ulid::Ulid;
+
+use crate::{
+    device::Device,
+    privilege::{EffectivePrivileges, PrivilegeGroups},
+    role::RoleLevel,
+    user::{DetailedUserView, User},
+    Privilege, Site,
+};
+
+/// Who performs an administrative write. Users may only manage roles and
+/// users strictly below their own [`RoleLevel`] and only grant privileges
+/// they hold themselves.
+#[derive(Debug, Clone)]
+pub enum Actor {
+    /// Internal processes such as the bootstrap, not bound by the hierarchy.
+    System,
+    /// The background sweep expiring sessions, grants and stale devices. Like
+    /// [`Actor::System`] it isn't bound by the hierarchy, but the versions it
+    /// writes carry an author of their own.
+    Maintenance,
+    User {
+        id: ID<User>,
+        level: RoleLevel,
+        privileges: HashSet<Privilege>,
+        site: Option<Site>,
+    },
+    /// A device acting with its own token. It outranks nobody and holds no
+    /// privileges.
+    Device(ID<Device>),
+}
+
+impl Actor {
+    /// The author of versions written by [`Actor::Maintenance`].
+    pub const MAINTENANCE_ID: Ulid = Ulid(1);
+
+    pub fn from_user(user: &DetailedUserView, groups: &PrivilegeGroups) -> Self {
+        Actor::User {
+            id: user.id(),
+            level: user.level(),
+            privileges: EffectivePrivileges::resolve(user, groups)
+                .privileges()
+                .cloned()
+                .collect(),
+            site: user.site().cloned(),
+        }
+    }
+
+    pub fn id(&self) -> Ulid {
+        match self {
+            Actor::System => Ulid::nil(),
+            Actor::Maintenance => Self::MAINTENANCE_ID,
+            Actor::User { id, .. } => id.value(),
+            Actor::Device(id) => id.value(),
+        }
+    }
+
+    pub fn ensure_outranks(&self, level: RoleLevel) -> gnify::error::Result<()> {
+        match self {
+            Actor::User { level: own, .. } if *own <= level => {
+                error("Can't manage a level equal to or above your own")
+            }
+            Actor::Device(_) => error("Can't manage a level equal to or above your own"),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn ensure_holds<'a>(
+        &self,
+        privileges: impl IntoIterator<Item = &'a Privilege>,
+    ) -> gnify::error::Result<()> {
+        let holds = |privilege: &Privilege| match self {
+            Actor::System | Actor::Maintenance => true,
+            Actor::User { privileges: held, .. } => held.contains(privilege),
+            Actor::Device(_) => false,
+        };
+        if privileges.into_iter().all(holds) {
+            Ok(())
+        } else {
+            error("Can't grant privileges you don't hold")
+        }
+    }
+}
+
+impl Author for Actor {
+    fn id(&self) -> Ulid {
+        Actor::id(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user(level: RoleLevel, privileges: &[&str]) -> Actor {
+        Actor::User {
+            id: ID::new(Ulid::new()),
+            level,
+            privileges: privileges.iter().map(|privilege| privilege.parse().unwrap()).collect(),
+            site: None,
+        }
+    }
+
+    #[test]
+    fn users_only_manage_levels_strictly_below_their_own() {
+        let manager = user(RoleLevel::MANAGER, &[]);
+        assert!(manager.ensure_outranks(RoleLevel::OPERATOR).is_ok());
+        assert!(manager.ensure_outranks(RoleLevel::MANAGER).is_err());
+        assert!(manager.ensure_outranks(RoleLevel::ADMINISTRATOR).is_err());
+    }
+
+    #[test]
+    fn internal_actors_outrank_everyone_and_devices_nobody() {
+        assert!(Actor::System.ensure_outranks(RoleLevel::DEVELOPER).is_ok());
+        assert!(Actor::Maintenance.ensure_outranks(RoleLevel::DEVELOPER).is_ok());
+        let device = Actor::Device(ID::new(Ulid::new()));
+        assert!(device.ensure_outranks(RoleLevel::GUEST).is_err());
+    }
+
+    #[test]
+    fn users_only_grant_privileges_they_hold() {
+        let granter = user(RoleLevel::ADMINISTRATOR, &["REGISTER USER"]);
+        let held: Privilege = "REGISTER USER".parse().unwrap();
+        let other: Privilege = "MANAGE ROLES".parse().unwrap();
+        assert!(granter.ensure_holds([&held]).is_ok());
+        assert!(granter.ensure_holds([&held, &other]).is_err());
+        assert!(Actor::System.ensure_holds([&other]).is_ok());
+        assert!(Actor::Device(ID::new(Ulid::new())).ensure_holds([&held]).is_err());
+    }
+}
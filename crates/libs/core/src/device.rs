@@ -1,32 +1,108 @@
-use gnify::{vo::ID, Model};
+use gnify::{
+    vo::{Version, ID},
+    Model, Record,
+};
 use serde::{Deserialize, Serialize};
+use sqlx::types::chrono::NaiveDateTime;
+use ulid::Ulid;
 
-use crate::user::User;
+use crate::{user::User, Site};
 
 mod bmc;
+mod event;
+mod update;
 mod view;
 mod vo;
 
 pub use vo::*;
 pub use bmc::*;
+pub use event::*;
+pub use update::*;
 pub use view::*;
 
 
+/// A device known by a non-secret id. Only a keyed hash of its token is
+/// kept; the token itself is handed out once, on registration.
 pub struct Device {
+    pub(crate) token_hash: TokenHash,
+    pub(crate) retired_token: Option<RetiredToken>,
     pub(crate) name: DeviceName,
-    pub(crate) session: Option<Session>,
+    pub(crate) sessions: Vec<Session>,
     pub(crate) status: DeviceStatus,
+    pub(crate) site: Option<Site>,
+}
+
+impl Device {
+    /// Registers a device pending approval under a freshly generated token,
+    /// which is returned alongside the record and not kept.
+    pub fn register(name: &str, author: Ulid) -> Result<(Record<Device>, DeviceToken), gnify::Error> {
+        let token = DeviceToken::generate();
+        let record = Self::new(Ulid::new(), &token, name, DeviceStatus::Pending, author)?;
+        Ok((record, token))
+    }
+
+    pub fn new(
+        id: Ulid,
+        token: &DeviceToken,
+        name: &str,
+        status: DeviceStatus,
+        author: Ulid,
+    ) -> Result<Record<Device>, gnify::Error> {
+        let state = Device {
+            token_hash: token.hash()?,
+            retired_token: None,
+            name: name.parse()?,
+            sessions: Vec::new(),
+            status,
+            site: None,
+        };
+        let id: ID<Device> = ID::new(id);
+        let version = Version::now(author);
+        let event = DeviceRegistered {
+            id,
+            name: state.name.clone(),
+            status: state.status,
+            version,
+        };
+        let mut record = Record::new(id, state, version);
+        record.record_event(event);
+        Ok(record)
+    }
 }
 
 impl Model for Device {
-    type ID = DeviceToken;
+    type ID = Ulid;
 
     const NAME: &'static str = "Device";
 }
 
-#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+/// A user logged in on a device, known by the hash of its token.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Session {
-    pub token: SessionToken,
+    #[serde(skip_serializing)]
+    pub token_hash: TokenHash,
     pub user_id: ID<User>,
     pub expiration: ExpirationTimestamp,
+    pub started_at: NaiveDateTime,
+}
+
+impl Session {
+    pub fn is_active(&self) -> bool {
+        !self.expiration.has_passed()
+    }
+}
+
+/// The token a rotation replaced. It keeps working until `until` so that the
+/// device can switch over to the new one.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetiredToken {
+    #[serde(skip_serializing)]
+    pub token_hash: TokenHash,
+    pub until: ExpirationTimestamp,
+}
+
+impl RetiredToken {
+    pub fn is_valid(&self) -> bool {
+        !self.until.has_passed()
+    }
 }
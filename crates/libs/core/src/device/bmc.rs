@@ -1,6 +1,7 @@
-use gnify::{source::BMC, Record};
+use gnify::{error::InvalidValue, source::BMC, vo::{Version, ID}, Record};
+use ulid::Ulid;
 
-use super::{Device, DeviceStatus, DeviceView};
+use super::{Device, DeviceStatus, DeviceToken, DeviceView, SessionToken, TokenHash};
 
 mod postgres;
 
@@ -14,4 +15,60 @@ impl BMC for ListDevices {
 
 pub struct WriteDevice {
     pub record: Record<Device>
-}
\ No newline at end of file
+}
+
+#[derive(Default)]
+pub struct GetDevice {
+    pub id: Option<Ulid>,
+    pub token_hash: Option<TokenHash>,
+    /// Hash of a token of one of the device's active sessions.
+    pub session_hash: Option<TokenHash>,
+}
+
+impl GetDevice {
+    pub fn by_id(id: Ulid) -> Self {
+        Self { id: Some(id), ..Default::default() }
+    }
+
+    /// Finds the device through the hash of the token it presents.
+    pub fn by_token(token: &DeviceToken) -> Result<Self, InvalidValue> {
+        Ok(Self { token_hash: Some(token.hash()?), ..Default::default() })
+    }
+
+    /// Finds the device an active session runs on through the hash of the
+    /// session's token.
+    pub fn by_session(token: &SessionToken) -> Result<Self, InvalidValue> {
+        Ok(Self { session_hash: Some(token.hash()?), ..Default::default() })
+    }
+}
+
+impl BMC for GetDevice {
+    type Output = Option<DeviceView>;
+}
+
+/// Locks the device's row for the rest of the transaction.
+pub struct LockDevice {
+    pub id: ID<Device>,
+}
+
+impl BMC for LockDevice {
+    type Output = Option<Version>;
+}
+
+pub struct DeleteDevice {
+    pub id: ID<Device>,
+}
+
+/// Removes sessions past their expiration, stamping a new [`Version`] by
+/// [`crate::actor::Actor::Maintenance`] on every affected device and
+/// announcing each ended session.
+pub struct ExpireSessions;
+
+impl ExpireSessions {
+    pub const TASK: &'static str = "expire sessions";
+}
+
+/// Replaces the plaintext tokens of devices stored before tokens were
+/// hashed with their hashes. Needs the [`super::TokenKey`] installed. Outbox
+/// entries already written are left as they were.
+pub struct HashLegacyDeviceTokens;
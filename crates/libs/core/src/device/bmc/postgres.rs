@@ -1,118 +1,153 @@
 mod list {
-    use std::ops::Deref;
-
     use gnify::{
         error::InvalidValue,
-        source::{PgSource, Read, RecordVersion},
+        source::{add_corrupt_record, PgSource, Read, RecordVersion},
         vo::Version,
     };
     use serde::Deserialize;
-    use sqlx::types::{chrono::NaiveDateTime, Json, Uuid};
+    use sqlx::{
+        types::{chrono::NaiveDateTime, Json, Uuid},
+        PgConnection,
+    };
 
-    use crate::device::{DeviceStatus, DeviceView, ListDevices, Session};
+    use crate::device::{DeviceStatus, DeviceView, ListDevices, RetiredToken, Session, TokenHash};
 
     impl Read<PgSource> for ListDevices {
         async fn read(
             self,
             connection: <PgSource as gnify::source::Source>::Connection<'_>,
         ) -> Result<Self::Output, gnify::error::PersistenceError> {
-            let status = self.status.map(|status| status as i16);
-            sqlx::query!(
-                r#"
-                delete from core.session where expiration <= CURRENT_TIMESTAMP;
-                "#
-            )
-            .execute(&mut *connection)
-            .await?;
-            let rows: Vec<DeviceRow> = sqlx::query_as!(
-                DeviceRow,
-                r#"
-                select
-                    d.token,
-                    d.version as "version: RecordVersion",
-                    d.first_version as "first_version: RecordVersion",
-                    d.name,
-                    d.status,
+            select(connection, self.status, None, None, None).await
+        }
+    }
+
+    /// Devices matching every filter given; shared with `GetDevice`.
+    pub(super) async fn select(
+        connection: &mut PgConnection,
+        status: Option<DeviceStatus>,
+        id: Option<Uuid>,
+        token_hash: Option<&TokenHash>,
+        session_hash: Option<&TokenHash>,
+    ) -> Result<Vec<DeviceView>, gnify::error::PersistenceError> {
+        let status = status.map(|status| status as i16);
+        let token_hash = token_hash.map(ToString::to_string);
+        let session_hash = session_hash.map(ToString::to_string);
+        let rows: Vec<DeviceRow> = sqlx::query_as!(
+            DeviceRow,
+            r#"
+            select
+                d.id,
+                d.token_hash,
+                d.retired_token_hash,
+                d.retired_until,
+                d.version as "version: RecordVersion",
+                d.first_version as "first_version: RecordVersion",
+                d.name,
+                d.site,
+                d.status,
+                coalesce(
                     (
-                        select
-                            row_to_json(s)
+                        select json_agg(s order by s.started_at)
                         from core.session s
-                        where id = d.session_id
-                        limit 1
-                    ) as "session!: Option<Json<SessionRow>>"
-                from core.device d
-                where coalesce(d.status = $1, true);
-                "#,
-                status
-            )
-            .fetch_all(&mut *connection)
-            .await?;
-
-            let (devices, corrupt_devices) = map_rows(rows);
-
-            if !corrupt_devices.is_empty() {
-                sqlx::query!(
-                    r#"
-                    delete from core.device where token = any($1::text[]);
-                    "#,
-                    &corrupt_devices[..]
+                        where s.device_id = d.id and s.expiration > CURRENT_TIMESTAMP
+                    ),
+                    '[]'
+                ) as "sessions!: Json<Vec<SessionRow>>"
+            from core.device d
+                left join public.corrupt_record crec on crec.id = d.id
+            where crec.id is null
+                and coalesce(d.status = $1, true)
+                and coalesce(d.id = $2, true)
+                and (
+                    $3::text is null
+                    or d.token_hash = $3
+                    or (d.retired_token_hash = $3 and d.retired_until > CURRENT_TIMESTAMP)
                 )
-                .execute(connection)
-                .await?;
+                and (
+                    $4::text is null
+                    or d.id in (
+                        select s.device_id from core.session s
+                        where s.token_hash = $4 and s.expiration > CURRENT_TIMESTAMP
+                    )
+                );
+            "#,
+            status,
+            id,
+            token_hash,
+            session_hash
+        )
+        .fetch_all(&mut *connection)
+        .await?;
+        let mut devices = Vec::with_capacity(rows.len());
+        for row in rows {
+            let id = row.id;
+            match map_device(row) {
+                Ok(view) => devices.push(view),
+                Err(iv) => add_corrupt_record(connection, id, "core.device", iv).await?,
             }
-
-            Ok(devices)
         }
+        Ok(devices)
     }
 
     struct DeviceRow {
-        token: String,
+        id: Uuid,
+        /// Only missing for a device whose legacy token was never hashed.
+        token_hash: Option<String>,
+        retired_token_hash: Option<String>,
+        retired_until: Option<NaiveDateTime>,
         version: RecordVersion,
         first_version: RecordVersion,
         name: String,
-        session: Option<Json<SessionRow>>,
-        status: DeviceStatus,
+        site: Option<String>,
+        sessions: Json<Vec<SessionRow>>,
+        status: i16,
     }
 
     #[derive(Deserialize)]
     struct SessionRow {
-        token: String,
+        token_hash: String,
         user_id: Uuid,
         expiration: NaiveDateTime,
+        started_at: NaiveDateTime,
     }
 
-    fn map_rows(rows: impl IntoIterator<Item = DeviceRow>) -> (Vec<DeviceView>, Vec<String>) {
-        fn map_session(session: impl Deref<Target = SessionRow>) -> Option<Session> {
-            Some(Session {
-                token: session.token.parse().ok()?,
+    fn map_device(device: DeviceRow) -> Result<DeviceView, InvalidValue> {
+        fn map_session(session: &SessionRow) -> Result<Session, InvalidValue> {
+            Ok(Session {
+                token_hash: session.token_hash.parse()?,
                 user_id: session.user_id.into(),
                 expiration: session.expiration.into(),
+                started_at: session.started_at,
             })
         }
-        fn map_device(device: DeviceRow) -> Result<DeviceView, InvalidValue> {
-            let session = device.session.and_then(map_session);
-            Ok(DeviceView {
-                token: device.token.parse()?,
-                version: Version::try_from(device.version)?,
-                first_version: Version::try_from(device.first_version)?,
-                name: device.name.parse()?,
-                session,
-                status: device.status,
-            })
-        }
-        rows.into_iter()
-            .fold((Vec::new(), Vec::new()), |mut acc, row| {
-                let token = row.token.clone();
-                match map_device(row) {
-                    Ok(device) => acc.0.push(device),
-                    Err(_) => acc.1.push(token),
-                }
-                acc
+        let sessions = device.sessions.iter().map(map_session).collect::<Result<_, _>>()?;
+        let retired_token = match (device.retired_token_hash, device.retired_until) {
+            (Some(token_hash), Some(until)) => Some(RetiredToken {
+                token_hash: token_hash.parse()?,
+                until: until.into(),
             })
+            .filter(RetiredToken::is_valid),
+            _ => None,
+        };
+        Ok(DeviceView {
+            id: device.id.into(),
+            token_hash: device
+                .token_hash
+                .ok_or_else(|| InvalidValue::new("TokenHash (missing)"))?
+                .parse()?,
+            retired_token,
+            version: Version::try_from(device.version)?,
+            first_version: Version::try_from(device.first_version)?,
+            name: device.name.parse()?,
+            site: device.site.map(|site| site.parse()).transpose()?,
+            sessions,
+            status: DeviceStatus::try_from(device.status)?,
+        })
     }
 }
+
 mod write {
-    use gnify::source::{PgSource, RecordVersion, Write};
+    use gnify::source::{add_outbox_entries, PgSource, RecordVersion, Write};
     use sqlx::types::chrono::NaiveDateTime;
     use uuid::Uuid;
 
@@ -122,67 +157,290 @@ mod write {
         async fn write(
             self,
             connection: <PgSource as gnify::source::Source>::Connection<'_>,
-        ) -> Result<(), gnify::error::PersistenceError> {
+        ) -> Result<(), gnify::Error> {
             let record = self.record;
-            let token = record.id().to_string();
+            let id = Uuid::from(record.id().value());
             let version = RecordVersion::from(record.version());
 
-            let Device { name, session, status } = record.state();
+            let Device { token_hash, retired_token, name, sessions, status, site } = record.state();
+            let token_hash = token_hash.to_string();
+            let retired_token_hash = retired_token.as_ref().map(|retired| retired.token_hash.to_string());
+            let retired_until = retired_token.as_ref().map(|retired| NaiveDateTime::from(retired.until));
             let name = name.to_string();
+            let site = site.as_ref().map(ToString::to_string);
             let status = *status as i16;
             sqlx::query!(
                 r#"
                 merge into core.device d
-                using (values ($1, $2::version, $3, $4::smallint))
-                    as src(token, version, name, status)
-                on d.token = src.token
+                using (values ($1::uuid, $2, $3::text, $4::timestamp, $5::version, $6, $7::smallint, $8::text))
+                    as src(id, token_hash, retired_token_hash, retired_until, version, name, status, site)
+                on d.id = src.id
                 when not matched then
-                    insert (token, version, first_version, name, status)
-                    values (src.token, src.version, src.version, src.name, src.status)
+                    insert (id, token_hash, retired_token_hash, retired_until, version, first_version, name, status, site)
+                    values (
+                        src.id, src.token_hash, src.retired_token_hash, src.retired_until,
+                        src.version, src.version, src.name, src.status, src.site
+                    )
                 when matched then
                     update set 
+                        token_hash = src.token_hash,
+                        retired_token_hash = src.retired_token_hash,
+                        retired_until = src.retired_until,
                         version = src.version,
                         name = src.name,
-                        status = src.status;
+                        status = src.status,
+                        site = src.site;
                 "#,
-                token,
+                id,
+                token_hash,
+                retired_token_hash,
+                retired_until,
                 version as RecordVersion,
                 name,
-                status
+                status,
+                site
             ).execute(&mut *connection).await?;
-            match session {
-                Some(session) => {
-                    let session_token = session.token.to_string();
-                    let user_id = Uuid::from(session.user_id.value());
-                    let expiration = NaiveDateTime::from(session.expiration);
-                    sqlx::query!(
-                        r#"
-                        with cte as (
-                            delete from core.session where token = $4
-                        ),
-                        s as (
-                            insert into core.session(token, user_id, expiration)
-                            values ($1, $2::uuid, $3) returning id
-                        )
-                        update core.device set 
-                            session_id = (select id from s limit 1);
-                        "#,
-                        session_token,
-                        user_id,
-                        expiration,
-                        token
-                    ).execute(connection).await?;
-                },
-                None => {
-                    sqlx::query!(
-                        r#"
-                        delete from core.session where token = $1
-                        "#,
-                        token
-                    ).execute(connection).await?;
-                },
+            let hashes: Vec<String> = sessions.iter().map(|session| session.token_hash.to_string()).collect();
+            sqlx::query!(
+                r#"
+                delete from core.session where device_id = $1 and token_hash <> all($2::text[]);
+                "#,
+                id,
+                &hashes[..]
+            ).execute(&mut *connection).await?;
+            for session in sessions {
+                sqlx::query!(
+                    r#"
+                    insert into core.session (token_hash, user_id, expiration, started_at, device_id)
+                    values ($1, $2::uuid, $3, $4, $5)
+                    on conflict (token_hash) do update set expiration = excluded.expiration;
+                    "#,
+                    session.token_hash.to_string(),
+                    Uuid::from(session.user_id.value()),
+                    NaiveDateTime::from(session.expiration),
+                    session.started_at,
+                    id
+                ).execute(&mut *connection).await?;
+            }
+            add_outbox_entries(connection, record.events()).await?;
+            Ok(())
+        }
+    }
+}
+
+mod expire {
+    use std::{collections::BTreeMap, sync::Arc};
+
+    use gnify::{
+        event::DomainEvent,
+        source::{add_maintenance_run, add_outbox_entries, PgSource, RecordVersion, Write},
+        vo::{Version, ID},
+    };
+    use sqlx::types::Uuid;
+
+    use crate::{
+        actor::Actor,
+        device::{ExpireSessions, SessionEnded},
+    };
+
+    impl Write<PgSource> for ExpireSessions {
+        async fn write(
+            self,
+            connection: <PgSource as gnify::source::Source>::Connection<'_>,
+        ) -> Result<(), gnify::Error> {
+            let expired = sqlx::query!(
+                r#"
+                delete from core.session where expiration <= CURRENT_TIMESTAMP returning device_id, user_id;
+                "#
+            )
+            .fetch_all(&mut *connection)
+            .await?;
+            let expired_sessions = expired.len() as u64;
+            let mut ended: BTreeMap<Uuid, Vec<Uuid>> = BTreeMap::new();
+            for row in expired {
+                ended.entry(row.device_id).or_default().push(row.user_id);
+            }
+            let version = Version::now(Actor::Maintenance.id());
+            let devices: Vec<Uuid> = ended.keys().copied().collect();
+            sqlx::query!(
+                r#"
+                update core.device set version = $1::version where id = any($2::uuid[]);
+                "#,
+                RecordVersion::from(version) as RecordVersion,
+                &devices[..]
+            )
+            .execute(&mut *connection)
+            .await?;
+            let events: Vec<Arc<dyn DomainEvent>> = ended
+                .into_iter()
+                .flat_map(|(id, users)| {
+                    users.into_iter().map(move |user_id| {
+                        Arc::new(SessionEnded {
+                            id: ID::from(id),
+                            user_id: ID::from(user_id),
+                            version,
+                        }) as Arc<dyn DomainEvent>
+                    })
+                })
+                .collect();
+            add_outbox_entries(connection, &events).await?;
+            add_maintenance_run(connection, ExpireSessions::TASK, expired_sessions).await?;
+            Ok(())
+        }
+    }
+}
+
+mod legacy {
+    use gnify::source::{PgSource, Write};
+
+    use crate::device::{HashLegacyDeviceTokens, TokenHash};
+
+    impl Write<PgSource> for HashLegacyDeviceTokens {
+        async fn write(
+            self,
+            connection: <PgSource as gnify::source::Source>::Connection<'_>,
+        ) -> Result<(), gnify::Error> {
+            let rows = sqlx::query!(
+                r#"
+                select id, token as "token!" from core.device where token is not null for update;
+                "#
+            )
+            .fetch_all(&mut *connection)
+            .await?;
+            for row in rows {
+                sqlx::query!(
+                    r#"
+                    update core.device set token_hash = $2, token = null where id = $1;
+                    "#,
+                    row.id,
+                    TokenHash::of(&row.token)?.to_string()
+                )
+                .execute(&mut *connection)
+                .await?;
             }
             Ok(())
         }
     }
 }
+
+mod get {
+    use gnify::source::{PgSource, Read};
+    use sqlx::types::Uuid;
+
+    use crate::device::GetDevice;
+
+    impl Read<PgSource> for GetDevice {
+        async fn read(
+            self,
+            connection: <PgSource as gnify::source::Source>::Connection<'_>,
+        ) -> Result<Self::Output, gnify::error::PersistenceError> {
+            if self.id.is_none() && self.token_hash.is_none() && self.session_hash.is_none() {
+                return Ok(None);
+            }
+            let id = self.id.map(Uuid::from);
+            let devices = super::list::select(
+                connection,
+                None,
+                id,
+                self.token_hash.as_ref(),
+                self.session_hash.as_ref(),
+            )
+            .await?;
+            Ok(devices.into_iter().next())
+        }
+    }
+}
+
+mod delete {
+    use gnify::source::{PgSource, Write};
+    use sqlx::types::Uuid;
+
+    use crate::device::DeleteDevice;
+
+    impl Write<PgSource> for DeleteDevice {
+        async fn write(
+            self,
+            connection: <PgSource as gnify::source::Source>::Connection<'_>,
+        ) -> Result<(), gnify::Error> {
+            let id = Uuid::from(self.id.value());
+            sqlx::query!(
+                r#"
+                delete from core.session where device_id = $1;
+                "#,
+                id
+            )
+            .execute(&mut *connection)
+            .await?;
+            sqlx::query!(
+                r#"
+                delete from core.device where id = $1;
+                "#,
+                id
+            )
+            .execute(connection)
+            .await?;
+            Ok(())
+        }
+    }
+}
+
+mod persist {
+    use gnify::{
+        error::PersistenceError,
+        repository::{Load, Persist},
+        source::{PgSource, Read, RecordVersion},
+        vo::{Version, ID},
+    };
+    use sqlx::types::Uuid;
+
+    use crate::{
+        actor::Actor,
+        device::{DeleteDevice, Device, GetDevice, LockDevice, WriteDevice},
+    };
+
+    impl Read<PgSource> for LockDevice {
+        async fn read(
+            self,
+            connection: <PgSource as gnify::source::Source>::Connection<'_>,
+        ) -> Result<Self::Output, PersistenceError> {
+            let version = sqlx::query_scalar!(
+                r#"
+                select version as "version: RecordVersion" from core.device where id = $1 for update;
+                "#,
+                Uuid::from(self.id.value())
+            )
+            .fetch_optional(connection)
+            .await?;
+            version
+                .map(Version::try_from)
+                .transpose()
+                .map_err(|iv| PersistenceError::new(iv.to_string()))
+        }
+    }
+
+    /// Device writes are not bound by the role hierarchy; the author only
+    /// stamps versions.
+    impl Persist<PgSource> for Device {
+        type Author = Actor;
+        type Get = Load<GetDevice>;
+        type Lock = LockDevice;
+        type Save = WriteDevice;
+        type Delete = DeleteDevice;
+
+        fn get(id: ID<Self>) -> Self::Get {
+            Load(GetDevice::by_id(id.value()))
+        }
+
+        fn lock(id: ID<Self>) -> Self::Lock {
+            LockDevice { id }
+        }
+
+        fn save(record: gnify::Record<Self>, _author: Actor) -> Self::Save {
+            WriteDevice { record }
+        }
+
+        fn delete(id: ID<Self>, _author: Actor) -> Self::Delete {
+            DeleteDevice { id }
+        }
+    }
+}
@@ -0,0 +1,112 @@
+use gnify::{
+    domain_event,
+    vo::{Version, ID},
+};
+use serde::Serialize;
+
+use crate::{user::User, Site};
+
+use super::{Device, DeviceName, DeviceStatus, ExpirationTimestamp};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DeviceRegistered {
+    pub id: ID<Device>,
+    pub name: DeviceName,
+    pub status: DeviceStatus,
+    pub version: Version,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DeviceRenamed {
+    pub id: ID<Device>,
+    pub name: DeviceName,
+    pub version: Version,
+}
+
+/// The device was assigned to another site, or to none.
+#[derive(Debug, Clone, Serialize)]
+pub struct DeviceMoved {
+    pub id: ID<Device>,
+    pub site: Option<Site>,
+    pub version: Version,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DeviceApproved {
+    pub id: ID<Device>,
+    pub version: Version,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DeviceRejected {
+    pub id: ID<Device>,
+    pub version: Version,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DeviceSuspended {
+    pub id: ID<Device>,
+    pub version: Version,
+}
+
+/// A suspended device approved again.
+#[derive(Debug, Clone, Serialize)]
+pub struct DeviceReinstated {
+    pub id: ID<Device>,
+    pub version: Version,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DeviceRevoked {
+    pub id: ID<Device>,
+    pub version: Version,
+}
+
+/// A user logged in on the device. The session token is left out.
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionStarted {
+    pub id: ID<Device>,
+    pub user_id: ID<User>,
+    pub expiration: ExpirationTimestamp,
+    pub version: Version,
+}
+
+/// A session on the device was logged out, replaced or ended with the
+/// device's approval.
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionEnded {
+    pub id: ID<Device>,
+    pub user_id: ID<User>,
+    pub version: Version,
+}
+
+/// The device was issued a new token. The previous one stays valid until
+/// `retired_until`, or stopped working at once when that's absent.
+#[derive(Debug, Clone, Serialize)]
+pub struct DeviceTokenRotated {
+    pub id: ID<Device>,
+    pub retired_until: Option<ExpirationTimestamp>,
+    pub version: Version,
+}
+
+/// The device's tokens were invalidated without a replacement.
+#[derive(Debug, Clone, Serialize)]
+pub struct DeviceTokenRevoked {
+    pub id: ID<Device>,
+    pub version: Version,
+}
+
+domain_event!(
+    Device: DeviceRegistered,
+    DeviceRenamed,
+    DeviceMoved,
+    DeviceApproved,
+    DeviceRejected,
+    DeviceSuspended,
+    DeviceReinstated,
+    DeviceRevoked,
+    DeviceTokenRotated,
+    DeviceTokenRevoked,
+    SessionStarted,
+    SessionEnded,
+);
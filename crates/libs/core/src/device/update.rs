@@ -0,0 +1,481 @@
+use std::{sync::Arc, time::Duration};
+
+use gnify::{error::InvalidValue, event::DomainEvent, model::RecordUpdate, vo::{Version, ID}};
+use sqlx::types::chrono::Utc;
+
+use crate::{user::User, Site};
+
+use super::{
+    Device, DeviceApproved, DeviceMoved, DeviceName, DeviceReinstated, DeviceRejected, DeviceRenamed, DeviceRevoked,
+    DeviceStatus, DeviceSuspended, DeviceToken, DeviceTokenRevoked, DeviceTokenRotated, DeviceUsers,
+    ExpirationTimestamp, RetiredToken, Session, SessionEnded, SessionRules, SessionStarted, SessionToken,
+    TokenHash,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceUpdate {
+    pub name: DeviceName,
+    pub site: Option<Site>,
+    status: DeviceStatus,
+    sessions: Vec<Session>,
+    token_hash: TokenHash,
+    retired_token: Option<RetiredToken>,
+    /// Set by [`Self::revoke_token`] so that the change is announced as a
+    /// revocation rather than a rotation.
+    token_revoked: bool,
+}
+
+impl DeviceUpdate {
+    pub fn status(&self) -> DeviceStatus {
+        self.status
+    }
+
+    /// Moves the device along its lifecycle; setting the current status again
+    /// is a no-op. Leaving `Approved` ends every session.
+    pub fn set_status(&mut self, status: DeviceStatus) -> Result<(), InvalidValue> {
+        if status != self.status {
+            self.status = self.status.transition(status)?;
+            if self.status != DeviceStatus::Approved {
+                self.sessions.clear();
+            }
+        }
+        Ok(())
+    }
+
+    pub fn sessions(&self) -> &[Session] {
+        &self.sessions
+    }
+
+    /// Starts a session for `user` and returns its token. A user has at most
+    /// one session per device, so logging in again replaces the previous one;
+    /// with [`DeviceUsers::Single`] other users' sessions end as well.
+    pub fn login(&mut self, user: ID<User>, rules: &SessionRules) -> Result<SessionToken, InvalidValue> {
+        if self.status != DeviceStatus::Approved {
+            return Err(InvalidValue::new(format!("Device (is {})", self.status.as_str())));
+        }
+        self.sessions.retain(Session::is_active);
+        match rules.users {
+            DeviceUsers::Single => self.sessions.clear(),
+            DeviceUsers::Multiple => self.sessions.retain(|session| session.user_id != user),
+        }
+        let token = SessionToken::generate();
+        self.sessions.push(Session {
+            token_hash: token.hash()?,
+            user_id: user,
+            expiration: ExpirationTimestamp::new(rules.idle),
+            started_at: Utc::now().naive_utc(),
+        });
+        Ok(token)
+    }
+
+    /// Slides the session's expiration `idle` ahead, up to its lifetime.
+    pub fn refresh(&mut self, token: &SessionToken, rules: &SessionRules) -> Result<ExpirationTimestamp, InvalidValue> {
+        let hash = token.hash()?;
+        let session = self
+            .sessions
+            .iter_mut()
+            .find(|session| session.token_hash == hash && session.is_active())
+            .ok_or_else(|| InvalidValue::new("Session (not found or expired)"))?;
+        let limit = ExpirationTimestamp::from(session.started_at + rules.lifetime);
+        let expiration = ExpirationTimestamp::new(rules.idle).min(limit);
+        if expiration.has_passed() {
+            return Err(InvalidValue::new("Session (reached its lifetime)"));
+        }
+        session.expiration = expiration;
+        Ok(expiration)
+    }
+
+    /// Ends the session; returns whether there was one.
+    pub fn logout(&mut self, token: &SessionToken) -> Result<bool, InvalidValue> {
+        let hash = token.hash()?;
+        let before = self.sessions.len();
+        self.sessions.retain(|session| session.token_hash != hash);
+        Ok(self.sessions.len() != before)
+    }
+
+    pub fn retired_token(&self) -> Option<&RetiredToken> {
+        self.retired_token.as_ref()
+    }
+
+    /// Issues a new token and returns it. The current one keeps working for
+    /// `overlap`; when that's zero it stops at once and every session on the
+    /// device ends with it. A token retired by an earlier rotation stops in
+    /// any case.
+    pub fn rotate_token(&mut self, overlap: Duration) -> Result<DeviceToken, InvalidValue> {
+        if matches!(self.status, DeviceStatus::Rejected | DeviceStatus::Revoked) {
+            return Err(InvalidValue::new(format!("Device (is {})", self.status.as_str())));
+        }
+        let token = DeviceToken::generate();
+        let retired = std::mem::replace(&mut self.token_hash, token.hash()?);
+        self.retired_token = (!overlap.is_zero()).then(|| RetiredToken {
+            token_hash: retired,
+            until: ExpirationTimestamp::new(overlap),
+        });
+        if overlap.is_zero() {
+            self.sessions.clear();
+        }
+        self.token_revoked = false;
+        Ok(token)
+    }
+
+    /// Invalidates the device's token, including a retired one still within
+    /// its overlap, and ends every session on the device. The device can't
+    /// authenticate again until its token is rotated.
+    pub fn revoke_token(&mut self) -> Result<(), InvalidValue> {
+        self.token_hash = DeviceToken::generate().hash()?;
+        self.retired_token = None;
+        self.sessions.clear();
+        self.token_revoked = true;
+        Ok(())
+    }
+
+    /// Ends every session of `user` on the device.
+    pub fn end_sessions_of(&mut self, user: &ID<User>) -> bool {
+        let before = self.sessions.len();
+        self.sessions.retain(|session| &session.user_id != user);
+        self.sessions.len() != before
+    }
+}
+
+impl RecordUpdate for DeviceUpdate {
+    type Model = Device;
+
+    fn new(model: &Device, _version: Version) -> Self {
+        Self {
+            name: model.name.clone(),
+            site: model.site.clone(),
+            status: model.status,
+            sessions: model.sessions.clone(),
+            token_hash: model.token_hash.clone(),
+            retired_token: model.retired_token.clone(),
+            token_revoked: false,
+        }
+    }
+
+    fn apply(self, state: &mut Device) {
+        state.name = self.name;
+        state.site = self.site;
+        state.status = self.status;
+        state.sessions = self.sessions;
+        state.token_hash = self.token_hash;
+        state.retired_token = self.retired_token;
+    }
+
+    fn events(
+        &self,
+        previous: &Self,
+        id: &ID<Device>,
+        version: Version,
+    ) -> Result<Vec<Arc<dyn DomainEvent>>, InvalidValue> {
+        let mut events: Vec<Arc<dyn DomainEvent>> = Vec::new();
+        if self.name != previous.name {
+            events.push(Arc::new(DeviceRenamed { id: *id, name: self.name.clone(), version }));
+        }
+        if self.site != previous.site {
+            events.push(Arc::new(DeviceMoved { id: *id, site: self.site.clone(), version }));
+        }
+        if self.status != previous.status {
+            let id = *id;
+            let event: Arc<dyn DomainEvent> = match (previous.status, self.status) {
+                (DeviceStatus::Suspended, DeviceStatus::Approved) => Arc::new(DeviceReinstated { id, version }),
+                (_, DeviceStatus::Approved) => Arc::new(DeviceApproved { id, version }),
+                (_, DeviceStatus::Rejected) => Arc::new(DeviceRejected { id, version }),
+                (_, DeviceStatus::Suspended) => Arc::new(DeviceSuspended { id, version }),
+                (_, DeviceStatus::Revoked) => Arc::new(DeviceRevoked { id, version }),
+                (_, DeviceStatus::Pending) => {
+                    return Err(InvalidValue::new("Device status (cannot go back to Pending)"))
+                }
+            };
+            events.push(event);
+        }
+        if self.token_hash != previous.token_hash {
+            let id = *id;
+            let event: Arc<dyn DomainEvent> = if self.token_revoked {
+                Arc::new(DeviceTokenRevoked { id, version })
+            } else {
+                let retired_until = self.retired_token.as_ref().map(|retired| retired.until);
+                Arc::new(DeviceTokenRotated { id, retired_until, version })
+            };
+            events.push(event);
+        }
+        for session in &previous.sessions {
+            if !self.sessions.iter().any(|current| current.token_hash == session.token_hash) {
+                events.push(Arc::new(SessionEnded { id: *id, user_id: session.user_id, version }));
+            }
+        }
+        for session in &self.sessions {
+            if !previous.sessions.iter().any(|earlier| earlier.token_hash == session.token_hash) {
+                events.push(Arc::new(SessionStarted {
+                    id: *id,
+                    user_id: session.user_id,
+                    expiration: session.expiration,
+                    version,
+                }));
+            }
+        }
+        Ok(events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ulid::Ulid;
+
+    use super::*;
+    use crate::device::TokenKey;
+
+    const NAME: &str = "Reception Desk Terminal In Lobby";
+
+    fn device(status: DeviceStatus) -> (DeviceUpdate, ID<Device>) {
+        TokenKey::new(&"k".repeat(32)).unwrap().install();
+        let record = Device::new(Ulid::new(), &DeviceToken::generate(), NAME, status, Ulid::nil()).unwrap();
+        (DeviceUpdate::new(record.state(), Version::now(Ulid::nil())), record.id())
+    }
+
+    fn rules(users: DeviceUsers) -> SessionRules {
+        SessionRules {
+            idle: Duration::from_secs(600),
+            lifetime: Duration::from_secs(3600),
+            users,
+            second_device: crate::device::SecondDevice::Allow,
+        }
+    }
+
+    fn user() -> ID<User> {
+        ID::new(Ulid::new())
+    }
+
+    fn event_names(update: &DeviceUpdate, previous: &DeviceUpdate, id: ID<Device>) -> Vec<&'static str> {
+        update
+            .events(previous, &id, Version::now(Ulid::nil()))
+            .unwrap()
+            .iter()
+            .map(|event| event.name())
+            .collect()
+    }
+
+    #[test]
+    fn set_status_follows_the_lifecycle() {
+        let (mut update, _) = device(DeviceStatus::Pending);
+        update.set_status(DeviceStatus::Approved).unwrap();
+        update.set_status(DeviceStatus::Approved).unwrap();
+        assert_eq!(update.status(), DeviceStatus::Approved);
+        assert!(update.set_status(DeviceStatus::Pending).is_err());
+        assert!(update.set_status(DeviceStatus::Rejected).is_err());
+        assert_eq!(update.status(), DeviceStatus::Approved);
+    }
+
+    #[test]
+    fn leaving_approved_ends_every_session() {
+        let (mut update, _) = device(DeviceStatus::Approved);
+        update.login(user(), &rules(DeviceUsers::Multiple)).unwrap();
+        update.set_status(DeviceStatus::Suspended).unwrap();
+        assert!(update.sessions().is_empty());
+    }
+
+    #[test]
+    fn status_changes_are_announced() {
+        let (previous, id) = device(DeviceStatus::Suspended);
+        let mut update = previous.clone();
+        update.set_status(DeviceStatus::Approved).unwrap();
+        assert_eq!(event_names(&update, &previous, id), ["DeviceReinstated"]);
+    }
+
+    #[test]
+    fn going_back_to_pending_is_refused_by_the_events() {
+        let (previous, id) = device(DeviceStatus::Approved);
+        let mut update = previous.clone();
+        update.status = DeviceStatus::Pending;
+        assert!(update.events(&previous, &id, Version::now(Ulid::nil())).is_err());
+    }
+
+    #[test]
+    fn login_needs_an_approved_device() {
+        let (mut update, _) = device(DeviceStatus::Pending);
+        assert!(update.login(user(), &rules(DeviceUsers::Multiple)).is_err());
+    }
+
+    #[test]
+    fn login_replaces_the_users_own_session() {
+        let (mut update, _) = device(DeviceStatus::Approved);
+        let (first, second) = (user(), user());
+        let rules = rules(DeviceUsers::Multiple);
+        update.login(first, &rules).unwrap();
+        update.login(second, &rules).unwrap();
+        update.login(first, &rules).unwrap();
+        assert_eq!(update.sessions().len(), 2);
+    }
+
+    #[test]
+    fn single_user_devices_end_other_sessions_on_login() {
+        let (mut update, _) = device(DeviceStatus::Approved);
+        let rules = rules(DeviceUsers::Single);
+        update.login(user(), &rules).unwrap();
+        let last = user();
+        update.login(last, &rules).unwrap();
+        assert_eq!(update.sessions().len(), 1);
+        assert_eq!(update.sessions()[0].user_id, last);
+    }
+
+    #[test]
+    fn login_drops_expired_sessions() {
+        let (mut update, _) = device(DeviceStatus::Approved);
+        let rules = rules(DeviceUsers::Multiple);
+        update.login(user(), &rules).unwrap();
+        update.sessions[0].expiration = ExpirationTimestamp::from(Utc::now().naive_utc() - Duration::from_secs(1));
+        update.login(user(), &rules).unwrap();
+        assert_eq!(update.sessions().len(), 1);
+    }
+
+    #[test]
+    fn refresh_slides_the_expiration_by_idle() {
+        let (mut update, _) = device(DeviceStatus::Approved);
+        let rules = rules(DeviceUsers::Multiple);
+        let token = update.login(user(), &rules).unwrap();
+        update.sessions[0].expiration = ExpirationTimestamp::new(Duration::from_secs(1));
+        let expiration = update.refresh(&token, &rules).unwrap();
+        assert!(*expiration > Utc::now().naive_utc() + Duration::from_secs(590));
+        assert_eq!(update.sessions[0].expiration, expiration);
+    }
+
+    #[test]
+    fn refresh_stops_at_the_lifetime() {
+        let (mut update, _) = device(DeviceStatus::Approved);
+        let rules = rules(DeviceUsers::Multiple);
+        let token = update.login(user(), &rules).unwrap();
+        let started_at = Utc::now().naive_utc() - Duration::from_secs(55 * 60);
+        update.sessions[0].started_at = started_at;
+        let expiration = update.refresh(&token, &rules).unwrap();
+        assert_eq!(*expiration, started_at + rules.lifetime);
+    }
+
+    #[test]
+    fn refresh_fails_once_the_lifetime_passed() {
+        let (mut update, _) = device(DeviceStatus::Approved);
+        let rules = rules(DeviceUsers::Multiple);
+        let token = update.login(user(), &rules).unwrap();
+        update.sessions[0].started_at = Utc::now().naive_utc() - Duration::from_secs(2 * 3600);
+        assert!(update.refresh(&token, &rules).is_err());
+    }
+
+    #[test]
+    fn refresh_fails_for_idle_sessions() {
+        let (mut update, _) = device(DeviceStatus::Approved);
+        let rules = rules(DeviceUsers::Multiple);
+        let token = update.login(user(), &rules).unwrap();
+        update.sessions[0].expiration = ExpirationTimestamp::from(Utc::now().naive_utc() - Duration::from_secs(1));
+        assert!(update.refresh(&token, &rules).is_err());
+    }
+
+    #[test]
+    fn logout_ends_only_the_given_session() {
+        let (mut update, _) = device(DeviceStatus::Approved);
+        let rules = rules(DeviceUsers::Multiple);
+        let token = update.login(user(), &rules).unwrap();
+        update.login(user(), &rules).unwrap();
+        assert!(update.logout(&token).unwrap());
+        assert!(!update.logout(&token).unwrap());
+        assert_eq!(update.sessions().len(), 1);
+    }
+
+    /// Whether the device authenticates with `token`, the way devices are
+    /// looked up by their token hash.
+    fn accepts(update: &DeviceUpdate, token: &DeviceToken) -> bool {
+        let hash = token.hash().unwrap();
+        update.token_hash == hash
+            || update
+                .retired_token()
+                .is_some_and(|retired| retired.is_valid() && retired.token_hash == hash)
+    }
+
+    #[test]
+    fn rotation_moves_authentication_to_the_new_token() {
+        TokenKey::new(&"k".repeat(32)).unwrap().install();
+        let old = DeviceToken::generate();
+        let record = Device::new(Ulid::new(), &old, NAME, DeviceStatus::Approved, Ulid::nil()).unwrap();
+        let mut update = DeviceUpdate::new(record.state(), Version::now(Ulid::nil()));
+        assert!(accepts(&update, &old));
+
+        let new = update.rotate_token(Duration::from_secs(60)).unwrap();
+        assert!(accepts(&update, &new));
+        assert!(accepts(&update, &old));
+        update.retired_token.as_mut().unwrap().until = ExpirationTimestamp::from(Utc::now().naive_utc());
+        assert!(!accepts(&update, &old));
+        assert!(accepts(&update, &new));
+
+        let newest = update.rotate_token(Duration::ZERO).unwrap();
+        assert!(accepts(&update, &newest));
+        assert!(!accepts(&update, &new));
+        assert!(!accepts(&update, &old));
+    }
+
+    #[test]
+    fn revoked_tokens_no_longer_authenticate() {
+        TokenKey::new(&"k".repeat(32)).unwrap().install();
+        let old = DeviceToken::generate();
+        let record = Device::new(Ulid::new(), &old, NAME, DeviceStatus::Approved, Ulid::nil()).unwrap();
+        let mut update = DeviceUpdate::new(record.state(), Version::now(Ulid::nil()));
+        let rotated = update.rotate_token(Duration::from_secs(60)).unwrap();
+        update.revoke_token().unwrap();
+        assert!(!accepts(&update, &old));
+        assert!(!accepts(&update, &rotated));
+    }
+
+    #[test]
+    fn rotate_token_keeps_the_old_one_for_the_overlap() {
+        let (previous, id) = device(DeviceStatus::Approved);
+        let mut update = previous.clone();
+        update.login(user(), &rules(DeviceUsers::Multiple)).unwrap();
+        let token = update.rotate_token(Duration::from_secs(60)).unwrap();
+        assert_eq!(update.token_hash, token.hash().unwrap());
+        let retired = update.retired_token().unwrap();
+        assert_eq!(retired.token_hash, previous.token_hash);
+        assert!(retired.is_valid());
+        assert_eq!(update.sessions().len(), 1);
+        assert!(event_names(&update, &previous, id).contains(&"DeviceTokenRotated"));
+    }
+
+    #[test]
+    fn rotate_token_without_overlap_ends_sessions() {
+        let (previous, id) = device(DeviceStatus::Approved);
+        let mut update = previous.clone();
+        update.login(user(), &rules(DeviceUsers::Multiple)).unwrap();
+        update.rotate_token(Duration::ZERO).unwrap();
+        assert!(update.retired_token().is_none());
+        assert!(update.sessions().is_empty());
+        assert_eq!(event_names(&update, &previous, id), ["DeviceTokenRotated"]);
+    }
+
+    #[test]
+    fn rotate_token_drops_an_earlier_retired_token() {
+        let (mut update, _) = device(DeviceStatus::Approved);
+        update.rotate_token(Duration::from_secs(60)).unwrap();
+        let current = update.token_hash.clone();
+        update.rotate_token(Duration::ZERO).unwrap();
+        assert!(update.retired_token().is_none());
+        assert_ne!(update.token_hash, current);
+    }
+
+    #[test]
+    fn rotate_token_is_refused_for_final_statuses() {
+        for status in [DeviceStatus::Rejected, DeviceStatus::Revoked] {
+            let (mut update, _) = device(status);
+            assert!(update.rotate_token(Duration::from_secs(60)).is_err());
+        }
+    }
+
+    #[test]
+    fn revoke_token_invalidates_every_token_and_session() {
+        let (previous, id) = device(DeviceStatus::Approved);
+        let mut update = previous.clone();
+        update.rotate_token(Duration::from_secs(60)).unwrap();
+        update.login(user(), &rules(DeviceUsers::Multiple)).unwrap();
+        let rotated = update.token_hash.clone();
+        update.revoke_token().unwrap();
+        assert_ne!(update.token_hash, rotated);
+        assert_ne!(update.token_hash, previous.token_hash);
+        assert!(update.retired_token().is_none());
+        assert!(update.sessions().is_empty());
+        assert_eq!(event_names(&update, &previous, id), ["DeviceTokenRevoked"]);
+    }
+}
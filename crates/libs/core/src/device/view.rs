@@ -1,14 +1,71 @@
-use gnify::vo::Version;
+use gnify::{model::Record, repository::View, vo::{Version, ID}};
 use serde::{Deserialize, Serialize};
 
-use super::{DeviceName, DeviceStatus, DeviceToken, Session};
+use crate::Site;
+
+use super::{Device, DeviceName, DeviceStatus, RetiredToken, Session, TokenHash};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct DeviceView {
-    pub(crate) token: DeviceToken,
+    pub(crate) id: ID<Device>,
+    #[serde(skip_serializing)]
+    pub(crate) token_hash: TokenHash,
+    /// Only present while the retired token still works.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) retired_token: Option<RetiredToken>,
     pub(crate) version: Version,
     pub(crate) first_version: Version,
     pub(crate) name: DeviceName,
-    pub(crate) session: Option<Session>,
+    pub(crate) site: Option<Site>,
+    pub(crate) sessions: Vec<Session>,
     pub(crate) status: DeviceStatus
-}
\ No newline at end of file
+}
+
+impl DeviceView {
+    pub fn as_record(self) -> Record<Device> {
+        let DeviceView { id, token_hash, retired_token, version, first_version: _, name, site, sessions, status } = self;
+        let state = Device { token_hash, retired_token, name, sessions, status, site };
+        Record::loaded(id, state, version)
+    }
+
+    pub fn id(&self) -> ID<Device> {
+        self.id
+    }
+
+    pub fn version(&self) -> Version {
+        self.version
+    }
+
+    pub fn first_version(&self) -> Version {
+        self.first_version
+    }
+
+    pub fn retired_token(&self) -> Option<&RetiredToken> {
+        self.retired_token.as_ref()
+    }
+
+    pub fn name(&self) -> &DeviceName {
+        &self.name
+    }
+
+    pub fn site(&self) -> Option<&Site> {
+        self.site.as_ref()
+    }
+
+    /// The sessions that have not expired.
+    pub fn sessions(&self) -> &[Session] {
+        &self.sessions
+    }
+
+    pub fn status(&self) -> DeviceStatus {
+        self.status
+    }
+}
+
+impl View for DeviceView {
+    type Model = Device;
+
+    fn into_record(self) -> Record<Device> {
+        self.as_record()
+    }
+}
@@ -1,8 +1,12 @@
 use std::time::Duration;
 
 use api_key::types::{ApiKeyResults, Default, StringGenerator};
-use gnify::text;
+use data_encoding::HEXLOWER;
+use gnify::{error::InvalidValue, text};
+use hmac::{Hmac, Mac};
+use once_cell::sync::OnceCell;
 use serde::{Deserialize, Serialize};
+use sha2::Sha256;
 use sqlx::types::chrono::{NaiveDateTime, Utc};
 
 text! {
@@ -17,32 +21,159 @@ text! {
     SessionToken: r"^\w{64}$"
 }
 
+text! {
+    TokenHash: r"^[0-9a-f]{64}$"
+}
+
+static TOKEN_KEY: OnceCell<Vec<u8>> = OnceCell::new();
+
+/// Secret the stored token hashes are keyed with. Changing it invalidates
+/// every device and session token.
+#[derive(Clone, Deserialize)]
+#[serde(try_from = "String")]
+pub struct TokenKey(Vec<u8>);
+
+impl TokenKey {
+    pub fn new(key: &str) -> Result<Self, InvalidValue> {
+        if key.len() < 32 {
+            return Err(InvalidValue::new("TokenKey (must be at least 32 bytes)"));
+        }
+        Ok(Self(key.as_bytes().to_vec()))
+    }
+
+    /// Makes this the key of every token hash; the first installed key wins.
+    pub fn install(self) {
+        let _ = TOKEN_KEY.set(self.0);
+    }
+}
+
+impl std::str::FromStr for TokenKey {
+    type Err = InvalidValue;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::new(s)
+    }
+}
+
+impl TryFrom<String> for TokenKey {
+    type Error = InvalidValue;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Self::new(&value)
+    }
+}
+
+impl std::fmt::Debug for TokenKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("TokenKey(..)")
+    }
+}
+
+impl TokenHash {
+    /// HMAC-SHA256 of `token` under the installed [`TokenKey`]; fails when
+    /// none has been installed.
+    pub fn of(token: &str) -> Result<Self, InvalidValue> {
+        let key = TOKEN_KEY.get().ok_or_else(|| InvalidValue::new("TokenKey (not installed)"))?;
+        let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes keys of any size");
+        mac.update(token.as_bytes());
+        Ok(Self(HEXLOWER.encode(&mac.finalize().into_bytes())))
+    }
+}
+
 impl SessionToken {
     pub fn generate() -> Self {
-        let options = StringGenerator {
-            prefix: String::from("GNI"),
-            length: 64,
-            ..StringGenerator::default()
-        };
+        Self(generate_token())
+    }
 
-        let token = api_key::string(options);
-        match token {
-            ApiKeyResults::String(token) => Self(token),
-            ApiKeyResults::StringArray(ref tokens) => Self(tokens[0].clone()),
-        }
+    pub fn hash(&self) -> Result<TokenHash, InvalidValue> {
+        TokenHash::of(&self.0)
     }
 }
 
+impl DeviceToken {
+    pub fn generate() -> Self {
+        Self(generate_token())
+    }
+
+    pub fn hash(&self) -> Result<TokenHash, InvalidValue> {
+        TokenHash::of(&self.0)
+    }
+}
+
+/// Characters of generated tokens; exactly those of the token formats.
+const TOKEN_CHARACTERS: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789_";
+
+fn generate_token() -> String {
+    let options = StringGenerator {
+        length: 64,
+        pool: String::from(TOKEN_CHARACTERS),
+        ..StringGenerator::default()
+    };
+
+    let token = api_key::string(options);
+    match token {
+        ApiKeyResults::String(token) => token,
+        ApiKeyResults::StringArray(ref tokens) => tokens[0].clone(),
+    }
+}
+
+/// Where a device is in its lifecycle. Devices register as `Pending`; only
+/// `Approved` devices may be used. `Rejected` and `Revoked` are final.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[repr(u8)]
 pub enum DeviceStatus {
-    Authorized = 1,
-    Unauthorized = 0,
+    Pending = 0,
+    Approved = 1,
+    Rejected = 2,
+    Suspended = 3,
+    Revoked = 4,
+}
+
+impl DeviceStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DeviceStatus::Pending => "Pending",
+            DeviceStatus::Approved => "Approved",
+            DeviceStatus::Rejected => "Rejected",
+            DeviceStatus::Suspended => "Suspended",
+            DeviceStatus::Revoked => "Revoked",
+        }
+    }
+
+    pub fn can_become(&self, next: DeviceStatus) -> bool {
+        use DeviceStatus::*;
+        matches!(
+            (self, next),
+            (Pending, Approved | Rejected) | (Approved, Suspended | Revoked) | (Suspended, Approved | Revoked)
+        )
+    }
+
+    /// Checks that the lifecycle allows moving to `next`.
+    pub fn transition(&self, next: DeviceStatus) -> Result<DeviceStatus, InvalidValue> {
+        if self.can_become(next) {
+            Ok(next)
+        } else {
+            Err(InvalidValue::new(format!(
+                "Device status (cannot go from {} to {})",
+                self.as_str(),
+                next.as_str()
+            )))
+        }
+    }
 }
 
-impl From<i16> for DeviceStatus {
-    fn from(value: i16) -> Self {
-        if value == 1 { DeviceStatus::Authorized } else { DeviceStatus::Unauthorized }
+impl TryFrom<i16> for DeviceStatus {
+    type Error = InvalidValue;
+
+    fn try_from(value: i16) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(DeviceStatus::Pending),
+            1 => Ok(DeviceStatus::Approved),
+            2 => Ok(DeviceStatus::Rejected),
+            3 => Ok(DeviceStatus::Suspended),
+            4 => Ok(DeviceStatus::Revoked),
+            _ => Err(InvalidValue::new(format!("DeviceStatus ({value})"))),
+        }
     }
 }
 
@@ -62,6 +193,74 @@ impl ExpirationTimestamp {
         let value = Utc::now().naive_utc() + offset.into();
         ExpirationTimestamp(value)
     }
+
+    pub fn has_passed(&self) -> bool {
+        self.0 <= Utc::now().naive_utc()
+    }
+}
+
+/// How many users may be logged in on a device at once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeviceUsers {
+    /// A login ends the sessions of anyone else on the device.
+    Single,
+    Multiple,
+}
+
+impl std::str::FromStr for DeviceUsers {
+    type Err = InvalidValue;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "single" => Ok(DeviceUsers::Single),
+            "multiple" => Ok(DeviceUsers::Multiple),
+            _ => Err(InvalidValue::new(format!("device users {value}"))),
+        }
+    }
+}
+
+/// What a login on one device does to the user's sessions on others.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SecondDevice {
+    Allow,
+    /// Ends the user's sessions on every other device.
+    EndOthers,
+    /// Refuses the login while the user has a session elsewhere.
+    Reject,
+}
+
+impl std::str::FromStr for SecondDevice {
+    type Err = InvalidValue;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "allow" => Ok(SecondDevice::Allow),
+            "end_others" => Ok(SecondDevice::EndOthers),
+            "reject" => Ok(SecondDevice::Reject),
+            _ => Err(InvalidValue::new(format!("second device {value}"))),
+        }
+    }
+}
+
+/// Lifetime and exclusivity of device sessions. Refreshing a session moves
+/// its expiration `idle` ahead, but never past `lifetime` after login.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionRules {
+    pub idle: Duration,
+    pub lifetime: Duration,
+    pub users: DeviceUsers,
+    pub second_device: SecondDevice,
+}
+
+impl SessionRules {
+    pub fn validate(&self) -> Result<(), InvalidValue> {
+        if self.idle.is_zero() || self.lifetime < self.idle {
+            return Err(InvalidValue::new("device session (idle must be positive and within lifetime)"));
+        }
+        Ok(())
+    }
 }
 
 
@@ -76,3 +275,103 @@ impl From<ExpirationTimestamp> for NaiveDateTime {
         value.0
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL: [DeviceStatus; 5] = [
+        DeviceStatus::Pending,
+        DeviceStatus::Approved,
+        DeviceStatus::Rejected,
+        DeviceStatus::Suspended,
+        DeviceStatus::Revoked,
+    ];
+
+    #[test]
+    fn lifecycle_allows_only_its_transitions() {
+        use DeviceStatus::*;
+        let allowed = [
+            (Pending, Approved),
+            (Pending, Rejected),
+            (Approved, Suspended),
+            (Approved, Revoked),
+            (Suspended, Approved),
+            (Suspended, Revoked),
+        ];
+        for from in ALL {
+            for to in ALL {
+                let expected = allowed.contains(&(from, to));
+                assert_eq!(from.can_become(to), expected, "{} to {}", from.as_str(), to.as_str());
+                assert_eq!(from.transition(to).is_ok(), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn rejected_and_revoked_are_final() {
+        for to in ALL {
+            assert!(!DeviceStatus::Rejected.can_become(to));
+            assert!(!DeviceStatus::Revoked.can_become(to));
+        }
+    }
+
+    #[test]
+    fn status_round_trips_through_its_stored_value() {
+        for status in ALL {
+            assert_eq!(DeviceStatus::try_from(status as i16).unwrap(), status);
+        }
+        assert!(DeviceStatus::try_from(5).is_err());
+    }
+
+    #[test]
+    fn session_rules_need_a_positive_idle_within_lifetime() {
+        let rules = |idle, lifetime| SessionRules {
+            idle: Duration::from_secs(idle),
+            lifetime: Duration::from_secs(lifetime),
+            users: DeviceUsers::Multiple,
+            second_device: SecondDevice::Allow,
+        };
+        assert!(rules(60, 3600).validate().is_ok());
+        assert!(rules(60, 60).validate().is_ok());
+        assert!(rules(0, 3600).validate().is_err());
+        assert!(rules(3600, 60).validate().is_err());
+    }
+
+    #[test]
+    fn expiration_has_passed_once_reached() {
+        assert!(!ExpirationTimestamp::new(Duration::from_secs(60)).has_passed());
+        assert!(ExpirationTimestamp::from(Utc::now().naive_utc()).has_passed());
+        assert!(ExpirationTimestamp::from(Utc::now().naive_utc() - Duration::from_secs(1)).has_passed());
+    }
+
+    #[test]
+    fn token_hash_depends_on_the_token() {
+        TokenKey::new(&"k".repeat(32)).unwrap().install();
+        let token = DeviceToken::generate();
+        assert_eq!(token.hash().unwrap(), token.hash().unwrap());
+        assert_ne!(token.hash().unwrap(), DeviceToken::generate().hash().unwrap());
+        assert!(token.hash().unwrap().to_string().parse::<TokenHash>().is_ok());
+    }
+
+    #[test]
+    fn generated_tokens_parse() {
+        for _ in 0..200 {
+            let token = DeviceToken::generate();
+            assert!(token.to_string().parse::<DeviceToken>().is_ok(), "{token}");
+            let token = SessionToken::generate();
+            assert!(token.to_string().parse::<SessionToken>().is_ok(), "{token}");
+        }
+    }
+
+    #[test]
+    fn token_key_must_be_long_enough() {
+        assert!(TokenKey::new(&"k".repeat(31)).is_err());
+    }
+
+    #[test]
+    fn token_key_is_not_debug_printed() {
+        let key = TokenKey::new(&"secret".repeat(6)).unwrap();
+        assert!(!format!("{key:?}").contains("secret"));
+    }
+}
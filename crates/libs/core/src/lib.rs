@@ -1,10 +1,23 @@
+pub mod actor;
 pub mod user;
 pub mod role;
 pub mod device;
+pub mod login;
+pub mod policy;
+pub mod privilege;
+pub mod webhook;
 
 gnify::text! {
     Privilege => 
         pattern: r"^([A-Z]+\s)*[A-Z]+$";
         min: 4;
         max: 32;
-}
\ No newline at end of file
+}
+
+// The site or tenant a user or device belongs to, e.g. `north-store`.
+gnify::text! {
+    Site =>
+        pattern: r"^[a-z0-9]+(-[a-z0-9]+)*$";
+        min: 1;
+        max: 64;
+}
@@ -0,0 +1,12 @@
+use crate::device::ExpirationTimestamp;
+
+mod bmc;
+
+pub use bmc::*;
+
+#[derive(Debug, Clone)]
+pub struct LoginSession {
+    pub id: String,
+    pub data: Vec<u8>,
+    pub expiration: ExpirationTimestamp,
+}
@@ -0,0 +1,23 @@
+use gnify::source::BMC;
+
+use super::LoginSession;
+
+mod postgres;
+
+pub struct GetLoginSession {
+    pub id: String,
+}
+
+impl BMC for GetLoginSession {
+    type Output = Option<LoginSession>;
+}
+
+pub struct WriteLoginSession {
+    pub session: LoginSession,
+}
+
+pub struct DeleteLoginSession {
+    pub id: String,
+}
+
+pub struct DeleteExpiredLoginSessions;
@@ -0,0 +1,100 @@
+mod get {
+    use gnify::source::{PgSource, Read};
+    use sqlx::types::chrono::NaiveDateTime;
+
+    use crate::login::{GetLoginSession, LoginSession};
+
+    impl Read<PgSource> for GetLoginSession {
+        async fn read(
+            self,
+            connection: <PgSource as gnify::source::Source>::Connection<'_>,
+        ) -> Result<Self::Output, gnify::error::PersistenceError> {
+            let row: Option<LoginSessionRow> = sqlx::query_as!(
+                LoginSessionRow,
+                r#"
+                select id, data, expiration
+                from core.login_session
+                where id = $1 and expiration > CURRENT_TIMESTAMP;
+                "#,
+                self.id
+            )
+            .fetch_optional(connection)
+            .await?;
+            Ok(row.map(|row| LoginSession {
+                id: row.id,
+                data: row.data,
+                expiration: row.expiration.into(),
+            }))
+        }
+    }
+
+    struct LoginSessionRow {
+        id: String,
+        data: Vec<u8>,
+        expiration: NaiveDateTime,
+    }
+}
+mod write {
+    use gnify::source::{PgSource, Write};
+    use sqlx::types::chrono::NaiveDateTime;
+
+    use crate::login::{DeleteExpiredLoginSessions, DeleteLoginSession, LoginSession, WriteLoginSession};
+
+    impl Write<PgSource> for WriteLoginSession {
+        async fn write(
+            self,
+            connection: <PgSource as gnify::source::Source>::Connection<'_>,
+        ) -> Result<(), gnify::Error> {
+            let LoginSession { id, data, expiration } = self.session;
+            let expiration = NaiveDateTime::from(expiration);
+            sqlx::query!(
+                r#"
+                insert into core.login_session (id, data, expiration)
+                values ($1, $2, $3)
+                on conflict (id) do update set
+                    data = excluded.data,
+                    expiration = excluded.expiration;
+                "#,
+                id,
+                data,
+                expiration
+            )
+            .execute(connection)
+            .await?;
+            Ok(())
+        }
+    }
+
+    impl Write<PgSource> for DeleteLoginSession {
+        async fn write(
+            self,
+            connection: <PgSource as gnify::source::Source>::Connection<'_>,
+        ) -> Result<(), gnify::Error> {
+            sqlx::query!(
+                r#"
+                delete from core.login_session where id = $1;
+                "#,
+                self.id
+            )
+            .execute(connection)
+            .await?;
+            Ok(())
+        }
+    }
+
+    impl Write<PgSource> for DeleteExpiredLoginSessions {
+        async fn write(
+            self,
+            connection: <PgSource as gnify::source::Source>::Connection<'_>,
+        ) -> Result<(), gnify::Error> {
+            sqlx::query!(
+                r#"
+                delete from core.login_session where expiration <= CURRENT_TIMESTAMP;
+                "#
+            )
+            .execute(connection)
+            .await?;
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,359 @@
+use std::collections::HashSet;
+
+use gnify::{
+    error::{error, InvalidValue},
+    model::Record,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    actor::Actor,
+    device::{Device, DeviceView},
+    role::{DetailedRoleView, Role, RoleLevel},
+    user::DetailedUserView,
+    Privilege,
+};
+
+/// Named values a policy condition can look up on a subject or resource.
+/// Users and devices have a `site`, which scoped policies match against.
+pub trait Attributes {
+    fn attribute(&self, name: &str) -> Option<String>;
+}
+
+/// Whoever asks to perform an action.
+pub trait Subject: Attributes {
+    fn level(&self) -> RoleLevel;
+
+    fn holds(&self, privilege: &Privilege) -> bool;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Effect {
+    Allow,
+    Deny,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Condition {
+    /// The subject and the resource share the value of an attribute, e.g.
+    /// the subject's `id` and a user's `id`.
+    SameAttribute { subject: String, resource: String },
+    SubjectAttribute { name: String, value: String },
+    ResourceAttribute { name: String, value: String },
+    MinimumLevel { level: RoleLevel },
+}
+
+impl Condition {
+    fn holds(&self, subject: &impl Subject, resource: &impl Attributes) -> bool {
+        match self {
+            Condition::SameAttribute {
+                subject: subject_name,
+                resource: resource_name,
+            } => match (subject.attribute(subject_name), resource.attribute(resource_name)) {
+                (Some(left), Some(right)) => left == right,
+                _ => false,
+            },
+            Condition::SubjectAttribute { name, value } => {
+                subject.attribute(name).as_ref() == Some(value)
+            }
+            Condition::ResourceAttribute { name, value } => {
+                resource.attribute(name).as_ref() == Some(value)
+            }
+            Condition::MinimumLevel { level } => subject.level() >= *level,
+        }
+    }
+}
+
+/// A rule matching an action. It applies when the subject holds `privilege`,
+/// if any, and every condition holds.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Policy {
+    pub action: String,
+    pub effect: Effect,
+    #[serde(default)]
+    pub privilege: Option<Privilege>,
+    #[serde(default)]
+    pub conditions: Vec<Condition>,
+    /// Sites the rule is limited to, matched against the resource's `site`;
+    /// a resource without one is in none of them. Every site when empty.
+    #[serde(default)]
+    pub scopes: Vec<String>,
+}
+
+impl Policy {
+    fn applies(&self, subject: &impl Subject, action: &str, resource: &impl Attributes) -> bool {
+        self.action == action
+            && (self.scopes.is_empty()
+                || resource
+                    .attribute("site")
+                    .is_some_and(|site| self.scopes.contains(&site)))
+            && self
+                .privilege
+                .as_ref()
+                .is_none_or(|privilege| subject.holds(privilege))
+            && self
+                .conditions
+                .iter()
+                .all(|condition| condition.holds(subject, resource))
+    }
+}
+
+/// Policies evaluated deny-first: any applicable `deny` wins, otherwise an
+/// applicable `allow` is needed.
+#[derive(Debug, Clone, Default)]
+pub struct PolicySet(Vec<Policy>);
+
+impl PolicySet {
+    pub fn new(policies: Vec<Policy>) -> Result<Self, InvalidValue> {
+        if let Some(privilege) = policies
+            .iter()
+            .filter_map(|policy| policy.privilege.as_ref())
+            .find(|privilege| !privilege.is_registered())
+        {
+            return Err(InvalidValue::new(format!("Privilege {privilege}")));
+        }
+        Ok(Self(policies))
+    }
+
+    pub fn authorize(
+        &self,
+        subject: &impl Subject,
+        action: &str,
+        resource: &impl Attributes,
+    ) -> Effect {
+        let applicable: HashSet<Effect> = self
+            .0
+            .iter()
+            .filter(|policy| policy.applies(subject, action, resource))
+            .map(|policy| policy.effect)
+            .collect();
+        if applicable.contains(&Effect::Allow) && !applicable.contains(&Effect::Deny) {
+            Effect::Allow
+        } else {
+            Effect::Deny
+        }
+    }
+
+    pub fn ensure(
+        &self,
+        subject: &impl Subject,
+        action: &str,
+        resource: &impl Attributes,
+    ) -> gnify::error::Result<()> {
+        match self.authorize(subject, action, resource) {
+            Effect::Allow => Ok(()),
+            Effect::Deny => error("Not allowed to perform this action"),
+        }
+    }
+}
+
+impl Attributes for Actor {
+    fn attribute(&self, name: &str) -> Option<String> {
+        match name {
+            "id" => match self {
+                Actor::Device(_) => None,
+                _ => Some(self.id().to_string()),
+            },
+            "device" => match self {
+                Actor::Device(id) => Some(id.to_string()),
+                _ => None,
+            },
+            "level" => Some(Subject::level(self).rank().to_string()),
+            "site" => match self {
+                Actor::User { site, .. } => site.as_ref().map(ToString::to_string),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+impl Subject for Actor {
+    fn level(&self) -> RoleLevel {
+        match self {
+            Actor::System | Actor::Maintenance => RoleLevel::DEVELOPER,
+            Actor::User { level, .. } => *level,
+            Actor::Device(_) => RoleLevel::GUEST,
+        }
+    }
+
+    fn holds(&self, privilege: &Privilege) -> bool {
+        match self {
+            Actor::System | Actor::Maintenance => true,
+            Actor::User { privileges, .. } => privileges.contains(privilege),
+            Actor::Device(_) => false,
+        }
+    }
+}
+
+impl Attributes for DetailedUserView {
+    fn attribute(&self, name: &str) -> Option<String> {
+        match name {
+            "id" => Some(self.id().to_string()),
+            "username" => Some(self.username().to_string()),
+            "email" => self.email().map(ToString::to_string),
+            "level" => Some(self.level().rank().to_string()),
+            "site" => self.site().map(ToString::to_string),
+            _ => None,
+        }
+    }
+}
+
+impl Attributes for DeviceView {
+    fn attribute(&self, name: &str) -> Option<String> {
+        match name {
+            "id" => Some(self.id.to_string()),
+            "name" => Some(self.name.to_string()),
+            "status" => Some(self.status.as_str().to_string()),
+            "site" => self.site.as_ref().map(ToString::to_string),
+            "user_id" => match &self.sessions[..] {
+                [session] => Some(session.user_id.to_string()),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+/// A device about to be stored, e.g. one being registered.
+impl Attributes for Record<Device> {
+    fn attribute(&self, name: &str) -> Option<String> {
+        let device = self.state();
+        match name {
+            "id" => Some(self.id().to_string()),
+            "name" => Some(device.name.to_string()),
+            "status" => Some(device.status.as_str().to_string()),
+            "site" => device.site.as_ref().map(ToString::to_string),
+            _ => None,
+        }
+    }
+}
+
+impl Attributes for DetailedRoleView {
+    fn attribute(&self, name: &str) -> Option<String> {
+        match name {
+            "id" => Some(self.id().to_string()),
+            "name" => Some(self.name().to_string()),
+            "level" => Some(self.level().rank().to_string()),
+            _ => None,
+        }
+    }
+}
+
+/// A role about to be stored, e.g. one being created.
+impl Attributes for Record<Role> {
+    fn attribute(&self, name: &str) -> Option<String> {
+        let role = self.state();
+        match name {
+            "id" => Some(self.id().to_string()),
+            "name" => Some(role.name.to_string()),
+            "level" => Some(role.level.rank().to_string()),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use gnify::vo::ID;
+    use ulid::Ulid;
+
+    use super::*;
+    use crate::privilege::MANAGE_DEVICES;
+
+    struct Resource {
+        site: Option<&'static str>,
+    }
+
+    impl Attributes for Resource {
+        fn attribute(&self, name: &str) -> Option<String> {
+            match name {
+                "site" => self.site.map(String::from),
+                _ => None,
+            }
+        }
+    }
+
+    fn user(privileges: &[&str], site: Option<&str>) -> Actor {
+        Actor::User {
+            id: ID::new(Ulid::new()),
+            level: RoleLevel::OPERATOR,
+            privileges: privileges.iter().map(|privilege| privilege.parse().unwrap()).collect(),
+            site: site.map(|site| site.parse().unwrap()),
+        }
+    }
+
+    fn policy(effect: Effect, scopes: &[&str], conditions: Vec<Condition>) -> Policy {
+        Policy {
+            action: String::from(MANAGE_DEVICES),
+            effect,
+            privilege: Some(MANAGE_DEVICES.parse().unwrap()),
+            conditions,
+            scopes: scopes.iter().map(|scope| scope.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn scoped_policies_match_the_site_of_the_resource() {
+        let policies = PolicySet::new(vec![policy(Effect::Allow, &["north"], Vec::new())]).unwrap();
+        let manager = user(&[MANAGE_DEVICES], None);
+        let north = Resource { site: Some("north") };
+        let south = Resource { site: Some("south") };
+        assert_eq!(policies.authorize(&manager, MANAGE_DEVICES, &north), Effect::Allow);
+        assert_eq!(policies.authorize(&manager, MANAGE_DEVICES, &south), Effect::Deny);
+        assert_eq!(policies.authorize(&manager, MANAGE_DEVICES, &Resource { site: None }), Effect::Deny);
+    }
+
+    #[test]
+    fn unscoped_policies_match_every_site() {
+        let policies = PolicySet::new(vec![policy(Effect::Allow, &[], Vec::new())]).unwrap();
+        let manager = user(&[MANAGE_DEVICES], None);
+        assert_eq!(policies.authorize(&manager, MANAGE_DEVICES, &Resource { site: Some("north") }), Effect::Allow);
+        assert_eq!(policies.authorize(&manager, MANAGE_DEVICES, &Resource { site: None }), Effect::Allow);
+    }
+
+    #[test]
+    fn subjects_can_be_limited_to_their_own_site() {
+        let same_site = Condition::SameAttribute {
+            subject: String::from("site"),
+            resource: String::from("site"),
+        };
+        let policies = PolicySet::new(vec![policy(Effect::Allow, &[], vec![same_site])]).unwrap();
+        let manager = user(&[MANAGE_DEVICES], Some("north"));
+        assert_eq!(policies.authorize(&manager, MANAGE_DEVICES, &Resource { site: Some("north") }), Effect::Allow);
+        assert_eq!(policies.authorize(&manager, MANAGE_DEVICES, &Resource { site: Some("south") }), Effect::Deny);
+        let unplaced = user(&[MANAGE_DEVICES], None);
+        assert_eq!(policies.authorize(&unplaced, MANAGE_DEVICES, &Resource { site: None }), Effect::Deny);
+    }
+
+    #[test]
+    fn an_applicable_deny_wins() {
+        let policies = PolicySet::new(vec![
+            policy(Effect::Allow, &[], Vec::new()),
+            policy(Effect::Deny, &["south"], Vec::new()),
+        ])
+        .unwrap();
+        let manager = user(&[MANAGE_DEVICES], None);
+        assert_eq!(policies.authorize(&manager, MANAGE_DEVICES, &Resource { site: Some("north") }), Effect::Allow);
+        assert_eq!(policies.authorize(&manager, MANAGE_DEVICES, &Resource { site: Some("south") }), Effect::Deny);
+        assert!(policies.ensure(&manager, MANAGE_DEVICES, &Resource { site: Some("south") }).is_err());
+    }
+
+    #[test]
+    fn policies_need_their_privilege_and_action() {
+        let policies = PolicySet::new(vec![policy(Effect::Allow, &[], Vec::new())]).unwrap();
+        let resource = Resource { site: None };
+        assert_eq!(policies.authorize(&user(&[], None), MANAGE_DEVICES, &resource), Effect::Deny);
+        assert_eq!(policies.authorize(&user(&[MANAGE_DEVICES], None), "OTHER", &resource), Effect::Deny);
+        assert_eq!(PolicySet::default().authorize(&Actor::System, MANAGE_DEVICES, &resource), Effect::Deny);
+    }
+
+    #[test]
+    fn unregistered_privileges_are_rejected() {
+        let mut unknown = policy(Effect::Allow, &[], Vec::new());
+        unknown.privilege = Some("NOT A PRIVILEGE".parse().unwrap());
+        assert!(PolicySet::new(vec![unknown]).is_err());
+    }
+}
@@ -0,0 +1,196 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use gnify::{error::InvalidValue, vo::ID};
+use serde::Serialize;
+
+use crate::{
+    role::{Role, RoleName},
+    user::DetailedUserView,
+    Privilege,
+};
+
+mod bmc;
+mod catalogue;
+
+pub use bmc::*;
+pub use catalogue::*;
+
+/// Privileges that imply other privileges, e.g. "MANAGE USERS" implying
+/// "REGISTER USER".
+#[derive(Debug, Clone, Default)]
+pub struct PrivilegeGroups(HashMap<Privilege, HashSet<Privilege>>);
+
+impl PrivilegeGroups {
+    pub fn new<'a>(
+        groups: impl IntoIterator<Item = (&'a str, &'a [&'a str])>,
+    ) -> Result<Self, InvalidValue> {
+        let groups = groups
+            .into_iter()
+            .map(|(group, members)| {
+                let members = members
+                    .iter()
+                    .map(|member| member.parse())
+                    .collect::<Result<_, InvalidValue>>()?;
+                Ok((group.parse()?, members))
+            })
+            .collect::<Result<_, InvalidValue>>()?;
+        Ok(Self(groups))
+    }
+
+    pub fn catalogue() -> Self {
+        let groups = CATALOGUE
+            .iter()
+            .filter(|definition| !definition.implies.is_empty())
+            .map(|definition| (definition.name, definition.implies));
+        Self::new(groups).expect("Invalid privilege catalogue")
+    }
+
+    pub fn implied(&self, privilege: &Privilege) -> impl Iterator<Item = &Privilege> {
+        self.0.get(privilege).into_iter().flatten()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PrivilegeOrigin {
+    Direct,
+    Role { id: ID<Role>, name: RoleName },
+    /// Held by a parent of the user's role.
+    Inherited { id: ID<Role>, name: RoleName },
+    Group { privilege: Privilege },
+}
+
+/// The privileges a user actually holds, with where each one came from.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct EffectivePrivileges(HashMap<Privilege, Vec<PrivilegeOrigin>>);
+
+impl EffectivePrivileges {
+    pub fn resolve(user: &DetailedUserView, groups: &PrivilegeGroups) -> Self {
+        let direct = user
+            .active_privileges()
+            .map(|privilege| (privilege.clone(), PrivilegeOrigin::Direct));
+        let roles = user.active_roles().flat_map(|role| {
+            let own = role.privileges.iter().map(|privilege| {
+                let origin = PrivilegeOrigin::Role {
+                    id: role.id,
+                    name: role.name.clone(),
+                };
+                (privilege.clone(), origin)
+            });
+            let inherited = role.inherited_privileges.iter().map(|privilege| {
+                let origin = PrivilegeOrigin::Inherited {
+                    id: role.id,
+                    name: role.name.clone(),
+                };
+                (privilege.clone(), origin)
+            });
+            own.chain(inherited)
+        });
+        Self::expand(direct.chain(roles), groups)
+    }
+
+    fn expand(
+        grants: impl IntoIterator<Item = (Privilege, PrivilegeOrigin)>,
+        groups: &PrivilegeGroups,
+    ) -> Self {
+        let mut resolved: HashMap<Privilege, Vec<PrivilegeOrigin>> = HashMap::new();
+        let mut pending: VecDeque<_> = grants.into_iter().collect();
+        while let Some((privilege, origin)) = pending.pop_front() {
+            let origins = resolved.entry(privilege.clone()).or_default();
+            let first = origins.is_empty();
+            if !origins.contains(&origin) {
+                origins.push(origin);
+            }
+            if first {
+                pending.extend(groups.implied(&privilege).map(|implied| {
+                    let origin = PrivilegeOrigin::Group {
+                        privilege: privilege.clone(),
+                    };
+                    (implied.clone(), origin)
+                }));
+            }
+        }
+        Self(resolved)
+    }
+
+    pub fn contains(&self, privilege: &str) -> bool {
+        self.0.keys().any(|held| held.value() == privilege)
+    }
+
+    pub fn privileges(&self) -> impl Iterator<Item = &Privilege> {
+        self.0.keys()
+    }
+
+    pub fn origins(&self, privilege: &Privilege) -> &[PrivilegeOrigin] {
+        self.0.get(privilege).map(Vec::as_slice).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn privilege(value: &str) -> Privilege {
+        value.parse().unwrap()
+    }
+
+    #[test]
+    fn groups_grant_their_members_transitively() {
+        let groups = PrivilegeGroups::new([
+            ("MANAGE ALL", &["MANAGE USERS"][..]),
+            ("MANAGE USERS", &["REGISTER USER"][..]),
+        ])
+        .unwrap();
+        let resolved =
+            EffectivePrivileges::expand([(privilege("MANAGE ALL"), PrivilegeOrigin::Direct)], &groups);
+        assert!(resolved.contains("MANAGE USERS"));
+        assert!(resolved.contains("REGISTER USER"));
+        assert_eq!(
+            resolved.origins(&privilege("REGISTER USER")),
+            [PrivilegeOrigin::Group {
+                privilege: privilege("MANAGE USERS")
+            }]
+        );
+    }
+
+    #[test]
+    fn privileges_held_several_ways_keep_every_origin() {
+        let groups = PrivilegeGroups::new([("MANAGE USERS", &["REGISTER USER"][..])]).unwrap();
+        let resolved = EffectivePrivileges::expand(
+            [
+                (privilege("REGISTER USER"), PrivilegeOrigin::Direct),
+                (privilege("MANAGE USERS"), PrivilegeOrigin::Direct),
+            ],
+            &groups,
+        );
+        assert_eq!(
+            resolved.origins(&privilege("REGISTER USER")),
+            [
+                PrivilegeOrigin::Direct,
+                PrivilegeOrigin::Group {
+                    privilege: privilege("MANAGE USERS")
+                }
+            ]
+        );
+        assert_eq!(resolved.privileges().count(), 2);
+    }
+
+    #[test]
+    fn cyclic_groups_resolve_once() {
+        let groups = PrivilegeGroups::new([
+            ("MANAGE USERS", &["MANAGE ROLES"][..]),
+            ("MANAGE ROLES", &["MANAGE USERS"][..]),
+        ])
+        .unwrap();
+        let resolved =
+            EffectivePrivileges::expand([(privilege("MANAGE USERS"), PrivilegeOrigin::Direct)], &groups);
+        assert_eq!(resolved.privileges().count(), 2);
+    }
+
+    #[test]
+    fn ungranted_privileges_are_not_held() {
+        let resolved = EffectivePrivileges::expand([], &PrivilegeGroups::catalogue());
+        assert!(!resolved.contains(REGISTER_USER));
+        assert!(resolved.origins(&privilege(REGISTER_USER)).is_empty());
+    }
+}
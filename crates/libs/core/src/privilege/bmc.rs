@@ -0,0 +1,25 @@
+use gnify::source::BMC;
+use serde::Serialize;
+use ulid::Ulid;
+
+mod postgres;
+
+/// Mirrors [`super::CATALOGUE`] into `core.privilege` and
+/// `core.privilege_group`. Privileges no longer in the catalogue are marked
+/// deprecated and keep their grants.
+pub struct SyncPrivileges;
+
+/// Grants of privileges that are deprecated or were never registered.
+pub struct UnresolvedGrants;
+
+impl BMC for UnresolvedGrants {
+    type Output = Vec<UnresolvedGrant>;
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UnresolvedGrant {
+    /// `role` or `user`.
+    pub holder: String,
+    pub holder_id: Ulid,
+    pub privilege: String,
+}
@@ -0,0 +1,99 @@
+mod write {
+    use gnify::source::{PgSource, Write};
+
+    use crate::privilege::{SyncPrivileges, CATALOGUE};
+
+    impl Write<PgSource> for SyncPrivileges {
+        async fn write(
+            self,
+            connection: <PgSource as gnify::source::Source>::Connection<'_>,
+        ) -> Result<(), gnify::Error> {
+            let (names, descriptions): (Vec<String>, Vec<String>) = CATALOGUE
+                .iter()
+                .map(|definition| (definition.name.to_string(), definition.description.to_string()))
+                .unzip();
+            let (groups, members): (Vec<String>, Vec<String>) = CATALOGUE
+                .iter()
+                .flat_map(|definition| {
+                    definition
+                        .implies
+                        .iter()
+                        .map(|member| (definition.name.to_string(), member.to_string()))
+                })
+                .unzip();
+            sqlx::query!(
+                r#"
+                insert into core.privilege (name, description)
+                select * from unnest($1::text[], $2::text[])
+                    on conflict (name) do update set description = excluded.description, deprecated = false;
+                "#,
+                &names[..],
+                &descriptions[..]
+            )
+            .execute(&mut *connection)
+            .await?;
+            sqlx::query!(
+                r#"
+                update core.privilege set deprecated = true where name != all($1::text[]);
+                "#,
+                &names[..]
+            )
+            .execute(&mut *connection)
+            .await?;
+            sqlx::query!(
+                r#"
+                delete from core.privilege_group;
+                "#
+            )
+            .execute(&mut *connection)
+            .await?;
+            sqlx::query!(
+                r#"
+                insert into core.privilege_group (group_name, member)
+                select * from unnest($1::text[], $2::text[]);
+                "#,
+                &groups[..],
+                &members[..]
+            )
+            .execute(connection)
+            .await?;
+            Ok(())
+        }
+    }
+}
+
+mod unresolved {
+    use gnify::source::{PgSource, Read};
+
+    use crate::privilege::{UnresolvedGrant, UnresolvedGrants};
+
+    impl Read<PgSource> for UnresolvedGrants {
+        async fn read(
+            self,
+            connection: <PgSource as gnify::source::Source>::Connection<'_>,
+        ) -> Result<Self::Output, gnify::error::PersistenceError> {
+            let rows = sqlx::query!(
+                r#"
+                select 'role' as "holder!", rp.role_id as "holder_id!", rp.privilege as "privilege!"
+                from core.role_privilege rp left join core.privilege p on p.name = rp.privilege
+                where p.name is null or p.deprecated
+                union all
+                select 'user', up.user_id, up.privilege
+                from core.user_privilege up left join core.privilege p on p.name = up.privilege
+                where p.name is null or p.deprecated
+                order by 1, 2, 3;
+                "#
+            )
+            .fetch_all(connection)
+            .await?;
+            Ok(rows
+                .into_iter()
+                .map(|row| UnresolvedGrant {
+                    holder: row.holder,
+                    holder_id: row.holder_id.into(),
+                    privilege: row.privilege,
+                })
+                .collect())
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use std::collections::HashSet;
+
+use gnify::error::InvalidValue;
+use serde::Serialize;
+
+use crate::Privilege;
+
+#[derive(Debug, Serialize)]
+pub struct PrivilegeDefinition {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub implies: &'static [&'static str],
+}
+
+macro_rules! privileges {
+    ($($constant: ident = $name: literal: $description: literal $(=> [$($member: ident),* $(,)?])?;)*) => {
+        $(pub const $constant: &str = $name;)*
+
+        /// Every privilege known to the system. Group members are referenced
+        /// by constant so a misspelled member fails to compile.
+        pub static CATALOGUE: &[PrivilegeDefinition] = &[
+            $(PrivilegeDefinition {
+                name: $constant,
+                description: $description,
+                implies: &[$($($member),*)?],
+            },)*
+        ];
+    };
+}
+
+privileges! {
+    REGISTER_USER = "REGISTER USER": "Register new users";
+    GET_USER_DETAILS = "GET USER DETAILS": "Read the details of any user";
+    REGISTER_ROLE = "REGISTER ROLE": "Register new roles";
+    MANAGE_USERS = "MANAGE USERS": "Administer users" => [REGISTER_USER, GET_USER_DETAILS];
+    MANAGE_ROLES = "MANAGE ROLES": "Administer roles" => [REGISTER_ROLE, GET_USER_DETAILS];
+    MANAGE_DEVICES = "MANAGE DEVICES": "Administer devices";
+    MANAGE_WEBHOOKS = "MANAGE WEBHOOKS": "Subscribe partners to change events";
+    OPERATE_SYSTEM = "OPERATE SYSTEM": "Run maintenance, repair corrupt records and requeue event deliveries";
+}
+
+impl Privilege {
+    pub fn is_registered(&self) -> bool {
+        definition(self.value()).is_some()
+    }
+
+    /// Parses a privilege, rejecting any not declared in [`CATALOGUE`].
+    pub fn registered(value: &str) -> Result<Privilege, InvalidValue> {
+        let privilege: Privilege = value.parse()?;
+        if privilege.is_registered() {
+            Ok(privilege)
+        } else {
+            Err(InvalidValue::new(format!("Privilege {value}")))
+        }
+    }
+}
+
+pub fn definition(name: &str) -> Option<&'static PrivilegeDefinition> {
+    CATALOGUE.iter().find(|definition| definition.name == name)
+}
+
+/// Checks that every name is a valid [`Privilege`], declared once, and that
+/// groups don't imply themselves.
+pub fn validate_catalogue() -> Result<(), InvalidValue> {
+    let mut names = HashSet::new();
+    for definition in CATALOGUE {
+        definition.name.parse::<Privilege>()?;
+        if !names.insert(definition.name) {
+            return Err(InvalidValue::new(format!("duplicated privilege {}", definition.name)));
+        }
+    }
+    for definition in CATALOGUE {
+        let mut visited = HashSet::new();
+        let mut pending = definition.implies.to_vec();
+        while let Some(member) = pending.pop() {
+            if member == definition.name {
+                return Err(InvalidValue::new(format!("cyclic privilege group {}", definition.name)));
+            }
+            if visited.insert(member) {
+                pending.extend(definition_implies(member));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn definition_implies(name: &str) -> &'static [&'static str] {
+    definition(name).map(|definition| definition.implies).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_catalogue_is_valid() {
+        validate_catalogue().unwrap();
+    }
+
+    #[test]
+    fn only_catalogued_privileges_are_registered() {
+        assert_eq!(Privilege::registered(MANAGE_DEVICES).unwrap().value(), MANAGE_DEVICES);
+        assert!(Privilege::registered("LAUNCH ROCKETS").is_err());
+        assert!(!"LAUNCH ROCKETS".parse::<Privilege>().unwrap().is_registered());
+    }
+
+    #[test]
+    fn groups_list_the_privileges_they_imply() {
+        let definition = definition(MANAGE_USERS).unwrap();
+        assert_eq!(definition.implies, [REGISTER_USER, GET_USER_DETAILS]);
+        assert!(definition_implies(REGISTER_USER).is_empty());
+        assert!(definition_implies("LAUNCH ROCKETS").is_empty());
+    }
+}
@@ -12,37 +12,124 @@ use crate::Privilege;
 
 mod vo;
 mod bmc;
+mod event;
+mod update;
 mod view;
 
 pub use vo::*;
 pub use view::*;
 pub use bmc::*;
+pub use event::*;
+pub use update::*;
 
 #[derive(Debug, Model)]
 pub struct Role {
     pub(crate) name: RoleName,
     pub(crate) level: RoleLevel,
     pub(crate) privileges: HashSet<Privilege>,
+    pub(crate) parents: HashSet<ID<Role>>,
 }
 
 impl Role {
     pub fn new(
         id: Ulid,
         name: &str,
-        level: &str,
+        level: RoleLevel,
         privileges: HashSet<&str>,
+        parents: HashSet<Ulid>,
         author: Ulid,
     ) -> Result<Record<Self>, gnify::Error> {
+        if parents.contains(&id) {
+            return Err(InvalidValue::new("Role parents").into());
+        }
         let id = ID::new(id);
         let state = Self {
             name: name.parse()?,
-            level: level.parse()?,
+            level,
             privileges: privileges
                 .iter()
-                .map(|name| name.parse())
+                .map(|name| Privilege::registered(name))
                 .collect::<Result<_, InvalidValue>>()?,
+            parents: parents.into_iter().map(ID::new).collect(),
         };
         let version = Version::now(author);
-        Ok(Record::new(id, state, version))
+        let event = RoleCreated {
+            id,
+            name: state.name.clone(),
+            level: state.level,
+            privileges: state.privileges.clone(),
+            parents: state.parents.clone(),
+            version,
+        };
+        let mut record = Record::new(id, state, version);
+        record.record_event(event);
+        Ok(record)
+    }
+
+    /// Whether giving the role `id` these `parents` makes it its own
+    /// ancestor, given the `(role, parent)` links above the parents.
+    pub(crate) fn closes_cycle(
+        id: ID<Role>,
+        parents: &HashSet<ID<Role>>,
+        links: &[(ID<Role>, ID<Role>)],
+    ) -> bool {
+        let mut visited = HashSet::new();
+        let mut pending: Vec<ID<Role>> = parents.iter().copied().collect();
+        while let Some(role) = pending.pop() {
+            if role == id {
+                return true;
+            }
+            if visited.insert(role) {
+                pending.extend(
+                    links
+                        .iter()
+                        .filter(|(child, _)| *child == role)
+                        .map(|(_, parent)| *parent),
+                );
+            }
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn role() -> ID<Role> {
+        ID::new(Ulid::new())
+    }
+
+    #[test]
+    fn roles_cannot_be_created_as_their_own_parent() {
+        let id = Ulid::new();
+        let create = |parents| Role::new(id, "Cashier", RoleLevel::OPERATOR, HashSet::new(), parents, Ulid::nil());
+        assert!(create(HashSet::from([id])).is_err());
+        assert!(create(HashSet::from([Ulid::new()])).is_ok());
+    }
+
+    #[test]
+    fn parenting_a_role_by_itself_closes_a_cycle() {
+        let a = role();
+        assert!(Role::closes_cycle(a, &HashSet::from([a]), &[]));
+    }
+
+    #[test]
+    fn parenting_a_role_by_its_child_closes_a_cycle() {
+        let (a, b) = (role(), role());
+        assert!(Role::closes_cycle(a, &HashSet::from([b]), &[(b, a)]));
+    }
+
+    #[test]
+    fn cycles_through_several_ancestors_are_found() {
+        let (a, b, c) = (role(), role(), role());
+        assert!(Role::closes_cycle(a, &HashSet::from([c]), &[(c, b), (b, a)]));
+    }
+
+    #[test]
+    fn shared_ancestors_are_no_cycle() {
+        let (a, b, c, root) = (role(), role(), role(), role());
+        let links = [(b, root), (c, root), (root, role())];
+        assert!(!Role::closes_cycle(a, &HashSet::from([b, c]), &links));
     }
 }
@@ -1,7 +1,9 @@
-use gnify::{model::Record, source::BMC};
+use gnify::{model::Record, source::BMC, vo::{Version, ID}};
 use ulid::Ulid;
 
-use super::{view::DetailedRoleView, Role};
+use crate::actor::Actor;
+
+use super::{view::DetailedRoleView, NamedLevel, Role, RoleLadder};
 
 mod postgres;
 
@@ -12,6 +14,13 @@ pub struct GetRole {
 }
 
 impl GetRole {
+    pub fn by_id(value: Ulid) -> Self {
+        Self {
+            id: Some(value),
+            ..Default::default()
+        }
+    }
+
     pub fn by_name(value: &str) -> Self {
         Self {
             name: Some(value.to_string()),
@@ -24,6 +33,40 @@ impl BMC for GetRole {
     type Output = Option<DetailedRoleView>;
 }
 
+/// All roles ordered by level, highest first.
+pub struct ListRoles;
+
+impl BMC for ListRoles {
+    type Output = Vec<DetailedRoleView>;
+}
+
 pub struct WriteRole {
     pub record: Record<Role>,
+    pub actor: Actor,
+}
+
+/// Locks the role's row for the rest of the transaction.
+pub struct LockRole {
+    pub id: ID<Role>,
+}
+
+impl BMC for LockRole {
+    type Output = Option<Version>;
+}
+
+pub struct DeleteRole {
+    pub id: ID<Role>,
+    pub actor: Actor,
+}
+
+pub struct GetRoleLadder;
+
+impl BMC for GetRoleLadder {
+    type Output = RoleLadder;
+}
+
+/// Adds a level to the ladder or renames an existing rank.
+pub struct WriteRoleLevel {
+    pub level: NamedLevel,
+    pub actor: Actor,
 }
@@ -1,5 +1,5 @@
 mod get {
-    use gnify::{error::InvalidValue, source::{add_corrupt_record, PgSource, Read, RecordVersion}};
+    use gnify::{error::InvalidValue, source::{add_corrupt_record, PgSource, Read, RecordVersion}, vo::ID};
     use sqlx::types::Uuid;
 
     use crate::role::{bmc::GetRole, view::DetailedRoleView, RoleLevel};
@@ -18,11 +18,24 @@ mod get {
                     , r.version as "version: RecordVersion"
                     , r.first_version "first_version: RecordVersion"
                     , r.name
-                    , r.level
+                    , rl.rank as "level?"
                     , array (
                         select privilege from core.role_privilege where role_id = r.id
                     ) as "privileges!"
+                    , array (
+                        select parent_id from core.role_parent where role_id = r.id
+                    ) as "parents!"
+                    , array (
+                        with recursive ancestor(id) as (
+                            select parent_id from core.role_parent where role_id = r.id
+                            union
+                            select rp.parent_id from core.role_parent rp join ancestor a on rp.role_id = a.id
+                        )
+                        select distinct privilege from core.role_privilege
+                        where role_id in (select id from ancestor)
+                    ) as "inherited_privileges!"
                 from core.role r
+                    left join core.role_level rl on rl.rank = r.level
                     left join corrupt_record crec on crec.id = r.id
                 where crec.id is null and (
                     r.id is not distinct from $1 or
@@ -48,43 +61,198 @@ mod get {
         }
     }
 
-    struct RoleRow {
-        id: Uuid,
-        version: RecordVersion,
-        first_version: RecordVersion,
-        name: String,
-        level: i16,
-        privileges: Vec<String>,
+    pub(super) struct RoleRow {
+        pub(super) id: Uuid,
+        pub(super) version: RecordVersion,
+        pub(super) first_version: RecordVersion,
+        pub(super) name: String,
+        pub(super) level: Option<i16>,
+        pub(super) privileges: Vec<String>,
+        pub(super) parents: Vec<Uuid>,
+        pub(super) inherited_privileges: Vec<String>,
     }
 
-    fn map_row(row: RoleRow) -> Result<DetailedRoleView, InvalidValue> {
+    pub(super) fn map_row(row: RoleRow) -> Result<DetailedRoleView, InvalidValue> {
         Ok(DetailedRoleView {
             id: row.id.into(),
             version: row.version.try_into()?,
             first_version: row.first_version.try_into()?,
             name: row.name.parse()?,
-            level: RoleLevel::from(row.level),
+            level: row.level.map(RoleLevel::new).ok_or_else(|| InvalidValue::new("RoleLevel"))?,
             privileges: row.privileges.into_iter().map(|privilege| privilege.parse()).collect::<Result<_, InvalidValue>>()?,
+            parents: row.parents.into_iter().map(ID::from).collect(),
+            inherited_privileges: row.inherited_privileges.into_iter().map(|privilege| privilege.parse()).collect::<Result<_, InvalidValue>>()?,
         })
     }
 }
+mod list {
+    use gnify::source::{add_corrupt_record, PgSource, Read, RecordVersion};
+
+    use super::get::{map_row, RoleRow};
+    use crate::role::bmc::ListRoles;
+
+    impl Read<PgSource> for ListRoles {
+        async fn read(
+            self,
+            connection: <PgSource as gnify::source::Source>::Connection<'_>,
+        ) -> Result<Self::Output, gnify::error::PersistenceError> {
+            let rows: Vec<RoleRow> = sqlx::query_as!(
+                RoleRow,
+                r#"
+                select
+                      r.id
+                    , r.version as "version: RecordVersion"
+                    , r.first_version "first_version: RecordVersion"
+                    , r.name
+                    , rl.rank as "level?"
+                    , array (
+                        select privilege from core.role_privilege where role_id = r.id
+                    ) as "privileges!"
+                    , array (
+                        select parent_id from core.role_parent where role_id = r.id
+                    ) as "parents!"
+                    , array (
+                        with recursive ancestor(id) as (
+                            select parent_id from core.role_parent where role_id = r.id
+                            union
+                            select rp.parent_id from core.role_parent rp join ancestor a on rp.role_id = a.id
+                        )
+                        select distinct privilege from core.role_privilege
+                        where role_id in (select id from ancestor)
+                    ) as "inherited_privileges!"
+                from core.role r
+                    left join core.role_level rl on rl.rank = r.level
+                    left join corrupt_record crec on crec.id = r.id
+                where crec.id is null
+                order by r.level desc, r.name;
+                "#
+            )
+            .fetch_all(&mut *connection)
+            .await?;
+            let mut roles = Vec::with_capacity(rows.len());
+            for row in rows {
+                let id = row.id;
+                match map_row(row) {
+                    Ok(view) => roles.push(view),
+                    Err(iv) => add_corrupt_record(connection, id, "core.role", iv).await?,
+                }
+            }
+            Ok(roles)
+        }
+    }
+}
 mod write {
-    use gnify::source::{PgSource, RecordVersion, Write};
+    use gnify::{error::InvalidValue, source::{add_outbox_entries, PgSource, RecordVersion, Write}, vo::ID};
     use sqlx::types::Uuid;
 
-    use crate::role::{bmc::WriteRole, Role};
+    use crate::{role::{bmc::WriteRole, Role, RoleLevel}, Privilege};
 
     impl Write<PgSource> for WriteRole {
         async fn write(
             self,
             connection: <PgSource as gnify::source::Source>::Connection<'_>,
-        ) -> Result<(), gnify::error::PersistenceError> {
-            let record = self.record;
+        ) -> Result<(), gnify::Error> {
+            let WriteRole { record, actor } = self;
             let id = Uuid::from(record.id());
             let version = RecordVersion::from(record.version());
-            let Role { name, level, privileges } = record.state();
+            let Role { name, level, privileges, parents } = record.state();
+            if let Some(privilege) = privileges.iter().find(|privilege| !privilege.is_registered()) {
+                return Err(InvalidValue::new(format!("Privilege {privilege}")).into());
+            }
+            let current = sqlx::query!(
+                r#"
+                select
+                    r.level
+                    , array(select privilege from core.role_privilege where role_id = r.id) as "privileges!"
+                from core.role r
+                where r.id = $1;
+                "#,
+                id
+            )
+            .fetch_optional(&mut *connection)
+            .await?;
+            let granted: Vec<String> = current.as_ref().map(|current| current.privileges.clone()).unwrap_or_default();
+            if let Some(current) = &current {
+                actor.ensure_outranks(RoleLevel::new(current.level))?;
+            }
+            actor.ensure_outranks(*level)?;
+            let known = sqlx::query_scalar!(
+                r#"
+                select exists(select 1 from core.role_level where rank = $1) as "known!";
+                "#,
+                level.rank()
+            )
+            .fetch_one(&mut *connection)
+            .await?;
+            if !known {
+                return Err(InvalidValue::new("RoleLevel").into());
+            }
+            actor.ensure_holds(privileges.iter().filter(|privilege| !granted.contains(privilege)))?;
+            let parents: Vec<Uuid> = parents.iter().map(|parent| Uuid::from(*parent)).collect();
+            let existing = sqlx::query_scalar!(
+                r#"
+                select count(*) as "count!" from core.role where id = any($1::uuid[]);
+                "#,
+                &parents[..]
+            )
+            .fetch_one(&mut *connection)
+            .await?;
+            if existing as usize != parents.len() {
+                return Err(InvalidValue::new("Role parents").into());
+            }
+            let links = sqlx::query!(
+                r#"
+                with recursive link(role_id, parent_id) as (
+                    select role_id, parent_id from core.role_parent where role_id = any($1::uuid[])
+                    union
+                    select rp.role_id, rp.parent_id from core.role_parent rp join link l on rp.role_id = l.parent_id
+                )
+                select role_id as "role_id!", parent_id as "parent_id!" from link;
+                "#,
+                &parents[..]
+            )
+            .fetch_all(&mut *connection)
+            .await?
+            .into_iter()
+            .map(|link| (ID::from(link.role_id), ID::from(link.parent_id)))
+            .collect::<Vec<_>>();
+            if Role::closes_cycle(record.id(), &record.state().parents, &links) {
+                return Err(InvalidValue::new("Role parents").into());
+            }
+            let added_parents = sqlx::query!(
+                r#"
+                select
+                    r.level
+                    , array (
+                        with recursive ancestor(id) as (
+                            select r.id
+                            union
+                            select rp.parent_id from core.role_parent rp join ancestor a on rp.role_id = a.id
+                        )
+                        select distinct privilege from core.role_privilege
+                        where role_id in (select id from ancestor)
+                    ) as "privileges!"
+                from core.role r
+                where r.id = any($2::uuid[]) and not exists (
+                    select 1 from core.role_parent where role_id = $1 and parent_id = r.id
+                );
+                "#,
+                id,
+                &parents[..]
+            )
+            .fetch_all(&mut *connection)
+            .await?;
+            for parent in added_parents {
+                let privileges = parent
+                    .privileges
+                    .iter()
+                    .map(|privilege| privilege.parse())
+                    .collect::<Result<Vec<Privilege>, InvalidValue>>()?;
+                actor.ensure_outranks(RoleLevel::new(parent.level))?;
+                actor.ensure_holds(&privileges)?;
+            }
             let name = name.to_string();
-            let level = *level as i16;
+            let level = level.rank();
             let (ids, privileges): (Vec<Uuid>, Vec<String>) = privileges.iter().map(|privilege| (id, privilege.to_string())).unzip();
             sqlx::query!(
                 r#"
@@ -117,7 +285,192 @@ mod write {
                 &ids[..],
                 &privileges[..],
             ).execute(&mut *connection).await?;
+            sqlx::query!(
+                r#"
+                with cte as (
+                    delete from core.role_parent where role_id = $1 and parent_id != all($2::uuid[])
+                )
+                insert into core.role_parent (role_id, parent_id) select $1, unnest($2::uuid[])
+                    on conflict (role_id, parent_id) do nothing;
+                "#,
+                id,
+                &parents[..],
+            ).execute(&mut *connection).await?;
+            add_outbox_entries(connection, record.events()).await?;
+            Ok(())
+        }
+    }
+}
+mod delete {
+    use gnify::source::{PgSource, Write};
+    use sqlx::types::Uuid;
+
+    use crate::role::{bmc::DeleteRole, RoleLevel};
+
+    impl Write<PgSource> for DeleteRole {
+        async fn write(
+            self,
+            connection: <PgSource as gnify::source::Source>::Connection<'_>,
+        ) -> Result<(), gnify::Error> {
+            let id = Uuid::from(self.id);
+            let level = sqlx::query_scalar!(
+                r#"
+                select level from core.role where id = $1;
+                "#,
+                id
+            )
+            .fetch_optional(&mut *connection)
+            .await?;
+            let Some(level) = level else {
+                return Ok(());
+            };
+            self.actor.ensure_outranks(RoleLevel::new(level))?;
+            sqlx::query!(
+                r#"
+                delete from core.user_role where role_id = $1;
+                "#,
+                id
+            )
+            .execute(&mut *connection)
+            .await?;
+            sqlx::query!(
+                r#"
+                delete from core.role_privilege where role_id = $1;
+                "#,
+                id
+            )
+            .execute(&mut *connection)
+            .await?;
+            sqlx::query!(
+                r#"
+                delete from core.role where id = $1;
+                "#,
+                id
+            )
+            .execute(connection)
+            .await?;
             Ok(())
         }
     }
-}
\ No newline at end of file
+}
+
+mod ladder {
+    use gnify::{
+        error::InvalidValue,
+        source::{PgSource, Read, Write},
+    };
+
+    use crate::role::{
+        bmc::{GetRoleLadder, WriteRoleLevel},
+        NamedLevel, RoleLadder, RoleLevel,
+    };
+
+    impl Read<PgSource> for GetRoleLadder {
+        async fn read(
+            self,
+            connection: <PgSource as gnify::source::Source>::Connection<'_>,
+        ) -> Result<Self::Output, gnify::error::PersistenceError> {
+            let rows = sqlx::query!(
+                r#"
+                select rank, name from core.role_level order by rank;
+                "#
+            )
+            .fetch_all(connection)
+            .await?;
+            let levels = rows
+                .into_iter()
+                .map(|row| {
+                    Ok(NamedLevel {
+                        name: row.name.parse()?,
+                        level: RoleLevel::new(row.rank),
+                    })
+                })
+                .collect::<Result<_, InvalidValue>>()
+                .map_err(|iv| gnify::error::PersistenceError::new(iv.to_string()))?;
+            RoleLadder::new(levels).map_err(|iv| gnify::error::PersistenceError::new(iv.to_string()))
+        }
+    }
+
+    impl Write<PgSource> for WriteRoleLevel {
+        async fn write(
+            self,
+            connection: <PgSource as gnify::source::Source>::Connection<'_>,
+        ) -> Result<(), gnify::Error> {
+            let WriteRoleLevel { level, actor } = self;
+            actor.ensure_outranks(level.level)?;
+            sqlx::query!(
+                r#"
+                insert into core.role_level (rank, name) values ($1, $2)
+                    on conflict (rank) do update set name = excluded.name;
+                "#,
+                level.level.rank(),
+                level.name.to_string()
+            )
+            .execute(connection)
+            .await?;
+            Ok(())
+        }
+    }
+}
+
+mod persist {
+    use gnify::{
+        error::PersistenceError,
+        repository::{Load, Persist},
+        source::{PgSource, Read, RecordVersion},
+        vo::{Version, ID},
+    };
+    use sqlx::types::Uuid;
+
+    use crate::{
+        actor::Actor,
+        role::{
+            bmc::{DeleteRole, GetRole, LockRole, WriteRole},
+            Role,
+        },
+    };
+
+    impl Read<PgSource> for LockRole {
+        async fn read(
+            self,
+            connection: <PgSource as gnify::source::Source>::Connection<'_>,
+        ) -> Result<Self::Output, PersistenceError> {
+            let version = sqlx::query_scalar!(
+                r#"
+                select version as "version: RecordVersion" from core.role where id = $1 for update;
+                "#,
+                Uuid::from(self.id)
+            )
+            .fetch_optional(connection)
+            .await?;
+            version
+                .map(Version::try_from)
+                .transpose()
+                .map_err(|iv| PersistenceError::new(iv.to_string()))
+        }
+    }
+
+    impl Persist<PgSource> for Role {
+        type Author = Actor;
+        type Get = Load<GetRole>;
+        type Lock = LockRole;
+        type Save = WriteRole;
+        type Delete = DeleteRole;
+
+        fn get(id: ID<Self>) -> Self::Get {
+            Load(GetRole::by_id(id.value()))
+        }
+
+        fn lock(id: ID<Self>) -> Self::Lock {
+            LockRole { id }
+        }
+
+        fn save(record: gnify::Record<Self>, author: Actor) -> Self::Save {
+            WriteRole { record, actor: author }
+        }
+
+        fn delete(id: ID<Self>, author: Actor) -> Self::Delete {
+            DeleteRole { id, actor: author }
+        }
+    }
+}
@@ -0,0 +1,53 @@
+use std::collections::HashSet;
+
+use gnify::{
+    domain_event,
+    vo::{Version, ID},
+};
+use serde::Serialize;
+
+use crate::Privilege;
+
+use super::{Role, RoleLevel, RoleName};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RoleCreated {
+    pub id: ID<Role>,
+    pub name: RoleName,
+    pub level: RoleLevel,
+    pub privileges: HashSet<Privilege>,
+    pub parents: HashSet<ID<Role>>,
+    pub version: Version,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RoleRenamed {
+    pub id: ID<Role>,
+    pub name: RoleName,
+    pub version: Version,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RoleLevelChanged {
+    pub id: ID<Role>,
+    pub level: RoleLevel,
+    pub version: Version,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RolePrivilegesChanged {
+    pub id: ID<Role>,
+    pub added: HashSet<Privilege>,
+    pub removed: HashSet<Privilege>,
+    pub version: Version,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RoleParentsChanged {
+    pub id: ID<Role>,
+    pub added: HashSet<ID<Role>>,
+    pub removed: HashSet<ID<Role>>,
+    pub version: Version,
+}
+
+domain_event!(Role: RoleCreated, RoleRenamed, RoleLevelChanged, RolePrivilegesChanged, RoleParentsChanged);
@@ -0,0 +1,130 @@
+use std::{collections::HashSet, sync::Arc};
+
+use gnify::{error::InvalidValue, event::DomainEvent, model::RecordUpdate, vo::{Version, ID}};
+
+use crate::Privilege;
+
+use super::{Role, RoleLevel, RoleLevelChanged, RoleName, RoleParentsChanged, RolePrivilegesChanged, RoleRenamed};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoleUpdate {
+    pub name: RoleName,
+    pub level: RoleLevel,
+    pub privileges: HashSet<Privilege>,
+    pub parents: HashSet<ID<Role>>,
+}
+
+impl RecordUpdate for RoleUpdate {
+    type Model = Role;
+
+    fn new(model: &Role, _version: Version) -> Self {
+        Self {
+            name: model.name.clone(),
+            level: model.level,
+            privileges: model.privileges.clone(),
+            parents: model.parents.clone(),
+        }
+    }
+
+    fn apply(self, state: &mut Role) {
+        state.name = self.name;
+        state.level = self.level;
+        state.privileges = self.privileges;
+        state.parents = self.parents;
+    }
+
+    fn events(
+        &self,
+        previous: &Self,
+        id: &ID<Role>,
+        version: Version,
+    ) -> Result<Vec<Arc<dyn DomainEvent>>, InvalidValue> {
+        let mut events: Vec<Arc<dyn DomainEvent>> = Vec::new();
+        let id = *id;
+        if self.name != previous.name {
+            events.push(Arc::new(RoleRenamed { id, name: self.name.clone(), version }));
+        }
+        if self.level != previous.level {
+            events.push(Arc::new(RoleLevelChanged { id, level: self.level, version }));
+        }
+        if self.privileges != previous.privileges {
+            events.push(Arc::new(RolePrivilegesChanged {
+                id,
+                added: self.privileges.difference(&previous.privileges).cloned().collect(),
+                removed: previous.privileges.difference(&self.privileges).cloned().collect(),
+                version,
+            }));
+        }
+        if self.parents != previous.parents {
+            events.push(Arc::new(RoleParentsChanged {
+                id,
+                added: self.parents.difference(&previous.parents).copied().collect(),
+                removed: previous.parents.difference(&self.parents).copied().collect(),
+                version,
+            }));
+        }
+        Ok(events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use gnify::model::Record;
+    use ulid::Ulid;
+
+    use super::*;
+    use crate::{
+        privilege::{GET_USER_DETAILS, MANAGE_DEVICES, REGISTER_USER},
+        role::RoleCreated,
+    };
+
+    fn role() -> Record<Role> {
+        let privileges = HashSet::from([REGISTER_USER, GET_USER_DETAILS]);
+        Role::new(Ulid::new(), "Cashier", RoleLevel::OPERATOR, privileges, HashSet::new(), Ulid::nil()).unwrap()
+    }
+
+    #[test]
+    fn new_roles_announce_their_creation() {
+        let record = role();
+        let created = record.events()[0].downcast_ref::<RoleCreated>().unwrap();
+        assert_eq!(created.name.value(), "Cashier");
+        assert_eq!(created.privileges.len(), 2);
+    }
+
+    #[test]
+    fn privilege_events_list_what_was_added_and_removed() {
+        let mut record = role();
+        record.take_events();
+        record
+            .update(Ulid::new(), |update: &mut RoleUpdate| {
+                update.privileges.remove(&Privilege::registered(REGISTER_USER)?);
+                update.privileges.insert(Privilege::registered(MANAGE_DEVICES)?);
+                update.level = RoleLevel::MANAGER;
+                Ok(())
+            })
+            .unwrap();
+        let names: Vec<&str> = record.events().iter().map(|event| event.name()).collect();
+        assert_eq!(names, ["RoleLevelChanged", "RolePrivilegesChanged"]);
+        let event = record.events()[1].downcast_ref::<RolePrivilegesChanged>().unwrap();
+        assert_eq!(event.added, HashSet::from([Privilege::registered(MANAGE_DEVICES).unwrap()]));
+        assert_eq!(event.removed, HashSet::from([Privilege::registered(REGISTER_USER).unwrap()]));
+    }
+
+    #[test]
+    fn parent_events_list_what_was_added_and_removed() {
+        let mut record = role();
+        record.take_events();
+        let parent = ID::new(Ulid::new());
+        record
+            .update(Ulid::new(), |update: &mut RoleUpdate| {
+                update.parents.insert(parent);
+                update.name = "Head Cashier".parse()?;
+                Ok(())
+            })
+            .unwrap();
+        let event = record.events()[1].downcast_ref::<RoleParentsChanged>().unwrap();
+        assert_eq!(event.added, HashSet::from([parent]));
+        assert!(event.removed.is_empty());
+        assert!(record.events()[0].downcast_ref::<RoleRenamed>().is_some());
+    }
+}
@@ -1,6 +1,6 @@
 use std::collections::HashSet;
 
-use gnify::{model::Record, vo::{Version, ID}};
+use gnify::{model::Record, repository::View, vo::{Version, ID}};
 use serde::Serialize;
 
 use crate::Privilege;
@@ -15,17 +15,20 @@ pub struct DetailedRoleView {
     pub(crate) name: RoleName,
     pub(crate) level: RoleLevel,
     pub(crate) privileges: HashSet<Privilege>,
+    pub(crate) parents: HashSet<ID<Role>>,
+    pub(crate) inherited_privileges: HashSet<Privilege>,
 }
 
 impl DetailedRoleView {
     pub fn as_record(self) -> Record<Role> {
-        let DetailedRoleView { id, version, first_version: _, name, level, privileges } = self;
+        let DetailedRoleView { id, version, first_version: _, name, level, privileges, parents, inherited_privileges: _ } = self;
         let state = Role {
             name,
             level,
-            privileges
+            privileges,
+            parents
         };
-        Record::new(id, state, version)
+        Record::loaded(id, state, version)
     }
     pub fn id(&self) -> ID<Role> {
         self.id
@@ -50,4 +53,21 @@ impl DetailedRoleView {
     pub fn privileges(&self) -> &HashSet<Privilege> {
         &self.privileges
     }
-}
\ No newline at end of file
+
+    pub fn parents(&self) -> &HashSet<ID<Role>> {
+        &self.parents
+    }
+
+    /// Privileges obtained from parent roles, transitively.
+    pub fn inherited_privileges(&self) -> &HashSet<Privilege> {
+        &self.inherited_privileges
+    }
+}
+
+impl View for DetailedRoleView {
+    type Model = Role;
+
+    fn into_record(self) -> Record<Role> {
+        self.as_record()
+    }
+}
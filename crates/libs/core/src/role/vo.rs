@@ -1,4 +1,4 @@
-use std::str::FromStr;
+use std::collections::HashSet;
 
 use gnify::{error::InvalidValue, text};
 use serde::{Deserialize, Serialize};
@@ -7,45 +7,154 @@ text! {
     RoleName: r"^(\p{L}+\s)*\p{L}+$"
 }
 
+text! {
+    RoleLevelName: r"^(\p{L}+\s)*\p{L}+$"
+}
+
+/// A rung on the role ladder. Levels compare by rank; their names are data,
+/// see [`RoleLadder`].
 #[derive(
-    Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, sqlx::Type, Clone, Copy, Default,
+    Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, Clone, Copy, Default,
 )]
-#[repr(u8)]
-pub enum RoleLevel {
-    Developer = 4,
-    Administrator = 3,
-    Manager = 2,
-    Operator = 1,
-    #[default]
-    Guest = 0,
-}
+#[serde(transparent)]
+pub struct RoleLevel(i16);
 
 impl RoleLevel {
-    pub fn from(value: impl TryInto<u8>) -> Self {
-        let value = value.try_into()
-            .unwrap_or(0);
-
-        match value {
-            4 => Self::Developer,
-            3 => Self::Administrator,
-            2 => Self::Manager,
-            1 => Self::Operator,
-            _ => Self::Guest
+    pub const DEVELOPER: Self = Self(40);
+    pub const ADMINISTRATOR: Self = Self(30);
+    pub const MANAGER: Self = Self(20);
+    pub const OPERATOR: Self = Self(10);
+    pub const GUEST: Self = Self(0);
+
+    pub const fn new(rank: i16) -> Self {
+        Self(rank)
+    }
+
+    pub fn rank(&self) -> i16 {
+        self.0
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NamedLevel {
+    pub name: RoleLevelName,
+    pub level: RoleLevel,
+}
+
+/// The ordered, named levels available to roles, stored in
+/// `core.role_level`. Defaults to Guest < Operator < Manager < Administrator
+/// < Developer.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RoleLadder(Vec<NamedLevel>);
+
+impl Default for RoleLadder {
+    fn default() -> Self {
+        let levels = [
+            ("Guest", RoleLevel::GUEST),
+            ("Operator", RoleLevel::OPERATOR),
+            ("Manager", RoleLevel::MANAGER),
+            ("Administrator", RoleLevel::ADMINISTRATOR),
+            ("Developer", RoleLevel::DEVELOPER),
+        ];
+        Self(
+            levels
+                .into_iter()
+                .map(|(name, level)| NamedLevel {
+                    name: name.parse().expect("invalid default level name"),
+                    level,
+                })
+                .collect(),
+        )
+    }
+}
+
+impl RoleLadder {
+    pub fn new(mut levels: Vec<NamedLevel>) -> Result<Self, InvalidValue> {
+        let mut names = HashSet::new();
+        let mut ranks = HashSet::new();
+        for level in &levels {
+            if !names.insert(level.name.to_lowercase()) || !ranks.insert(level.level) {
+                return Err(InvalidValue::new("RoleLadder"));
+            }
         }
+        levels.sort_by_key(|level| level.level);
+        Ok(Self(levels))
+    }
+
+    /// Looks a level up by name, ignoring case.
+    pub fn level(&self, name: &str) -> Result<RoleLevel, InvalidValue> {
+        let name = name.trim().to_lowercase();
+        self.0
+            .iter()
+            .find(|level| level.name.to_lowercase() == name)
+            .map(|level| level.level)
+            .ok_or_else(|| InvalidValue::new("RoleLevel"))
+    }
+
+    pub fn name(&self, level: RoleLevel) -> Option<&RoleLevelName> {
+        self.0
+            .iter()
+            .find(|named| named.level == level)
+            .map(|named| &named.name)
+    }
+
+    pub fn contains(&self, level: RoleLevel) -> bool {
+        self.name(level).is_some()
+    }
+
+    pub fn levels(&self) -> &[NamedLevel] {
+        &self.0
     }
 }
 
-impl FromStr for RoleLevel {
-    type Err = InvalidValue;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s.trim().to_lowercase().as_ref() {
-            "developer" => Ok(RoleLevel::Developer),
-            "administrator" => Ok(RoleLevel::Administrator),
-            "manager" => Ok(RoleLevel::Manager),
-            "operator" => Ok(RoleLevel::Operator),
-            "guest" => Ok(RoleLevel::Guest),
-            _ => Err(InvalidValue::new("RoleLevel")),
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn named(name: &str, rank: i16) -> NamedLevel {
+        NamedLevel {
+            name: name.parse().unwrap(),
+            level: RoleLevel::new(rank),
         }
     }
+
+    #[test]
+    fn default_ladder_is_ordered_by_rank() {
+        let ladder = RoleLadder::default();
+        let ranks: Vec<i16> = ladder.levels().iter().map(|named| named.level.rank()).collect();
+        assert_eq!(ranks, [0, 10, 20, 30, 40]);
+        assert_eq!(ladder.level("Administrator").unwrap(), RoleLevel::ADMINISTRATOR);
+    }
+
+    #[test]
+    fn new_sorts_levels_by_rank() {
+        let ladder = RoleLadder::new(vec![named("High", 20), named("Low", 5), named("Middle", 10)]).unwrap();
+        let names: Vec<&str> = ladder.levels().iter().map(|named| named.name.value()).collect();
+        assert_eq!(names, ["Low", "Middle", "High"]);
+    }
+
+    #[test]
+    fn new_rejects_duplicate_names_ignoring_case() {
+        assert!(RoleLadder::new(vec![named("Staff", 1), named("staff", 2)]).is_err());
+    }
+
+    #[test]
+    fn new_rejects_duplicate_ranks() {
+        assert!(RoleLadder::new(vec![named("Staff", 1), named("Crew", 1)]).is_err());
+    }
+
+    #[test]
+    fn level_ignores_case_and_surrounding_space() {
+        let ladder = RoleLadder::default();
+        assert_eq!(ladder.level("  manager ").unwrap(), RoleLevel::MANAGER);
+        assert!(ladder.level("Owner").is_err());
+    }
+
+    #[test]
+    fn contains_only_levels_on_the_ladder() {
+        let ladder = RoleLadder::default();
+        assert!(ladder.contains(RoleLevel::OPERATOR));
+        assert!(!ladder.contains(RoleLevel::new(15)));
+        assert_eq!(ladder.name(RoleLevel::GUEST).map(|name| name.value()), Some("Guest"));
+    }
 }
@@ -1,4 +1,4 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
 use gnify::{
     model::Record,
@@ -7,13 +7,17 @@ use gnify::{
 };
 use ulid::Ulid;
 
-use crate::{role::Role, Privilege};
+use crate::{role::Role, Privilege, Site};
 
 mod bmc;
+mod event;
+mod update;
 mod view;
 mod vo;
 
 pub use bmc::*;
+pub use event::*;
+pub use update::*;
 pub use view::*;
 pub use vo::*;
 
@@ -22,8 +26,9 @@ pub struct User {
     pub(crate) username: Username,
     pub(crate) password: Password,
     pub(crate) email: Option<Email>,
-    pub(crate) role_id: Option<ID<Role>>,
-    pub(crate) privileges: HashSet<Privilege>,
+    pub(crate) roles: HashMap<ID<Role>, Validity>,
+    pub(crate) privileges: HashMap<Privilege, Validity>,
+    pub(crate) site: Option<Site>,
 }
 
 impl User {
@@ -32,17 +37,31 @@ impl User {
         username: &str,
         password: &str,
         email: Option<&str>,
-        role_id: Option<Ulid>,
+        roles: HashSet<Ulid>,
         author: Ulid,
     ) -> Result<Record<User>, gnify::Error> {
         let state = User {
             username: username.parse()?,
             password: Password::generate(password)?,
             email: email.map(str::parse).transpose()?,
-            role_id: role_id.map(ID::new),
-            privileges: HashSet::new(),
+            roles: roles
+                .into_iter()
+                .map(|role| (ID::new(role), Validity::default()))
+                .collect(),
+            privileges: HashMap::new(),
+            site: None,
         };
+        let id = ID::new(id);
         let version = Version::now(author);
-        Ok(Record::new(ID::new(id), state, version))
+        let event = UserCreated {
+            id,
+            username: state.username.clone(),
+            email: state.email.clone(),
+            roles: state.roles.clone(),
+            version,
+        };
+        let mut record = Record::new(id, state, version);
+        record.record_event(event);
+        Ok(record)
     }
 }
@@ -1,6 +1,8 @@
-use gnify::{model::Record, source::BMC};
+use gnify::{model::Record, source::BMC, vo::{Version, ID}};
 use ulid::Ulid;
 
+use crate::{actor::Actor, role::RoleLevel};
+
 use super::{view::DetailedUserView, User};
 
 mod postgres; 
@@ -24,5 +26,38 @@ impl BMC for GetUser {
 
 
 pub struct WriteUser {
-    pub record: Record<User>
-}
\ No newline at end of file
+    pub record: Record<User>,
+    pub actor: Actor,
+}
+
+/// Locks the user's row for the rest of the transaction.
+pub struct LockUser {
+    pub id: ID<User>,
+}
+
+impl BMC for LockUser {
+    type Output = Option<Version>;
+}
+
+pub struct DeleteUser {
+    pub id: ID<User>,
+    pub actor: Actor,
+}
+
+/// Removes privilege grants and role assignments past their `valid_until`,
+/// stamping a new [`gnify::vo::Version`] by [`Actor::Maintenance`] on every
+/// affected user and announcing what each of them lost.
+pub struct ExpireGrants;
+
+impl ExpireGrants {
+    pub const TASK: &'static str = "expire grants";
+}
+
+/// Whether any user holds an active role at `level` or above.
+pub struct ExistsUserAtLevel {
+    pub level: RoleLevel,
+}
+
+impl BMC for ExistsUserAtLevel {
+    type Output = bool;
+}
@@ -1,18 +1,20 @@
 mod get {
-    use std::collections::HashSet;
+    use std::collections::{HashMap, HashSet};
 
     use gnify::{
         error::InvalidValue,
-        source::{PgSource, Read, RecordVersion},
+        source::{add_corrupt_record, PgSource, Read, RecordVersion},
         vo::{Version, ID},
     };
-    use sqlx::types::Uuid;
+    use serde::Deserialize;
+    use sqlx::types::{chrono::NaiveDateTime, Json, Uuid};
 
     use crate::{
         role::RoleLevel,
         user::{
             bmc::GetUser,
             view::{DetailedUserView, UserRole},
+            Validity,
         },
         Privilege,
     };
@@ -32,9 +34,20 @@ mod get {
                     , u.first_version as "first_version: RecordVersion"
                     , u.username
                     , u.email
+                    , u.site
                     , u.password
-                    , u.role_id
-                    , array(select privilege from core.user_privilege where user_id = u.id) as "privileges!"
+                    , coalesce(
+                        (
+                            select json_agg(json_build_object(
+                                'privilege', privilege,
+                                'valid_from', valid_from,
+                                'valid_until', valid_until
+                            ))
+                            from core.user_privilege
+                            where user_id = u.id and coalesce(valid_until > CURRENT_TIMESTAMP, true)
+                        ),
+                        '[]'
+                    ) as "privileges!: Json<Vec<PrivilegeGrantRow>>"
                 from core.user u
                     left join public.corrupt_record crec on u.id = crec.id
                 where crec.id is null and (
@@ -54,24 +67,55 @@ mod get {
                 return Ok(None);
             };
 
-            let role_row: Option<RoleRow> = sqlx::query_as!(
+            let role_rows: Vec<RoleRow> = sqlx::query_as!(
             RoleRow,
             r#"
             select 
                 r.id
                 , r.name
-                , r.level
+                , rl.rank as "level?"
                 , array(select privilege from core.role_privilege where role_id = r.id) as "privileges!"
-            from core.role r
+                , array(
+                    with recursive ancestor(id) as (
+                        select parent_id from core.role_parent where role_id = r.id
+                        union
+                        select rp.parent_id from core.role_parent rp join ancestor a on rp.role_id = a.id
+                    )
+                    select distinct privilege from core.role_privilege
+                    where role_id in (select id from ancestor)
+                ) as "inherited_privileges!"
+                , ur.valid_from
+                , ur.valid_until
+                , crec.id is not null as "corrupt!"
+            from core.user_role ur
+                join core.role r on r.id = ur.role_id
+                left join core.role_level rl on rl.rank = r.level
                 left join public.corrupt_record crec on r.id = crec.id
-            where crec.id is null and r.id = $1;
+            where ur.user_id = $1
+                and coalesce(ur.valid_until > CURRENT_TIMESTAMP, true)
+            order by r.level desc, r.name;
             "#,
-            user_row.role_id
+            user_row.id
         )
-        .fetch_optional(&mut *connection)
+        .fetch_all(&mut *connection)
         .await?;
+            let mut roles = Vec::with_capacity(role_rows.len());
+            let mut unreadable = Vec::new();
+            for row in role_rows {
+                if row.corrupt {
+                    unreadable.push(row);
+                    continue;
+                }
+                match map_role(&row) {
+                    Ok(role) => roles.push(role),
+                    Err(iv) => {
+                        add_corrupt_record(&mut *connection, row.id, "core.role", iv).await?;
+                        unreadable.push(row);
+                    }
+                }
+            }
             let id = user_row.id;
-            match map_rows(user_row, role_row) {
+            match map_rows(user_row, roles, unreadable) {
                 Ok(view) => Ok(Some(view)),
                 Err(iv) => {
                     sqlx::query!(
@@ -94,119 +138,482 @@ mod get {
         first_version: RecordVersion,
         username: String,
         email: Option<String>,
+        site: Option<String>,
         password: String,
-        privileges: Vec<String>,
-        role_id: Uuid,
+        privileges: Json<Vec<PrivilegeGrantRow>>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct PrivilegeGrantRow {
+        privilege: String,
+        valid_from: Option<NaiveDateTime>,
+        valid_until: Option<NaiveDateTime>,
     }
 
     struct RoleRow {
         id: Uuid,
         name: String,
-        level: i16,
+        level: Option<i16>,
         privileges: Vec<String>,
+        inherited_privileges: Vec<String>,
+        valid_from: Option<NaiveDateTime>,
+        valid_until: Option<NaiveDateTime>,
+        corrupt: bool,
     }
 
+    fn map_role(row: &RoleRow) -> Result<UserRole, InvalidValue> {
+        Ok(UserRole {
+            id: ID::from(row.id),
+            name: row.name.parse()?,
+            level: row.level.map(RoleLevel::new).ok_or_else(|| InvalidValue::new("RoleLevel"))?,
+            privileges: row
+                .privileges
+                .iter()
+                .map(|value| value.parse())
+                .collect::<Result<HashSet<Privilege>, InvalidValue>>()?,
+            inherited_privileges: row
+                .inherited_privileges
+                .iter()
+                .map(|value| value.parse())
+                .collect::<Result<HashSet<Privilege>, InvalidValue>>()?,
+            validity: Validity::new(row.valid_from, row.valid_until)?,
+        })
+    }
+
+    /// Assignments of roles that can't be read are kept apart, so that the
+    /// user can still be saved without losing them.
     fn map_rows(
         user_row: UserRow,
-        role_row: Option<RoleRow>,
+        roles: Vec<UserRole>,
+        unreadable: Vec<RoleRow>,
     ) -> Result<DetailedUserView, InvalidValue> {
         let privileges = user_row
             .privileges
+            .0
             .into_iter()
-            .map(|value| value.parse())
-            .collect::<Result<HashSet<Privilege>, InvalidValue>>()?;
-        let role = if let Some(row) = role_row {
-            Some(UserRole {
-                id: ID::from(row.id),
-                name: row.name.parse()?,
-                level: RoleLevel::from(row.level),
-                privileges: row
-                    .privileges
-                    .into_iter()
-                    .map(|value| value.parse())
-                    .collect::<Result<HashSet<Privilege>, InvalidValue>>()?,
+            .map(|grant| {
+                let validity = Validity::new(grant.valid_from, grant.valid_until)?;
+                Ok((grant.privilege.parse()?, validity))
             })
-        } else {
-            None
-        };
+            .collect::<Result<HashMap<Privilege, Validity>, InvalidValue>>()?;
+        let unreadable_roles = unreadable
+            .into_iter()
+            .map(|row| Ok((ID::from(row.id), Validity::new(row.valid_from, row.valid_until)?)))
+            .collect::<Result<_, InvalidValue>>()?;
         Ok(DetailedUserView {
             id: ID::from(user_row.id),
             version: Version::try_from(user_row.version)?,
             first_version: Version::try_from(user_row.first_version)?,
             username: user_row.username.parse()?,
             email: user_row.email.map(|value| value.parse()).transpose()?,
+            site: user_row.site.map(|value| value.parse()).transpose()?,
             password: user_row.password.parse()?,
             privileges,
-            role,
+            roles,
+            unreadable_roles,
         })
     }
 }
 mod write {
-    use gnify::source::{PgSource, RecordVersion, Write};
-    use sqlx::types::Uuid;
+    use gnify::{
+        error::InvalidValue,
+        source::{add_outbox_entries, PgSource, RecordVersion, Write},
+    };
+    use sqlx::types::{chrono::NaiveDateTime, Uuid};
 
-    use crate::user::{bmc::WriteUser, User};
+    use crate::{
+        role::RoleLevel,
+        user::{bmc::WriteUser, User},
+        Privilege,
+    };
 
     impl Write<PgSource> for WriteUser {
         async fn write(
             self,
             connection: <PgSource as gnify::source::Source>::Connection<'_>,
-        ) -> Result<(), gnify::error::PersistenceError> {
-            let record = self.record;
+        ) -> Result<(), gnify::Error> {
+            let WriteUser { record, actor } = self;
             let id = Uuid::from(*record.id());
             let version = RecordVersion::from(record.version());
             let User {
                 username,
                 password,
                 email,
-                role_id,
+                roles,
                 privileges,
+                site,
             } = record.state();
+            if let Some(privilege) = privileges.keys().find(|privilege| !privilege.is_registered()) {
+                return Err(InvalidValue::new(format!("Privilege {privilege}")).into());
+            }
+            let current = sqlx::query!(
+                r#"
+                select
+                    (
+                        select max(r.level) from core.user_role ur join core.role r on r.id = ur.role_id
+                        where ur.user_id = u.id
+                    ) as "level?"
+                    , array(select role_id from core.user_role where user_id = u.id) as "roles!"
+                    , array(select privilege from core.user_privilege where user_id = u.id) as "privileges!"
+                from core.user u
+                where u.id = $1;
+                "#,
+                id
+            )
+            .fetch_optional(&mut *connection)
+            .await?;
+            if let Some(current) = &current {
+                actor.ensure_outranks(current.level.map(RoleLevel::new).unwrap_or_default())?;
+            }
+            let (current_roles, granted) = current
+                .map(|current| (current.roles, current.privileges))
+                .unwrap_or_default();
+            actor.ensure_holds(privileges.keys().filter(|privilege| !granted.contains(privilege)))?;
+            let (roles, (roles_from, roles_until)): (Vec<Uuid>, (Vec<_>, Vec<_>)) = roles
+                .iter()
+                .map(|(role, validity)| (Uuid::from(*role), (validity.valid_from(), validity.valid_until())))
+                .unzip();
+            let role_rows = sqlx::query!(
+                r#"
+                select
+                    r.id
+                    , r.level
+                    , rl.rank as "rank?"
+                    , array (
+                        with recursive ancestor(id) as (
+                            select r.id
+                            union
+                            select rp.parent_id from core.role_parent rp join ancestor a on rp.role_id = a.id
+                        )
+                        select distinct privilege from core.role_privilege
+                        where role_id in (select id from ancestor)
+                    ) as "privileges!"
+                from core.role r
+                    left join core.role_level rl on rl.rank = r.level
+                where r.id = any($1::uuid[]);
+                "#,
+                &roles[..]
+            )
+            .fetch_all(&mut *connection)
+            .await?;
+            if role_rows.len() != roles.len() {
+                return Err(InvalidValue::new("Role ID").into());
+            }
+            if role_rows.is_empty() {
+                actor.ensure_outranks(RoleLevel::GUEST)?;
+            }
+            for role in role_rows {
+                actor.ensure_outranks(RoleLevel::new(role.level))?;
+                if !current_roles.contains(&role.id) {
+                    // Assignments the user already has are kept even when their
+                    // role has fallen off the ladder; new ones must be on it.
+                    if role.rank.is_none() {
+                        return Err(InvalidValue::new("RoleLevel").into());
+                    }
+                    let role_privileges = role
+                        .privileges
+                        .iter()
+                        .map(|privilege| privilege.parse())
+                        .collect::<Result<Vec<Privilege>, InvalidValue>>()?;
+                    actor.ensure_holds(&role_privileges)?;
+                }
+            }
             let username = username.to_string();
             let password = password.to_string();
             let email = email.as_ref().map(ToString::to_string);
-            let role_id = role_id.map(Uuid::from);
+            let site = site.as_ref().map(ToString::to_string);
             sqlx::query!(
                 r#"
                 merge into core.user as u
-                using (values ($1::uuid, $2::version, $3, $4, $5, $6::uuid)) as src(id, version, username, "password", email, role_id)
+                using (values ($1::uuid, $2::version, $3, $4, $5, $6::text)) as src(id, version, username, "password", email, site)
                 on u.id = src.id
                 when not matched then 
-                    insert (id, version, first_version, username, password, email, role_id) 
-                    values (src.id, src.version, src.version, src.username, src.password, src.email, src.role_id)
+                    insert (id, version, first_version, username, password, email, site) 
+                    values (src.id, src.version, src.version, src.username, src.password, src.email, src.site)
                 when matched then
                     update set 
                         version = src.version,
                         username = src.username,
                         password = src.password,
                         email = src.email,
-                        role_id = src.role_id;
+                        site = src.site;
                 "#,
                 id,
                 version as RecordVersion,
                 username,
                 password,
                 email,
-                role_id
+                site
+            ).execute(&mut *connection).await?;
+            sqlx::query!(
+                r#"
+                with cte as (
+                    delete from core.user_role where user_id = $1 and role_id != all($2::uuid[])
+                )
+                insert into core.user_role (user_id, role_id, valid_from, valid_until)
+                select $1, * from unnest($2::uuid[], $3::timestamp[], $4::timestamp[])
+                    on conflict (user_id, role_id) do update set
+                        valid_from = excluded.valid_from,
+                        valid_until = excluded.valid_until;
+                "#,
+                id,
+                &roles[..],
+                &roles_from[..] as &[Option<NaiveDateTime>],
+                &roles_until[..] as &[Option<NaiveDateTime>]
             ).execute(&mut *connection).await?;
-            let (ids, privileges): (Vec<Uuid>, Vec<String>) = privileges
+            let (privileges, (privileges_from, privileges_until)): (Vec<String>, (Vec<_>, Vec<_>)) = privileges
                 .iter()
-                .map(|privilege| (id, privilege.to_string()))
+                .map(|(privilege, validity)| (privilege.to_string(), (validity.valid_from(), validity.valid_until())))
                 .unzip();
             sqlx::query!(
                 r#"
                 with cte as (
-                    delete from core.user_privilege where user_id = $1::uuid and privilege != all($3::text[])
+                    delete from core.user_privilege where user_id = $1::uuid and privilege != all($2::text[])
                 )
-                insert into core.user_privilege(user_id, privilege) 
-                select * from unnest($2::uuid[], $3::text[]) 
-                    on conflict (user_id, privilege) do nothing
+                insert into core.user_privilege(user_id, privilege, valid_from, valid_until) 
+                select $1, * from unnest($2::text[], $3::timestamp[], $4::timestamp[]) 
+                    on conflict (user_id, privilege) do update set
+                        valid_from = excluded.valid_from,
+                        valid_until = excluded.valid_until
                 "#,
                 id,
-                &ids[..],
-                &privileges[..]
+                &privileges[..],
+                &privileges_from[..] as &[Option<NaiveDateTime>],
+                &privileges_until[..] as &[Option<NaiveDateTime>]
             ).execute(&mut *connection).await?;
+            add_outbox_entries(connection, record.events()).await?;
+            Ok(())
+        }
+    }
+}
+
+mod delete {
+    use gnify::source::{PgSource, Write};
+    use sqlx::types::Uuid;
+
+    use crate::{role::RoleLevel, user::bmc::DeleteUser};
+
+    impl Write<PgSource> for DeleteUser {
+        async fn write(
+            self,
+            connection: <PgSource as gnify::source::Source>::Connection<'_>,
+        ) -> Result<(), gnify::Error> {
+            let id = Uuid::from(self.id);
+            let current = sqlx::query!(
+                r#"
+                select (
+                    select max(r.level) from core.user_role ur join core.role r on r.id = ur.role_id
+                    where ur.user_id = u.id
+                ) as "level?"
+                from core.user u
+                where u.id = $1;
+                "#,
+                id
+            )
+            .fetch_optional(&mut *connection)
+            .await?;
+            let Some(current) = current else {
+                return Ok(());
+            };
+            self.actor
+                .ensure_outranks(current.level.map(RoleLevel::new).unwrap_or_default())?;
+            sqlx::query!(
+                r#"
+                delete from core.user_privilege where user_id = $1;
+                "#,
+                id
+            )
+            .execute(&mut *connection)
+            .await?;
+            sqlx::query!(
+                r#"
+                delete from core.user where id = $1;
+                "#,
+                id
+            )
+            .execute(connection)
+            .await?;
             Ok(())
         }
     }
 }
+
+mod expire {
+    use std::{
+        collections::{BTreeMap, HashMap, HashSet},
+        sync::Arc,
+    };
+
+    use gnify::{
+        event::DomainEvent,
+        source::{add_maintenance_run, add_outbox_entries, PgSource, RecordVersion, Write},
+        vo::{Version, ID},
+    };
+    use sqlx::types::Uuid;
+
+    use crate::{
+        actor::Actor,
+        role::Role,
+        user::{bmc::ExpireGrants, UserPrivilegesChanged, UserRoleChanged},
+        Privilege,
+    };
+
+    impl Write<PgSource> for ExpireGrants {
+        async fn write(
+            self,
+            connection: <PgSource as gnify::source::Source>::Connection<'_>,
+        ) -> Result<(), gnify::Error> {
+            let privileges = sqlx::query!(
+                r#"
+                delete from core.user_privilege where valid_until <= CURRENT_TIMESTAMP
+                returning user_id, privilege;
+                "#
+            )
+            .fetch_all(&mut *connection)
+            .await?;
+            let roles = sqlx::query!(
+                r#"
+                delete from core.user_role where valid_until <= CURRENT_TIMESTAMP
+                returning user_id, role_id;
+                "#
+            )
+            .fetch_all(&mut *connection)
+            .await?;
+            let mut expired: BTreeMap<Uuid, (HashSet<Privilege>, HashSet<ID<Role>>)> = BTreeMap::new();
+            for row in privileges {
+                expired.entry(row.user_id).or_default().0.insert(row.privilege.parse()?);
+            }
+            for row in roles {
+                expired.entry(row.user_id).or_default().1.insert(row.role_id.into());
+            }
+            let version = Version::now(Actor::Maintenance.id());
+            let users: Vec<Uuid> = expired.keys().copied().collect();
+            sqlx::query!(
+                r#"
+                update core.user set version = $1::version where id = any($2::uuid[]);
+                "#,
+                RecordVersion::from(version) as RecordVersion,
+                &users[..]
+            )
+            .execute(&mut *connection)
+            .await?;
+            let mut events: Vec<Arc<dyn DomainEvent>> = Vec::new();
+            for (id, (revoked, removed)) in expired {
+                let id = ID::from(id);
+                if !revoked.is_empty() {
+                    events.push(Arc::new(UserPrivilegesChanged {
+                        id,
+                        granted: HashMap::new(),
+                        revoked,
+                        version,
+                    }));
+                }
+                if !removed.is_empty() {
+                    events.push(Arc::new(UserRoleChanged {
+                        id,
+                        assigned: HashMap::new(),
+                        removed,
+                        version,
+                    }));
+                }
+            }
+            add_outbox_entries(connection, &events).await?;
+            add_maintenance_run(connection, ExpireGrants::TASK, users.len() as u64).await?;
+            Ok(())
+        }
+    }
+}
+
+mod exists {
+    use gnify::source::{PgSource, Read};
+
+    use crate::user::bmc::ExistsUserAtLevel;
+
+    impl Read<PgSource> for ExistsUserAtLevel {
+        async fn read(
+            self,
+            connection: <PgSource as gnify::source::Source>::Connection<'_>,
+        ) -> Result<Self::Output, gnify::error::PersistenceError> {
+            let exists = sqlx::query_scalar!(
+                r#"
+                select exists(
+                    select 1 from core.user_role ur
+                        join core.role r on r.id = ur.role_id
+                    where r.level >= $1
+                        and coalesce(ur.valid_from <= CURRENT_TIMESTAMP, true)
+                        and coalesce(ur.valid_until > CURRENT_TIMESTAMP, true)
+                ) as "exists!";
+                "#,
+                self.level.rank()
+            )
+            .fetch_one(connection)
+            .await?;
+            Ok(exists)
+        }
+    }
+}
+
+mod persist {
+    use gnify::{
+        error::PersistenceError,
+        repository::{Load, Persist},
+        source::{PgSource, Read, RecordVersion},
+        vo::{Version, ID},
+    };
+    use sqlx::types::Uuid;
+
+    use crate::{
+        actor::Actor,
+        user::{
+            bmc::{DeleteUser, GetUser, LockUser, WriteUser},
+            User,
+        },
+    };
+
+    impl Read<PgSource> for LockUser {
+        async fn read(
+            self,
+            connection: <PgSource as gnify::source::Source>::Connection<'_>,
+        ) -> Result<Self::Output, PersistenceError> {
+            let version = sqlx::query_scalar!(
+                r#"
+                select version as "version: RecordVersion" from core.user where id = $1 for update;
+                "#,
+                Uuid::from(self.id)
+            )
+            .fetch_optional(connection)
+            .await?;
+            version
+                .map(Version::try_from)
+                .transpose()
+                .map_err(|iv| PersistenceError::new(iv.to_string()))
+        }
+    }
+
+    impl Persist<PgSource> for User {
+        type Author = Actor;
+        type Get = Load<GetUser>;
+        type Lock = LockUser;
+        type Save = WriteUser;
+        type Delete = DeleteUser;
+
+        fn get(id: ID<Self>) -> Self::Get {
+            Load(GetUser {
+                id: Some(id.value()),
+                ..Default::default()
+            })
+        }
+
+        fn lock(id: ID<Self>) -> Self::Lock {
+            LockUser { id }
+        }
+
+        fn save(record: gnify::Record<Self>, author: Actor) -> Self::Save {
+            WriteUser { record, actor: author }
+        }
+
+        fn delete(id: ID<Self>, author: Actor) -> Self::Delete {
+            DeleteUser { id, actor: author }
+        }
+    }
+}
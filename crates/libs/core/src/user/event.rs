@@ -0,0 +1,69 @@
+use std::collections::{HashMap, HashSet};
+
+use gnify::{
+    domain_event,
+    vo::{Version, ID},
+};
+use serde::Serialize;
+
+use crate::{role::Role, Privilege, Site};
+
+use super::{Email, User, Username, Validity};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UserCreated {
+    pub id: ID<User>,
+    pub username: Username,
+    pub email: Option<Email>,
+    pub roles: HashMap<ID<Role>, Validity>,
+    pub version: Version,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UserEmailChanged {
+    pub id: ID<User>,
+    pub email: Option<Email>,
+    pub version: Version,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UserSiteChanged {
+    pub id: ID<User>,
+    pub site: Option<Site>,
+    pub version: Version,
+}
+
+/// Carries no hash; subscribers only learn that the password changed.
+#[derive(Debug, Clone, Serialize)]
+pub struct UserPasswordChanged {
+    pub id: ID<User>,
+    pub version: Version,
+}
+
+/// Roles that were assigned, or whose validity changed, and roles taken away.
+#[derive(Debug, Clone, Serialize)]
+pub struct UserRoleChanged {
+    pub id: ID<User>,
+    pub assigned: HashMap<ID<Role>, Validity>,
+    pub removed: HashSet<ID<Role>>,
+    pub version: Version,
+}
+
+/// Privileges that were granted, or whose validity changed, and privileges
+/// revoked.
+#[derive(Debug, Clone, Serialize)]
+pub struct UserPrivilegesChanged {
+    pub id: ID<User>,
+    pub granted: HashMap<Privilege, Validity>,
+    pub revoked: HashSet<Privilege>,
+    pub version: Version,
+}
+
+domain_event!(
+    User: UserCreated,
+    UserEmailChanged,
+    UserSiteChanged,
+    UserPasswordChanged,
+    UserRoleChanged,
+    UserPrivilegesChanged,
+);
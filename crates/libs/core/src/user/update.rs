@@ -0,0 +1,182 @@
+use std::{collections::HashMap, sync::Arc};
+
+use gnify::{error::InvalidValue, event::DomainEvent, model::RecordUpdate, vo::{Version, ID}};
+
+use crate::{role::Role, Privilege, Site};
+
+use super::{
+    Email, Password, User, UserEmailChanged, UserPasswordChanged, UserPrivilegesChanged, UserRoleChanged,
+    UserSiteChanged, Validity,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UserUpdate {
+    password: Password,
+    pub email: Option<Email>,
+    pub roles: HashMap<ID<Role>, Validity>,
+    pub privileges: HashMap<Privilege, Validity>,
+    pub site: Option<Site>,
+}
+
+impl UserUpdate {
+    pub fn set_password(&mut self, password: &str) -> Result<(), InvalidValue> {
+        self.password = Password::generate(password)?;
+        Ok(())
+    }
+}
+
+impl RecordUpdate for UserUpdate {
+    type Model = User;
+
+    fn new(model: &User, _version: Version) -> Self {
+        Self {
+            password: model.password.clone(),
+            email: model.email.clone(),
+            roles: model.roles.clone(),
+            privileges: model.privileges.clone(),
+            site: model.site.clone(),
+        }
+    }
+
+    fn apply(self, state: &mut User) {
+        state.password = self.password;
+        state.email = self.email;
+        state.roles = self.roles;
+        state.privileges = self.privileges;
+        state.site = self.site;
+    }
+
+    fn events(
+        &self,
+        previous: &Self,
+        id: &ID<User>,
+        version: Version,
+    ) -> Result<Vec<Arc<dyn DomainEvent>>, InvalidValue> {
+        let mut events: Vec<Arc<dyn DomainEvent>> = Vec::new();
+        let id = *id;
+        if self.email != previous.email {
+            events.push(Arc::new(UserEmailChanged { id, email: self.email.clone(), version }));
+        }
+        if self.site != previous.site {
+            events.push(Arc::new(UserSiteChanged { id, site: self.site.clone(), version }));
+        }
+        if self.password != previous.password {
+            events.push(Arc::new(UserPasswordChanged { id, version }));
+        }
+        if self.roles != previous.roles {
+            events.push(Arc::new(UserRoleChanged {
+                id,
+                assigned: changed(&previous.roles, &self.roles),
+                removed: previous.roles.keys().filter(|role| !self.roles.contains_key(role)).copied().collect(),
+                version,
+            }));
+        }
+        if self.privileges != previous.privileges {
+            events.push(Arc::new(UserPrivilegesChanged {
+                id,
+                granted: changed(&previous.privileges, &self.privileges),
+                revoked: previous
+                    .privileges
+                    .keys()
+                    .filter(|privilege| !self.privileges.contains_key(privilege))
+                    .cloned()
+                    .collect(),
+                version,
+            }));
+        }
+        Ok(events)
+    }
+}
+
+/// Entries of `current` that are new or differ from `previous`.
+fn changed<K: Clone + Eq + std::hash::Hash>(previous: &HashMap<K, Validity>, current: &HashMap<K, Validity>) -> HashMap<K, Validity> {
+    current
+        .iter()
+        .filter(|(key, validity)| previous.get(key) != Some(validity))
+        .map(|(key, validity)| (key.clone(), *validity))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use gnify::model::Record;
+    use ulid::Ulid;
+
+    use super::*;
+    use crate::user::UserCreated;
+
+    fn user() -> Record<User> {
+        let state = User {
+            username: "cashier".parse().unwrap(),
+            password: "$argon2id$v=19$m=8,t=1,p=1$c2FsdHNhbHQ$0ZVSpWCUGPs0ODv1CzHeTA".parse().unwrap(),
+            email: None,
+            roles: HashMap::new(),
+            privileges: HashMap::new(),
+            site: None,
+        };
+        Record::loaded(ID::new(Ulid::new()), state, Version::now(Ulid::nil()))
+    }
+
+    fn names(record: &Record<User>) -> Vec<&'static str> {
+        record.events().iter().map(|event| event.name()).collect()
+    }
+
+    #[test]
+    fn new_users_announce_their_creation() {
+        let role = Ulid::new();
+        let record = User::new(Ulid::new(), "cashier", "secret", None, HashSet::from([role]), Ulid::nil()).unwrap();
+        let created = record.events()[0].downcast_ref::<UserCreated>().unwrap();
+        assert_eq!(created.id, record.id());
+        assert!(created.roles.contains_key(&ID::new(role)));
+        assert_eq!(record.events().len(), 1);
+    }
+
+    #[test]
+    fn each_changed_field_raises_its_event() {
+        let mut record = user();
+        let changed = record
+            .update(Ulid::new(), |update: &mut UserUpdate| {
+                update.email = Some("cashier@example.com".parse()?);
+                update.site = Some("north".parse()?);
+                Ok(())
+            })
+            .unwrap();
+        assert!(changed);
+        assert_eq!(names(&record), ["UserEmailChanged", "UserSiteChanged"]);
+    }
+
+    #[test]
+    fn role_events_list_assigned_and_removed_roles() {
+        let (kept, dropped, added) = (ID::new(Ulid::new()), ID::new(Ulid::new()), ID::new(Ulid::new()));
+        let mut record = user();
+        record.update(Ulid::new(), |update: &mut UserUpdate| {
+            update.roles.extend([(kept, Validity::default()), (dropped, Validity::default())]);
+            Ok(())
+        })
+        .unwrap();
+        record.take_events();
+        let until = Validity::new(None, Some(sqlx::types::chrono::Utc::now().naive_utc())).unwrap();
+        record.update(Ulid::new(), |update: &mut UserUpdate| {
+            update.roles.remove(&dropped);
+            update.roles.insert(added, Validity::default());
+            update.roles.insert(kept, until);
+            Ok(())
+        })
+        .unwrap();
+        let event = record.events()[0].downcast_ref::<UserRoleChanged>().unwrap();
+        assert_eq!(event.assigned, HashMap::from([(added, Validity::default()), (kept, until)]));
+        assert_eq!(event.removed, HashSet::from([dropped]));
+    }
+
+    #[test]
+    fn unchanged_users_raise_no_events() {
+        let mut record = user();
+        let version = record.version();
+        let changed = record.update(Ulid::new(), |_: &mut UserUpdate| Ok(())).unwrap();
+        assert!(!changed);
+        assert!(record.events().is_empty());
+        assert_eq!(record.version(), version);
+    }
+}
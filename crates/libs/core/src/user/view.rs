@@ -1,11 +1,11 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
-use gnify::{model::Record, vo::{Version, ID}};
+use gnify::{model::Record, repository::View, vo::{Version, ID}};
 use serde::Serialize;
 
-use crate::{role::{RoleLevel, RoleName, Role}, Privilege};
+use crate::{role::{RoleLevel, RoleName, Role}, Privilege, Site};
 
-use super::{vo::{Email, Password, Username}, User};
+use super::{vo::{Email, Password, Username, Validity}, User};
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub struct DetailedUserView {
@@ -14,17 +14,34 @@ pub struct DetailedUserView {
     #[serde(skip)]
     pub(crate) password: Password,
     pub(crate) email: Option<Email>,
-    pub(crate) role: Option<UserRole>,
-    pub(crate) privileges: HashSet<Privilege>,
+    pub(crate) roles: Vec<UserRole>,
+    /// Assignments of roles that couldn't be read, kept so that saving the
+    /// user doesn't take them away.
+    #[serde(skip)]
+    pub(crate) unreadable_roles: HashMap<ID<Role>, Validity>,
+    pub(crate) privileges: HashMap<Privilege, Validity>,
+    pub(crate) site: Option<Site>,
     pub(crate) version: Version,
     pub(crate) first_version: Version
 }
 
 impl DetailedUserView {
     pub fn as_record(self) -> Record<User> {
-        let DetailedUserView { id, username, password, email, role, privileges, version, first_version: _ } = self;
-        let state = User { username, password, email, role_id: role.map(|role| role.id), privileges };
-        Record::new(id, state, version)
+        let DetailedUserView {
+            id,
+            username,
+            password,
+            email,
+            roles,
+            unreadable_roles,
+            privileges,
+            site,
+            version,
+            first_version: _,
+        } = self;
+        let roles = roles.into_iter().map(|role| (role.id, role.validity)).chain(unreadable_roles).collect();
+        let state = User { username, password, email, roles, privileges, site };
+        Record::loaded(id, state, version)
     }
     
     pub fn id(&self) -> ID<User> {
@@ -43,13 +60,34 @@ impl DetailedUserView {
         self.email.as_ref()
     }
     
-    pub fn role(&self) -> Option<&UserRole> {
-        self.role.as_ref()
+    pub fn site(&self) -> Option<&Site> {
+        self.site.as_ref()
+    }
+
+    pub fn roles(&self) -> &[UserRole] {
+        &self.roles
+    }
+
+    /// The highest level among the user's active roles, [`RoleLevel::GUEST`] without any.
+    pub fn level(&self) -> RoleLevel {
+        self.active_roles().map(|role| role.level).max().unwrap_or_default()
     }
     
-    pub fn privileges(&self) -> &HashSet<Privilege> {
+    pub fn privileges(&self) -> &HashMap<Privilege, Validity> {
         &self.privileges
     }
+
+    /// Privileges granted directly whose validity includes the present.
+    pub fn active_privileges(&self) -> impl Iterator<Item = &Privilege> {
+        self.privileges
+            .iter()
+            .filter(|(_, validity)| validity.is_active())
+            .map(|(privilege, _)| privilege)
+    }
+
+    pub fn active_roles(&self) -> impl Iterator<Item = &UserRole> {
+        self.roles.iter().filter(|role| role.validity.is_active())
+    }
     
     pub fn version(&self) -> Version {
         self.version
@@ -60,10 +98,98 @@ impl DetailedUserView {
     }
 }
 
+impl View for DetailedUserView {
+    type Model = User;
+
+    fn into_record(self) -> Record<User> {
+        self.as_record()
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub struct UserRole {
     pub id: ID<Role>,
     pub name: RoleName, 
     pub level: RoleLevel,
-    pub privileges: HashSet<Privilege>
-}
\ No newline at end of file
+    pub privileges: HashSet<Privilege>,
+    pub inherited_privileges: HashSet<Privilege>,
+    pub validity: Validity,
+}
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use sqlx::types::chrono::Utc;
+    use ulid::Ulid;
+
+    use super::*;
+
+    pub(crate) fn role(name: &str, level: RoleLevel, validity: Validity) -> UserRole {
+        UserRole {
+            id: ID::new(Ulid::new()),
+            name: name.parse().unwrap(),
+            level,
+            privileges: HashSet::new(),
+            inherited_privileges: HashSet::new(),
+            validity,
+        }
+    }
+
+    pub(crate) fn user(roles: Vec<UserRole>) -> DetailedUserView {
+        let version = Version::now(Ulid::nil());
+        DetailedUserView {
+            id: ID::new(Ulid::new()),
+            username: "cashier".parse().unwrap(),
+            password: "$argon2id$v=19$m=8,t=1,p=1$c2FsdHNhbHQ$0ZVSpWCUGPs0ODv1CzHeTA".parse().unwrap(),
+            email: None,
+            roles,
+            unreadable_roles: HashMap::new(),
+            privileges: HashMap::new(),
+            site: None,
+            version,
+            first_version: version,
+        }
+    }
+
+    #[test]
+    fn users_rank_at_their_highest_role() {
+        let user = user(vec![
+            role("Cashier", RoleLevel::OPERATOR, Validity::default()),
+            role("Shift Lead", RoleLevel::MANAGER, Validity::default()),
+        ]);
+        assert_eq!(user.level(), RoleLevel::MANAGER);
+    }
+
+    #[test]
+    fn users_without_roles_are_guests() {
+        assert_eq!(user(Vec::new()).level(), RoleLevel::GUEST);
+    }
+
+    #[test]
+    fn saving_keeps_every_assignment_including_unreadable_ones() {
+        let mut user = user(vec![
+            role("Cashier", RoleLevel::OPERATOR, Validity::default()),
+            role("Shift Lead", RoleLevel::MANAGER, Validity::default()),
+        ]);
+        let unreadable = ID::new(Ulid::new());
+        user.unreadable_roles.insert(unreadable, Validity::default());
+        let record = user.as_record();
+        assert_eq!(record.state().roles.len(), 3);
+        assert!(record.state().roles.contains_key(&unreadable));
+    }
+
+    #[test]
+    fn expired_grants_are_inactive() {
+        let expired = Validity::new(None, Some(Utc::now().naive_utc() - Duration::from_secs(60))).unwrap();
+        let mut user = user(vec![
+            role("Cashier", RoleLevel::OPERATOR, Validity::default()),
+            role("Shift Lead", RoleLevel::MANAGER, expired),
+        ]);
+        user.privileges.insert("REGISTER USER".parse().unwrap(), expired);
+        user.privileges.insert("MANAGE DEVICES".parse().unwrap(), Validity::default());
+        assert_eq!(user.level(), RoleLevel::OPERATOR);
+        assert_eq!(user.active_roles().count(), 1);
+        let active: Vec<_> = user.active_privileges().map(|privilege| privilege.value()).collect();
+        assert_eq!(active, ["MANAGE DEVICES"]);
+    }
+}
@@ -1,7 +1,9 @@
 use std::{fmt, str::FromStr};
 
 use gnify::{error::InvalidValue, text};
-use serde::Serialize;
+use once_cell::sync::OnceCell;
+use serde::{Deserialize, Serialize};
+use sqlx::types::chrono::{NaiveDateTime, Utc};
 
 text! {
     Username: r"^[a-z0-9][a-z0-9_]{3,63}$"
@@ -11,6 +13,47 @@ text! {
     Email: r"^[a-z0-9!#$%&'*+/=?^_`{|}~-]+(?:\.[a-z0-9!#$%&'*+/=?^_`{|}~-]+)*@(?:[a-z0-9](?:[a-z0-9-]*[a-z0-9])?\.)+[a-z0-9](?:[a-z0-9-]*[a-z0-9])?$"
 }
 
+static HASHING: OnceCell<argon2::Params> = OnceCell::new();
+
+/// Argon2id cost parameters used for new password hashes. Existing hashes
+/// carry their own parameters and keep verifying after a change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct HashingParams {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl Default for HashingParams {
+    fn default() -> Self {
+        Self {
+            memory_kib: argon2::Params::DEFAULT_M_COST,
+            iterations: argon2::Params::DEFAULT_T_COST,
+            parallelism: argon2::Params::DEFAULT_P_COST,
+        }
+    }
+}
+
+impl HashingParams {
+    pub fn validate(&self) -> Result<(), InvalidValue> {
+        self.params().map(|_| ())
+    }
+
+    /// Makes these parameters the ones used by [`Password::generate`]. Only
+    /// the first call has an effect.
+    pub fn install(self) -> Result<(), InvalidValue> {
+        let params = self.params()?;
+        let _ = HASHING.set(params);
+        Ok(())
+    }
+
+    fn params(&self) -> Result<argon2::Params, InvalidValue> {
+        argon2::Params::new(self.memory_kib, self.iterations, self.parallelism, None)
+            .map_err(|error| InvalidValue::new(format!("HashingParams ({error})")))
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub struct Password(String);
 
@@ -18,10 +61,11 @@ impl Password {
     pub fn generate(password: &str) -> Result<Password, InvalidValue> {
         use argon2::{
             password_hash::{rand_core::OsRng, SaltString},
-            Argon2, PasswordHasher,
+            Algorithm, Argon2, PasswordHasher, Version,
         };
         let salt = SaltString::generate(&mut OsRng);
-        let hash = Argon2::default()
+        let params = HASHING.get().cloned().unwrap_or_default();
+        let hash = Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
             .hash_password(password.as_bytes(), &salt)
             .unwrap();
         Ok(Password(hash.to_string()))
@@ -62,3 +106,79 @@ impl fmt::Display for Password {
         )
     }
 }
+
+/// The period in which a privilege grant or role assignment is in effect.
+/// Either end may be open.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub struct Validity {
+    valid_from: Option<NaiveDateTime>,
+    valid_until: Option<NaiveDateTime>,
+}
+
+impl Validity {
+    pub fn new(
+        valid_from: Option<NaiveDateTime>,
+        valid_until: Option<NaiveDateTime>,
+    ) -> Result<Self, InvalidValue> {
+        match (valid_from, valid_until) {
+            (Some(from), Some(until)) if from >= until => Err(InvalidValue::new("Validity")),
+            _ => Ok(Self {
+                valid_from,
+                valid_until,
+            }),
+        }
+    }
+
+    pub fn valid_from(&self) -> Option<NaiveDateTime> {
+        self.valid_from
+    }
+
+    pub fn valid_until(&self) -> Option<NaiveDateTime> {
+        self.valid_until
+    }
+
+    pub fn contains(&self, instant: NaiveDateTime) -> bool {
+        self.valid_from.is_none_or(|from| from <= instant)
+            && self.valid_until.is_none_or(|until| instant < until)
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.contains(Utc::now().naive_utc())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn at(seconds: i64) -> NaiveDateTime {
+        sqlx::types::chrono::DateTime::from_timestamp(seconds, 0).unwrap().naive_utc()
+    }
+
+    #[test]
+    fn validities_must_end_after_they_start() {
+        assert!(Validity::new(Some(at(10)), Some(at(10))).is_err());
+        assert!(Validity::new(Some(at(20)), Some(at(10))).is_err());
+        assert!(Validity::new(Some(at(10)), Some(at(20))).is_ok());
+    }
+
+    #[test]
+    fn validities_include_their_start_but_not_their_end() {
+        let validity = Validity::new(Some(at(10)), Some(at(20))).unwrap();
+        assert!(!validity.contains(at(9)));
+        assert!(validity.contains(at(10)));
+        assert!(validity.contains(at(19)));
+        assert!(!validity.contains(at(20)));
+    }
+
+    #[test]
+    fn open_ended_validities_last_forever() {
+        assert!(Validity::default().is_active());
+        let started = Validity::new(Some(at(10)), None).unwrap();
+        assert!(started.is_active());
+        let pending = Validity::new(Some(Utc::now().naive_utc() + Duration::from_secs(3600)), None).unwrap();
+        assert!(!pending.is_active());
+    }
+}
@@ -0,0 +1,51 @@
+use serde::Serialize;
+use sqlx::types::chrono::NaiveDateTime;
+use ulid::Ulid;
+
+mod bmc;
+
+pub use bmc::*;
+
+/// A partner's subscription to domain events delivered over HTTP.
+#[derive(Debug, Clone, Serialize)]
+pub struct Webhook {
+    pub id: Ulid,
+    pub url: String,
+    /// Event names or aggregate types; empty means every event.
+    pub events: Vec<String>,
+    /// Key of the HMAC signature; only shown when the webhook is created.
+    #[serde(skip_serializing)]
+    pub secret: String,
+    pub active: bool,
+    pub created_at: NaiveDateTime,
+}
+
+impl Webhook {
+    pub fn matches(&self, event: &str, aggregate_type: &str) -> bool {
+        self.events.is_empty()
+            || self
+                .events
+                .iter()
+                .any(|filter| filter == event || filter == aggregate_type)
+    }
+}
+
+/// One attempt to deliver an event to a webhook.
+#[derive(Debug, Clone, Serialize)]
+pub struct WebhookDelivery {
+    pub id: i64,
+    pub webhook_id: Ulid,
+    /// The outbox entry delivered; absent for pings.
+    pub outbox_id: Option<i64>,
+    pub event: String,
+    pub status_code: Option<i32>,
+    pub error: Option<String>,
+    pub duration_ms: i32,
+    pub attempted_at: NaiveDateTime,
+}
+
+impl WebhookDelivery {
+    pub fn succeeded(&self) -> bool {
+        self.error.is_none()
+    }
+}
@@ -0,0 +1,47 @@
+use gnify::source::BMC;
+use ulid::Ulid;
+
+use super::{Webhook, WebhookDelivery};
+
+mod postgres;
+
+pub struct CreateWebhook {
+    pub webhook: Webhook,
+}
+
+pub struct GetWebhook {
+    pub id: Ulid,
+}
+
+impl BMC for GetWebhook {
+    type Output = Option<Webhook>;
+}
+
+pub struct ListWebhooks;
+
+impl BMC for ListWebhooks {
+    type Output = Vec<Webhook>;
+}
+
+pub struct DeleteWebhook {
+    pub id: Ulid,
+}
+
+pub struct LogWebhookDelivery {
+    pub webhook_id: Ulid,
+    pub outbox_id: Option<i64>,
+    pub event: String,
+    pub status_code: Option<i32>,
+    pub error: Option<String>,
+    pub duration_ms: i32,
+}
+
+/// The most recent deliveries to a webhook, newest first.
+pub struct ListWebhookDeliveries {
+    pub webhook_id: Ulid,
+    pub limit: i64,
+}
+
+impl BMC for ListWebhookDeliveries {
+    type Output = Vec<WebhookDelivery>;
+}
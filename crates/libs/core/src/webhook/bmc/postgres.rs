@@ -0,0 +1,190 @@
+mod get {
+    use gnify::source::{PgSource, Read};
+    use sqlx::types::{chrono::NaiveDateTime, Uuid};
+
+    use crate::webhook::{GetWebhook, ListWebhookDeliveries, ListWebhooks, Webhook, WebhookDelivery};
+
+    impl Read<PgSource> for GetWebhook {
+        async fn read(
+            self,
+            connection: <PgSource as gnify::source::Source>::Connection<'_>,
+        ) -> Result<Self::Output, gnify::error::PersistenceError> {
+            let row: Option<WebhookRow> = sqlx::query_as!(
+                WebhookRow,
+                r#"
+                select id, url, events, secret, active, created_at
+                from core.webhook
+                where id = $1;
+                "#,
+                Uuid::from(self.id)
+            )
+            .fetch_optional(connection)
+            .await?;
+            Ok(row.map(Webhook::from))
+        }
+    }
+
+    impl Read<PgSource> for ListWebhooks {
+        async fn read(
+            self,
+            connection: <PgSource as gnify::source::Source>::Connection<'_>,
+        ) -> Result<Self::Output, gnify::error::PersistenceError> {
+            let rows: Vec<WebhookRow> = sqlx::query_as!(
+                WebhookRow,
+                r#"
+                select id, url, events, secret, active, created_at
+                from core.webhook
+                order by created_at;
+                "#
+            )
+            .fetch_all(connection)
+            .await?;
+            Ok(rows.into_iter().map(Webhook::from).collect())
+        }
+    }
+
+    impl Read<PgSource> for ListWebhookDeliveries {
+        async fn read(
+            self,
+            connection: <PgSource as gnify::source::Source>::Connection<'_>,
+        ) -> Result<Self::Output, gnify::error::PersistenceError> {
+            let rows: Vec<DeliveryRow> = sqlx::query_as!(
+                DeliveryRow,
+                r#"
+                select id, webhook_id, outbox_id, event, status_code, error, duration_ms, attempted_at
+                from core.webhook_delivery
+                where webhook_id = $1
+                order by id desc
+                limit $2;
+                "#,
+                Uuid::from(self.webhook_id),
+                self.limit
+            )
+            .fetch_all(connection)
+            .await?;
+            Ok(rows
+                .into_iter()
+                .map(|row| WebhookDelivery {
+                    id: row.id,
+                    webhook_id: row.webhook_id.into(),
+                    outbox_id: row.outbox_id,
+                    event: row.event,
+                    status_code: row.status_code,
+                    error: row.error,
+                    duration_ms: row.duration_ms,
+                    attempted_at: row.attempted_at,
+                })
+                .collect())
+        }
+    }
+
+    struct WebhookRow {
+        id: Uuid,
+        url: String,
+        events: Vec<String>,
+        secret: String,
+        active: bool,
+        created_at: NaiveDateTime,
+    }
+
+    impl From<WebhookRow> for Webhook {
+        fn from(row: WebhookRow) -> Self {
+            Webhook {
+                id: row.id.into(),
+                url: row.url,
+                events: row.events,
+                secret: row.secret,
+                active: row.active,
+                created_at: row.created_at,
+            }
+        }
+    }
+
+    struct DeliveryRow {
+        id: i64,
+        webhook_id: Uuid,
+        outbox_id: Option<i64>,
+        event: String,
+        status_code: Option<i32>,
+        error: Option<String>,
+        duration_ms: i32,
+        attempted_at: NaiveDateTime,
+    }
+}
+mod write {
+    use gnify::source::{PgSource, Write};
+    use sqlx::types::Uuid;
+
+    use crate::webhook::{CreateWebhook, DeleteWebhook, LogWebhookDelivery, Webhook};
+
+    impl Write<PgSource> for CreateWebhook {
+        async fn write(
+            self,
+            connection: <PgSource as gnify::source::Source>::Connection<'_>,
+        ) -> Result<(), gnify::Error> {
+            let Webhook {
+                id,
+                url,
+                events,
+                secret,
+                active,
+                created_at,
+            } = self.webhook;
+            sqlx::query!(
+                r#"
+                insert into core.webhook (id, url, events, secret, active, created_at)
+                values ($1, $2, $3, $4, $5, $6);
+                "#,
+                Uuid::from(id),
+                url,
+                &events[..],
+                secret,
+                active,
+                created_at
+            )
+            .execute(connection)
+            .await?;
+            Ok(())
+        }
+    }
+
+    impl Write<PgSource> for DeleteWebhook {
+        async fn write(
+            self,
+            connection: <PgSource as gnify::source::Source>::Connection<'_>,
+        ) -> Result<(), gnify::Error> {
+            sqlx::query!(
+                r#"
+                delete from core.webhook where id = $1;
+                "#,
+                Uuid::from(self.id)
+            )
+            .execute(connection)
+            .await?;
+            Ok(())
+        }
+    }
+
+    impl Write<PgSource> for LogWebhookDelivery {
+        async fn write(
+            self,
+            connection: <PgSource as gnify::source::Source>::Connection<'_>,
+        ) -> Result<(), gnify::Error> {
+            sqlx::query!(
+                r#"
+                insert into core.webhook_delivery (webhook_id, outbox_id, event, status_code, error, duration_ms)
+                values ($1, $2, $3, $4, $5, $6);
+                "#,
+                Uuid::from(self.webhook_id),
+                self.outbox_id,
+                self.event,
+                self.status_code,
+                self.error,
+                self.duration_ms
+            )
+            .execute(connection)
+            .await?;
+            Ok(())
+        }
+    }
+}
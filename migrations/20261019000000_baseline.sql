@@ -0,0 +1,63 @@
+-- The schema the later migrations build on. Deployments that predate it
+-- already have every object, so each statement is a no-op there.
+create schema if not exists core;
+
+do $$
+begin
+    if not exists (select 1 from pg_type where typname = 'version') then
+        create type version as (author uuid, timestamp timestamp);
+    end if;
+end
+$$;
+
+create table if not exists corrupt_record (
+    id uuid primary key,
+    model text not null,
+    description text not null
+);
+
+create table if not exists core.role (
+    id uuid primary key,
+    version version not null,
+    first_version version not null,
+    name text not null unique,
+    level smallint not null
+);
+
+create table if not exists core.role_privilege (
+    role_id uuid not null references core.role (id) on delete cascade,
+    privilege text not null,
+    primary key (role_id, privilege)
+);
+
+create table if not exists core.user (
+    id uuid primary key,
+    version version not null,
+    first_version version not null,
+    username text not null unique,
+    email text unique,
+    password text not null,
+    role_id uuid references core.role (id)
+);
+
+create table if not exists core.user_privilege (
+    user_id uuid not null references core.user (id) on delete cascade,
+    privilege text not null,
+    primary key (user_id, privilege)
+);
+
+create table if not exists core.session (
+    id bigint generated always as identity primary key,
+    token text not null,
+    user_id uuid not null references core.user (id) on delete cascade,
+    expiration timestamp not null
+);
+
+create table if not exists core.device (
+    token text primary key,
+    version version not null,
+    first_version version not null,
+    name text not null,
+    status smallint not null,
+    session_id bigint references core.session (id) on delete set null
+);
@@ -0,0 +1,7 @@
+create table if not exists core.login_session (
+    id text primary key,
+    data bytea not null,
+    expiration timestamp not null
+);
+
+create index if not exists login_session_expiration_idx on core.login_session (expiration);
@@ -0,0 +1,20 @@
+create table if not exists core.privilege (
+    name text primary key,
+    description text not null
+);
+
+create table if not exists core.privilege_group (
+    group_name text not null references core.privilege (name) on delete cascade,
+    member text not null references core.privilege (name) on delete cascade,
+    primary key (group_name, member)
+);
+
+-- Grants are only checked from now on; existing rows are left for the
+-- startup sync to reconcile.
+alter table core.role_privilege
+    add constraint role_privilege_privilege_fkey
+    foreign key (privilege) references core.privilege (name) on delete cascade not valid;
+
+alter table core.user_privilege
+    add constraint user_privilege_privilege_fkey
+    foreign key (privilege) references core.privilege (name) on delete cascade not valid;
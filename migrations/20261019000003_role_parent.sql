@@ -0,0 +1,6 @@
+create table if not exists core.role_parent (
+    role_id uuid not null references core.role (id) on delete cascade,
+    parent_id uuid not null references core.role (id) on delete cascade,
+    primary key (role_id, parent_id),
+    check (role_id != parent_id)
+);
@@ -0,0 +1,11 @@
+create table if not exists core.user_role (
+    user_id uuid not null references core.user (id) on delete cascade,
+    role_id uuid not null references core.role (id) on delete cascade,
+    primary key (user_id, role_id)
+);
+
+insert into core.user_role (user_id, role_id)
+select id, role_id from core.user where role_id is not null
+    on conflict (user_id, role_id) do nothing;
+
+alter table core.user drop column role_id;
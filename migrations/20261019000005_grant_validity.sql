@@ -0,0 +1,12 @@
+alter table core.user_privilege
+    add column valid_from timestamp,
+    add column valid_until timestamp,
+    add constraint user_privilege_validity_check check (valid_from < valid_until);
+
+alter table core.user_role
+    add column valid_from timestamp,
+    add column valid_until timestamp,
+    add constraint user_role_validity_check check (valid_from < valid_until);
+
+create index if not exists user_privilege_valid_until_idx on core.user_privilege (valid_until);
+create index if not exists user_role_valid_until_idx on core.user_role (valid_until);
@@ -0,0 +1,19 @@
+create table if not exists core.role_level (
+    rank smallint primary key,
+    name text not null unique
+);
+
+insert into core.role_level (rank, name) values
+    (0, 'Guest'),
+    (10, 'Operator'),
+    (20, 'Manager'),
+    (30, 'Administrator'),
+    (40, 'Developer')
+    on conflict (rank) do nothing;
+
+-- Leave room between the built-in levels for custom ones.
+update core.role set level = level * 10 where level between 1 and 4;
+
+alter table core.role
+    add constraint role_level_fkey
+    foreign key (level) references core.role_level (rank) not valid;
@@ -0,0 +1,18 @@
+-- Events written in the same transaction as the records they describe.
+-- status: 0 pending, 1 delivered, 2 dead letter.
+create table if not exists core.outbox (
+    id bigserial primary key,
+    aggregate_type text not null,
+    aggregate_id text not null,
+    event text not null,
+    payload jsonb not null,
+    version version not null,
+    status smallint not null default 0,
+    attempts integer not null default 0,
+    next_attempt_at timestamp not null default CURRENT_TIMESTAMP,
+    last_error text,
+    delivered_at timestamp
+);
+
+create index if not exists outbox_pending_idx on core.outbox (aggregate_type, aggregate_id, id) where status = 0;
+create index if not exists outbox_status_idx on core.outbox (status);
@@ -0,0 +1,24 @@
+-- Partner subscriptions to domain events and the log of their deliveries.
+create table if not exists core.webhook (
+    id uuid primary key,
+    url text not null,
+    events text[] not null default '{}',
+    secret text not null,
+    active boolean not null default true,
+    created_at timestamp not null default CURRENT_TIMESTAMP
+);
+
+-- error is null for successful deliveries; outbox_id is null for pings.
+create table if not exists core.webhook_delivery (
+    id bigserial primary key,
+    webhook_id uuid not null references core.webhook (id) on delete cascade,
+    outbox_id bigint,
+    event text not null,
+    status_code integer,
+    error text,
+    duration_ms integer not null,
+    attempted_at timestamp not null default CURRENT_TIMESTAMP
+);
+
+create index if not exists webhook_delivery_webhook_idx on core.webhook_delivery (webhook_id, id);
+create index if not exists webhook_delivery_outbox_idx on core.webhook_delivery (outbox_id) where error is null;
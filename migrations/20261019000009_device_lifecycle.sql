@@ -0,0 +1,6 @@
+-- Device status is now a lifecycle: 0 pending, 1 approved, 2 rejected,
+-- 3 suspended, 4 revoked. Previously authorized devices (1) stay approved;
+-- unauthorized ones (0) had been revoked and stay so.
+update core.device set status = 4 where status = 0;
+alter table core.device drop constraint if exists device_status_check;
+alter table core.device add constraint device_status_check check (status between 0 and 4);
@@ -0,0 +1,13 @@
+-- Sessions now point at their device so that a device can hold several.
+alter table core.session add column if not exists device_token text references core.device (token) on delete cascade;
+alter table core.session add column if not exists started_at timestamp not null default CURRENT_TIMESTAMP;
+
+update core.session s set device_token = d.token from core.device d where d.session_id = s.id;
+delete from core.session where device_token is null;
+
+alter table core.session alter column device_token set not null;
+alter table core.device drop column if exists session_id;
+
+create unique index if not exists session_token_idx on core.session (token);
+create index if not exists session_device_idx on core.session (device_token);
+create index if not exists session_user_idx on core.session (user_id);
@@ -0,0 +1,10 @@
+-- What each run of a background maintenance task changed.
+create table if not exists core.maintenance_run (
+    id bigserial primary key,
+    task text not null,
+    affected bigint not null,
+    ran_at timestamp not null default CURRENT_TIMESTAMP
+);
+
+create index if not exists maintenance_run_task_idx on core.maintenance_run (task, id);
+create index if not exists session_expiration_idx on core.session (expiration);
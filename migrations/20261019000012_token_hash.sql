@@ -0,0 +1,22 @@
+-- Devices are identified by a non-secret id; only keyed hashes of device and
+-- session tokens are stored. The hash key is not available here, so existing
+-- device tokens are hashed by the application on start, which leaves
+-- token_hash null until then; existing sessions are ended.
+alter table core.device add column if not exists id uuid not null default gen_random_uuid();
+alter table core.device add column if not exists token_hash text;
+
+delete from core.session;
+drop index if exists core.session_device_idx;
+alter table core.session drop column if exists device_token;
+alter table core.session add column device_id uuid not null;
+alter table core.session rename column token to token_hash;
+alter index if exists core.session_token_idx rename to session_token_hash_idx;
+
+alter table core.device drop constraint if exists device_pkey;
+alter table core.device add primary key (id);
+alter table core.device alter column token drop not null;
+alter table core.session add constraint session_device_fkey
+    foreign key (device_id) references core.device (id) on delete cascade;
+
+create unique index if not exists device_token_hash_idx on core.device (token_hash);
+create index if not exists session_device_idx on core.session (device_id);
@@ -0,0 +1,7 @@
+-- A rotated device token keeps working until retired_until so that the
+-- device can switch over to its new one.
+alter table core.device add column if not exists retired_token_hash text;
+alter table core.device add column if not exists retired_until timestamp;
+
+create index if not exists device_retired_token_hash_idx on core.device (retired_token_hash)
+    where retired_token_hash is not null;
@@ -0,0 +1,22 @@
+-- Delivery state of each outbox entry per target, so that one failing
+-- target is retried and dead-lettered without resending to the others.
+-- recipient tells apart the receivers within a target, such as the webhooks
+-- subscribed to an event; it is empty for targets with a single receiver.
+-- status: 0 pending, 1 delivered, 2 dead letter.
+create table if not exists core.outbox_delivery (
+    outbox_id bigint not null references core.outbox (id) on delete cascade,
+    target text not null,
+    recipient text not null default '',
+    status smallint not null default 0,
+    attempts integer not null default 0,
+    next_attempt_at timestamp not null default CURRENT_TIMESTAMP,
+    last_error text,
+    delivered_at timestamp,
+    primary key (outbox_id, target, recipient)
+);
+
+-- The dispatcher that leased an entry, for reading back what it claimed.
+alter table core.outbox add column if not exists claim uuid;
+
+create index if not exists outbox_claim_idx on core.outbox (claim) where claim is not null;
+create index if not exists outbox_delivered_idx on core.outbox (delivered_at) where status = 1;
@@ -0,0 +1,3 @@
+-- Privileges dropped from the catalogue are deprecated rather than deleted,
+-- so that removing one from the code never takes grants away silently.
+alter table core.privilege add column if not exists deprecated boolean not null default false;
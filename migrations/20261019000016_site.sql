@@ -0,0 +1,3 @@
+-- The site or tenant users and devices belong to, for policies scoped to one.
+alter table core.user add column if not exists site text;
+alter table core.device add column if not exists site text;
@@ -1,32 +1,104 @@
 use std::{borrow::Borrow, net::TcpListener};
 
-use axum::{routing::get, Json, Router};
+use axum::{middleware::from_fn_with_state, routing::get, Json, Router};
+use axum_login::{
+    tower_sessions::{Expiry, SessionManagerLayer},
+    AuthManagerLayerBuilder,
+};
+use gnify_core::privilege::{self, PrivilegeDefinition};
 use smol::{Async, Executor};
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+use tower_http::cors::{AllowOrigin, CorsLayer};
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
-use crate::application::{self, AppState};
+use crate::{
+    application::AppState,
+    maintenance,
+    config::{Config, CorsConfig},
+    outbox::Dispatcher,
+};
 
-pub mod auth {
-    
-}
+pub mod auth;
+pub mod changes;
+pub mod devices;
+pub mod setup;
+pub mod users;
+pub mod webhooks;
 
 pub async fn run<'ex>(ex: impl Borrow<Executor<'ex>> + Clone + Send + 'ex) -> Result<(), Box<dyn std::error::Error>> {
+    let config = Config::load()?;
     tracing_subscriber::registry()
-        .with(EnvFilter::new(std::env::var("RUST_LOG").unwrap_or_else(
-            |_| "axum_login=debug,tower_sessions=debug,sqlx=warn,tower_http=debug".into(),
-        )))
+        .with(config.log.env_filter()?)
         .with(tracing_subscriber::fmt::layer())
         .try_init()?;
-    let url = env!("DATABASE_URL");
-    let state = AppState::init(url).await.expect("Couldn't start server");
+    config.hashing.install()?;
+    config.tokens.install()?;
+    let state = AppState::init(&config)
+        .await
+        .expect("Couldn't start server");
+    let session_config = config.session.clone();
+
+    ex.borrow()
+        .spawn(maintenance::run(
+            state.source.clone(),
+            state.events.clone(),
+            config.maintenance.clone(),
+        ))
+        .detach();
+    let dispatcher = Dispatcher::new(state.source.clone(), config.outbox.clone())
+        .with_configured_targets()?
+        .with_target(state.webhooks.clone());
+    ex.borrow().spawn(dispatcher.run()).detach();
+    ex.borrow()
+        .spawn(state.changes.clone().run(state.source.clone()))
+        .detach();
+    let store = auth::PgSessionStore::new(state.source.clone());
+    ex.borrow()
+        .spawn(store.clone().run_cleanup(session_config.cleanup_interval))
+        .detach();
+    let session_layer = SessionManagerLayer::new(store)
+        .with_name(session_config.cookie_name)
+        .with_secure(session_config.secure)
+        .with_same_site(session_config.same_site)
+        .with_expiry(Expiry::OnInactivity(time::Duration::seconds(
+            session_config.max_age.as_secs() as i64,
+        )));
+    let backend = auth::Backend::new(state.source.clone());
+    let auth_layer = AuthManagerLayerBuilder::new(backend, session_layer).build();
 
-    let app = Router::new().route("/", get(handler)).with_state(state);
-    let listener = Async::<TcpListener>::bind(([127, 0, 0, 1], 3000)).unwrap();
+    let mut app = Router::new()
+        .route("/", get(handler))
+        .merge(auth::router())
+        .merge(setup::router())
+        .merge(users::router())
+        .merge(devices::router())
+        .merge(webhooks::router())
+        .merge(changes::router())
+        .layer(from_fn_with_state(state.clone(), auth::bearer))
+        .layer(auth_layer)
+        .with_state(state);
+    if config.cors.is_enabled() {
+        app = app.layer(cors_layer(&config.cors)?);
+    }
+    let listener = Async::<TcpListener>::bind(config.server.bind)?;
     println!("listening on http://{}", listener.get_ref().local_addr().unwrap());
     smol_axum::serve(ex, listener, app).await?;
     Ok(())
 }
 
-async fn handler() -> Json<&'static phf::Map<&'static str, &'static [&'static str]>> {
-    Json(&application::PRIVILEGES)
-}
\ No newline at end of file
+async fn handler() -> Json<&'static [PrivilegeDefinition]> {
+    Json(privilege::CATALOGUE)
+}
+
+fn cors_layer(config: &CorsConfig) -> Result<CorsLayer, gnify::error::InvalidValue> {
+    let origin = if config.any_origin() {
+        AllowOrigin::any()
+    } else {
+        AllowOrigin::list(config.origins()?)
+    };
+    Ok(CorsLayer::new()
+        .allow_origin(origin)
+        .allow_methods(config.methods()?)
+        .allow_headers(config.headers()?)
+        .allow_credentials(config.allow_credentials)
+        .max_age(config.max_age))
+}
@@ -0,0 +1,134 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use axum_login::{AuthUser, AuthnBackend, UserId};
+use gnify::source::{PgSource, Source};
+use gnify_core::{privilege::EffectivePrivileges, user::GetUser};
+use serde::Deserialize;
+
+use crate::application::{AppState, AuthProfile, PRIVILEGE_GROUPS};
+
+mod bearer;
+mod guard;
+mod store;
+
+pub use bearer::*;
+pub use guard::*;
+pub use store::*;
+
+pub type AuthSession = axum_login::AuthSession<Backend>;
+
+impl AuthUser for AuthProfile {
+    type Id = ulid::Ulid;
+
+    /// Only users are kept in a session; devices authenticate per request.
+    fn id(&self) -> Self::Id {
+        self.principal.value()
+    }
+
+    fn session_auth_hash(&self) -> &[u8] {
+        &self.auth_hash
+    }
+}
+
+#[derive(Clone, Deserialize)]
+pub struct Credentials {
+    pub username: String,
+    pub password: String,
+}
+
+#[derive(Clone)]
+pub struct Backend {
+    source: Arc<PgSource>,
+}
+
+impl Backend {
+    pub fn new(source: Arc<PgSource>) -> Self {
+        Self { source }
+    }
+}
+
+#[async_trait]
+impl AuthnBackend for Backend {
+    type User = AuthProfile;
+    type Credentials = Credentials;
+    type Error = gnify::Error;
+
+    async fn authenticate(
+        &self,
+        credentials: Self::Credentials,
+    ) -> Result<Option<Self::User>, Self::Error> {
+        let user = self
+            .source
+            .read(GetUser::by_username(&credentials.username))
+            .await?;
+        Ok(user
+            .filter(|user| user.password().verify(&credentials.password))
+            .map(|user| AuthProfile::from(&user)))
+    }
+
+    async fn get_user(&self, id: &UserId<Self>) -> Result<Option<Self::User>, Self::Error> {
+        let user = self
+            .source
+            .read(GetUser {
+                id: Some(*id),
+                ..Default::default()
+            })
+            .await?;
+        Ok(user.map(|user| AuthProfile::from(&user)))
+    }
+}
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/login", post(login))
+        .route("/logout", post(logout))
+        .route("/me/privileges", get(privileges))
+}
+
+async fn login(mut session: AuthSession, Json(credentials): Json<Credentials>) -> StatusCode {
+    let user = match session.authenticate(credentials).await {
+        Ok(Some(user)) => user,
+        Ok(None) => return StatusCode::UNAUTHORIZED,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    match session.login(&user).await {
+        Ok(()) => StatusCode::NO_CONTENT,
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+async fn logout(mut session: AuthSession) -> StatusCode {
+    match session.logout().await {
+        Ok(_) => StatusCode::NO_CONTENT,
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+async fn privileges(State(state): State<AppState>, session: AuthSession) -> Response {
+    let Some(profile) = session.user else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+    let Some(id) = profile.principal.user() else {
+        return StatusCode::FORBIDDEN.into_response();
+    };
+    let user = state
+        .source
+        .read(GetUser {
+            id: Some(id.value()),
+            ..Default::default()
+        })
+        .await;
+    match user {
+        Ok(Some(user)) => Json(EffectivePrivileges::resolve(&user, &PRIVILEGE_GROUPS)).into_response(),
+        Ok(None) => StatusCode::UNAUTHORIZED.into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
@@ -0,0 +1,154 @@
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use gnify::{
+    error::{InvalidValue, PersistenceError},
+    source::Source,
+};
+use gnify_core::{
+    device::{DeviceStatus, DeviceToken, DeviceView, GetDevice, SessionToken},
+    user::GetUser,
+};
+
+use crate::application::{AppState, AuthProfile};
+
+use super::AuthSession;
+
+/// Why a bearer token was not accepted.
+enum Rejection {
+    /// Missing from both `core.session` and `core.device`, or expired.
+    Unknown,
+    /// The device isn't approved (anymore).
+    Inactive(DeviceStatus),
+    Failed(gnify::Error),
+}
+
+impl From<PersistenceError> for Rejection {
+    fn from(error: PersistenceError) -> Self {
+        Rejection::Failed(error.into())
+    }
+}
+
+impl From<InvalidValue> for Rejection {
+    fn from(error: InvalidValue) -> Self {
+        Rejection::Failed(error.into())
+    }
+}
+
+/// Authenticates requests carrying `Authorization: Bearer <token>`, where the
+/// token is a session token or, without a user logged in, the device's own
+/// token. The resulting [`AuthProfile`] takes the place of the cookie
+/// session's user for this request only; nothing is stored in the session.
+///
+/// Requests without the header pass through untouched. Must run inside the
+/// auth manager layer.
+pub async fn bearer(State(state): State<AppState>, mut request: Request, next: Next) -> Response {
+    let Some(value) = request.headers().get(header::AUTHORIZATION) else {
+        return next.run(request).await;
+    };
+    let Some(token) = bearer_token(value) else {
+        return challenge();
+    };
+    let profile = match resolve(&state, token).await {
+        Ok(profile) => profile,
+        Err(Rejection::Unknown) => {
+            tracing::info!(path = %request.uri().path(), "rejected unknown bearer token");
+            return challenge();
+        }
+        Err(Rejection::Inactive(status)) => {
+            tracing::warn!(path = %request.uri().path(), status = status.as_str(), "rejected bearer token of inactive device");
+            return challenge();
+        }
+        Err(Rejection::Failed(error)) => {
+            tracing::warn!(%error, "couldn't resolve bearer token");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    let Some(session) = request.extensions_mut().get_mut::<AuthSession>() else {
+        tracing::error!("bearer layer runs outside the auth manager layer");
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+    session.user = Some(profile);
+    next.run(request).await
+}
+
+/// The token of an `Authorization` header using the bearer scheme.
+fn bearer_token(value: &HeaderValue) -> Option<&str> {
+    let token = value.to_str().ok()?.strip_prefix("Bearer ")?.trim();
+    (!token.is_empty()).then_some(token)
+}
+
+/// Session tokens are tried first; both kinds are only ever compared by hash.
+async fn resolve(state: &AppState, token: &str) -> Result<AuthProfile, Rejection> {
+    if let Ok(session) = token.parse::<SessionToken>() {
+        if let Some(device) = state.source.read(GetDevice::by_session(&session)?).await? {
+            let device = approved(device)?;
+            let hash = session.hash()?;
+            let user_id = device
+                .sessions()
+                .iter()
+                .find(|session| session.token_hash == hash)
+                .map(|session| session.user_id.value())
+                .ok_or(Rejection::Unknown)?;
+            let user = state
+                .source
+                .read(GetUser {
+                    id: Some(user_id),
+                    ..Default::default()
+                })
+                .await?
+                .ok_or(Rejection::Unknown)?;
+            return Ok(AuthProfile::from(&user).on_device(&device));
+        }
+    }
+    let token = token.parse::<DeviceToken>().map_err(|_| Rejection::Unknown)?;
+    let device = state
+        .source
+        .read(GetDevice::by_token(&token)?)
+        .await?
+        .ok_or(Rejection::Unknown)?;
+    Ok(AuthProfile::for_device(&approved(device)?))
+}
+
+fn approved(device: DeviceView) -> Result<DeviceView, Rejection> {
+    match device.status() {
+        DeviceStatus::Approved => Ok(device),
+        status => Err(Rejection::Inactive(status)),
+    }
+}
+
+fn challenge() -> Response {
+    (
+        StatusCode::UNAUTHORIZED,
+        [(header::WWW_AUTHENTICATE, HeaderValue::from_static(r#"Bearer error="invalid_token""#))],
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bearer_token_is_read_from_the_bearer_scheme_only() {
+        let token = DeviceToken::generate().to_string();
+        let header = HeaderValue::from_str(&format!("Bearer {token} ")).unwrap();
+        assert_eq!(bearer_token(&header), Some(token.as_str()));
+        assert_eq!(bearer_token(&HeaderValue::from_static("Basic dXNlcjpwYXNz")), None);
+        assert_eq!(bearer_token(&HeaderValue::from_static("Bearer ")), None);
+        assert_eq!(bearer_token(&HeaderValue::from_static("bearer token")), None);
+    }
+
+    #[test]
+    fn challenge_asks_for_a_valid_bearer_token() {
+        let response = challenge();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            response.headers()[header::WWW_AUTHENTICATE],
+            HeaderValue::from_static(r#"Bearer error="invalid_token""#)
+        );
+    }
+}
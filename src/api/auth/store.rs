@@ -0,0 +1,133 @@
+use std::{sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use axum_login::tower_sessions::{
+    session::{Id, Record},
+    session_store, ExpiredDeletion, SessionStore,
+};
+use gnify::source::{PgSource, Source};
+use gnify_core::login::{
+    DeleteExpiredLoginSessions, DeleteLoginSession, GetLoginSession, LoginSession,
+    WriteLoginSession,
+};
+use smol::{stream::StreamExt, Timer};
+use chrono::{DateTime, NaiveDateTime};
+use time::OffsetDateTime;
+
+#[derive(Clone)]
+pub struct PgSessionStore {
+    source: Arc<PgSource>,
+}
+
+impl std::fmt::Debug for PgSessionStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PgSessionStore").finish_non_exhaustive()
+    }
+}
+
+impl PgSessionStore {
+    pub fn new(source: Arc<PgSource>) -> Self {
+        Self { source }
+    }
+
+    pub async fn run_cleanup(self, period: Duration) {
+        let mut interval = Timer::interval(period);
+        while interval.next().await.is_some() {
+            if let Err(error) = self.delete_expired().await {
+                tracing::warn!(%error, "couldn't delete expired login sessions");
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl SessionStore for PgSessionStore {
+    async fn save(&self, record: &Record) -> session_store::Result<()> {
+        let data = serde_json::to_vec(&record.data)
+            .map_err(|error| session_store::Error::Encode(error.to_string()))?;
+        let session = LoginSession {
+            id: record.id.to_string(),
+            data,
+            expiration: expiration(record.expiry_date)?.into(),
+        };
+        self.source
+            .write(WriteLoginSession { session })
+            .await
+            .map_err(backend)
+    }
+
+    async fn load(&self, session_id: &Id) -> session_store::Result<Option<Record>> {
+        let session = self
+            .source
+            .read(GetLoginSession {
+                id: session_id.to_string(),
+            })
+            .await
+            .map_err(backend)?;
+        let Some(session) = session else {
+            return Ok(None);
+        };
+        let data = serde_json::from_slice(&session.data)
+            .map_err(|error| session_store::Error::Decode(error.to_string()))?;
+        Ok(Some(Record {
+            id: *session_id,
+            data,
+            expiry_date: expiry_date(*session.expiration)?,
+        }))
+    }
+
+    async fn delete(&self, session_id: &Id) -> session_store::Result<()> {
+        self.source
+            .write(DeleteLoginSession {
+                id: session_id.to_string(),
+            })
+            .await
+            .map_err(backend)
+    }
+}
+
+#[async_trait]
+impl ExpiredDeletion for PgSessionStore {
+    async fn delete_expired(&self) -> session_store::Result<()> {
+        self.source
+            .write(DeleteExpiredLoginSessions)
+            .await
+            .map_err(backend)
+    }
+}
+
+fn backend(error: impl ToString) -> session_store::Error {
+    session_store::Error::Backend(error.to_string())
+}
+
+fn expiration(expiry_date: OffsetDateTime) -> session_store::Result<NaiveDateTime> {
+    DateTime::from_timestamp(expiry_date.unix_timestamp(), expiry_date.nanosecond())
+        .map(|expiration| expiration.naive_utc())
+        .ok_or_else(|| session_store::Error::Encode(String::from("expiry date out of range")))
+}
+
+fn expiry_date(expiration: NaiveDateTime) -> session_store::Result<OffsetDateTime> {
+    OffsetDateTime::from_unix_timestamp(expiration.and_utc().timestamp())
+        .map_err(|error| session_store::Error::Decode(error.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expiry_dates_survive_the_round_trip_to_whole_seconds() {
+        let date = OffsetDateTime::from_unix_timestamp(1_800_000_000).unwrap();
+        let stored = expiration(date).unwrap();
+        assert_eq!(stored.and_utc().timestamp(), 1_800_000_000);
+        assert_eq!(expiry_date(stored).unwrap(), date);
+    }
+
+    #[test]
+    fn expiry_dates_are_stored_in_utc() {
+        let date = OffsetDateTime::from_unix_timestamp(1_800_000_000)
+            .unwrap()
+            .to_offset(time::UtcOffset::from_hms(2, 0, 0).unwrap());
+        assert_eq!(expiration(date).unwrap().and_utc().timestamp(), 1_800_000_000);
+    }
+}
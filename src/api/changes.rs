@@ -0,0 +1,71 @@
+use std::{collections::HashSet, time::Duration};
+
+use axum::{
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode},
+    response::{
+        sse::{Event, Sse},
+        IntoResponse, Response,
+    },
+    routing::get,
+    Router,
+};
+use serde::Deserialize;
+use smol::{
+    stream::{self, StreamExt},
+    Timer,
+};
+
+use crate::{application::AppState, feed::Subscription};
+
+use super::auth::AuthSession;
+
+/// Comment sent on idle streams so proxies keep them open.
+const HEARTBEAT: Duration = Duration::from_secs(15);
+
+pub fn router() -> Router<AppState> {
+    Router::new().route("/changes", get(stream_changes))
+}
+
+#[derive(Deserialize)]
+struct ChangesQuery {
+    /// Comma separated aggregate types such as `User,Device`; all when absent.
+    types: Option<String>,
+}
+
+/// Streams the changes to users, roles and devices the caller may see as
+/// Server-Sent Events, named after the domain event and identified by their
+/// outbox entry. A client reconnecting with `Last-Event-ID` first gets the
+/// changes stored after that entry.
+async fn stream_changes(
+    State(state): State<AppState>,
+    session: AuthSession,
+    headers: HeaderMap,
+    Query(query): Query<ChangesQuery>,
+) -> Response {
+    let Some(profile) = session.user else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+    let last_seen = match headers.get("last-event-id").map(|value| value.to_str().ok()?.parse::<i64>().ok()) {
+        None => None,
+        Some(Some(id)) => Some(id),
+        Some(None) => return StatusCode::BAD_REQUEST.into_response(),
+    };
+    let types: Option<HashSet<String>> = query
+        .types
+        .map(|types| types.split(',').map(|name| name.trim().to_string()).collect());
+    let subscription = Subscription::new(&state.changes, state.source.clone(), profile, last_seen);
+    let changes = stream::unfold(subscription, |mut subscription| async move {
+        let change = subscription.next().await?;
+        Some((change, subscription))
+    })
+    .filter(move |change| types.as_ref().is_none_or(|types| types.contains(&change.aggregate_type)))
+        .map(|change| {
+            Event::default()
+                .id(change.id.to_string())
+                .event(change.event.as_str())
+                .json_data(&*change)
+        });
+    let heartbeat = Timer::interval(HEARTBEAT).map(|_| Ok(Event::default().comment("")));
+    Sse::new(changes.or(heartbeat)).into_response()
+}
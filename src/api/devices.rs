@@ -0,0 +1,353 @@
+use std::time::Duration;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    middleware::from_fn_with_state,
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Extension, Json, Router,
+};
+use gnify::{repository::Repository, source::Source, vo::ID};
+use gnify_core::{
+    actor::Actor,
+    device::{
+        Device, DeviceStatus, DeviceToken, DeviceUpdate, DeviceView, ExpirationTimestamp, GetDevice, ListDevices,
+        SessionToken,
+    },
+    policy::Effect,
+    privilege,
+};
+use serde::{Deserialize, Serialize};
+use ulid::Ulid;
+
+use crate::{
+    application::{AppState, AuthProfile, Principal},
+    device_session::{self, DeviceLogin, SessionError},
+    device_token,
+};
+
+use super::auth::{require, AuthSession, Requirement};
+
+pub fn router() -> Router<AppState> {
+    let manage = Router::new()
+        .route("/devices", get(list_devices))
+        .route("/devices/:id/:action", post(change_status))
+        .route("/device-tokens/rotate", post(rotate_all))
+        .route("/device-tokens/:id/rotate", post(rotate))
+        .route("/device-tokens/:id/revoke", post(revoke))
+        .route_layer(from_fn_with_state(Requirement::privilege(privilege::MANAGE_DEVICES), require));
+    Router::new()
+        .merge(manage)
+        .route("/devices", post(register_device))
+        .route("/device-tokens/self/rotate", post(rotate_own))
+        .route("/device-sessions", post(login))
+        .route("/device-sessions/refresh", post(refresh))
+        .route("/device-sessions/logout", post(logout))
+}
+
+#[derive(Deserialize)]
+struct RegisterRequest {
+    name: String,
+}
+
+#[derive(Serialize)]
+struct Registration {
+    id: ID<Device>,
+    token: DeviceToken,
+    status: DeviceStatus,
+}
+
+/// Device tokens travel in the body so that they stay out of access logs.
+#[derive(Deserialize)]
+struct LoginRequest {
+    device: DeviceToken,
+    username: String,
+    password: String,
+}
+
+#[derive(Deserialize)]
+struct SessionRequest {
+    device: DeviceToken,
+    session: SessionToken,
+}
+
+#[derive(Serialize)]
+struct SessionResponse {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    session: Option<SessionToken>,
+    expiration: ExpirationTimestamp,
+}
+
+#[derive(Deserialize)]
+struct ListQuery {
+    status: Option<DeviceStatus>,
+}
+
+/// Without `overlap` (in seconds) the configured one applies; zero cuts the
+/// previous token off at once.
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct RotateRequest {
+    overlap: Option<u64>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct BulkRotateRequest {
+    status: Option<DeviceStatus>,
+    overlap: Option<u64>,
+}
+
+/// Lets a device register itself; it stays pending until an administrator
+/// approves it. The token is only ever returned here.
+async fn register_device(State(state): State<AppState>, Json(request): Json<RegisterRequest>) -> Response {
+    let (record, token) = match Device::register(&request.name, Actor::System.id()) {
+        Ok(registration) => registration,
+        Err(error) => return (StatusCode::UNPROCESSABLE_ENTITY, error.to_string()).into_response(),
+    };
+    let id = record.id();
+    match Repository::<_, Device>::new(state.source.as_ref(), &state.events)
+        .save(record, Actor::System)
+        .await
+    {
+        Ok(()) => (
+            StatusCode::CREATED,
+            Json(Registration {
+                id,
+                token,
+                status: DeviceStatus::Pending,
+            }),
+        )
+            .into_response(),
+        Err(error) => {
+            tracing::warn!(%error, "device registration failed");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// Lists the devices the policies let the caller manage.
+async fn list_devices(
+    State(state): State<AppState>,
+    Extension(profile): Extension<AuthProfile>,
+    Query(query): Query<ListQuery>,
+) -> Response {
+    match state.source.read(ListDevices { status: query.status }).await {
+        Ok(devices) => {
+            let devices: Vec<DeviceView> = devices
+                .into_iter()
+                .filter(|device| state.policies.authorize(&profile, privilege::MANAGE_DEVICES, device) == Effect::Allow)
+                .collect();
+            Json(devices).into_response()
+        }
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+/// The device `id` names, when the policies let the caller manage it.
+async fn managed(state: &AppState, profile: &AuthProfile, id: &str) -> Result<DeviceView, StatusCode> {
+    let Ok(id) = id.parse::<Ulid>() else {
+        return Err(StatusCode::NOT_FOUND);
+    };
+    let device = match state.source.read(GetDevice::by_id(id)).await {
+        Ok(Some(device)) => device,
+        Ok(None) => return Err(StatusCode::NOT_FOUND),
+        Err(_) => return Err(StatusCode::INTERNAL_SERVER_ERROR),
+    };
+    match state.policies.authorize(profile, privilege::MANAGE_DEVICES, &device) {
+        Effect::Allow => Ok(device),
+        Effect::Deny => {
+            tracing::warn!(principal = %profile.principal, device = %id, "forbidden device management");
+            Err(StatusCode::FORBIDDEN)
+        }
+    }
+}
+
+/// Approves, rejects, suspends or revokes a device. Transitions the
+/// lifecycle doesn't allow are answered with 409.
+async fn change_status(
+    State(state): State<AppState>,
+    Extension(profile): Extension<AuthProfile>,
+    Path((id, action)): Path<(String, String)>,
+) -> Response {
+    let status = match action.as_str() {
+        "approve" => DeviceStatus::Approved,
+        "reject" => DeviceStatus::Rejected,
+        "suspend" => DeviceStatus::Suspended,
+        "revoke" => DeviceStatus::Revoked,
+        _ => return StatusCode::NOT_FOUND.into_response(),
+    };
+    let id = match managed(&state, &profile, &id).await {
+        Ok(device) => device.id().value(),
+        Err(status) => return status.into_response(),
+    };
+    let result = Repository::<_, Device>::new(state.source.as_ref(), &state.events)
+        .modify(ID::new(id), profile.actor(), |update: &mut DeviceUpdate| {
+            Ok(update.set_status(status)?)
+        })
+        .await;
+    match result {
+        Ok(_) => {
+            tracing::info!(principal = %profile.principal, status = status.as_str(), "changed device status");
+            match state.source.read(GetDevice::by_id(id)).await {
+                Ok(Some(device)) => Json(device).into_response(),
+                Ok(None) => StatusCode::NOT_FOUND.into_response(),
+                Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+            }
+        }
+        Err(gnify::Error::InvalidValue(error)) => (StatusCode::CONFLICT, error.to_string()).into_response(),
+        Err(gnify::Error::VersionConflict(_)) => StatusCode::CONFLICT.into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+/// Issues the device a new token, returned this once. The previous token
+/// keeps working for the overlap.
+async fn rotate(
+    State(state): State<AppState>,
+    Extension(profile): Extension<AuthProfile>,
+    Path(id): Path<String>,
+    Json(request): Json<RotateRequest>,
+) -> Response {
+    let id = match managed(&state, &profile, &id).await {
+        Ok(device) => device.id().value(),
+        Err(status) => return status.into_response(),
+    };
+    let overlap = request.overlap.map_or(state.rotation_overlap, Duration::from_secs);
+    match device_token::rotate(&state.source, &state.events, profile.actor(), ID::new(id), overlap).await {
+        Ok(rotation) => {
+            tracing::info!(principal = %profile.principal, device = %id, overlap = overlap.as_secs(), "rotated device token");
+            Json(rotation).into_response()
+        }
+        Err(gnify::Error::InvalidValue(error)) => (StatusCode::CONFLICT, error.to_string()).into_response(),
+        Err(gnify::Error::VersionConflict(_)) => StatusCode::CONFLICT.into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+/// Lets a device calling with its own bearer token swap it for a new one,
+/// returned this once. The previous token keeps working for the configured
+/// overlap so that the device can switch over.
+async fn rotate_own(State(state): State<AppState>, session: AuthSession) -> Response {
+    let Some(profile) = session.user else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+    let Principal::Device(id) = profile.principal else {
+        return StatusCode::FORBIDDEN.into_response();
+    };
+    let overlap = state.rotation_overlap;
+    match device_token::rotate(&state.source, &state.events, profile.actor(), id, overlap).await {
+        Ok(rotation) => {
+            tracing::info!(device = %id.value(), overlap = overlap.as_secs(), "device rotated its token");
+            Json(rotation).into_response()
+        }
+        Err(gnify::Error::InvalidValue(error)) => (StatusCode::CONFLICT, error.to_string()).into_response(),
+        Err(gnify::Error::VersionConflict(_)) => StatusCode::CONFLICT.into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+/// Rotates the tokens of many devices at once, e.g. after a suspected leak.
+/// Answers with every new token and with the devices that couldn't be
+/// rotated.
+async fn rotate_all(
+    State(state): State<AppState>,
+    Extension(profile): Extension<AuthProfile>,
+    Json(request): Json<BulkRotateRequest>,
+) -> Response {
+    let overlap = request.overlap.map_or(state.rotation_overlap, Duration::from_secs);
+    let result = device_token::rotate_all(
+        &state.source,
+        &state.events,
+        &state.policies,
+        profile.actor(),
+        request.status,
+        overlap,
+    )
+    .await;
+    match result {
+        Ok(outcome) => {
+            tracing::warn!(principal = %profile.principal, rotated = outcome.rotated.len(), "bulk device token rotation");
+            Json(outcome).into_response()
+        }
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+/// Invalidates the device's tokens at once and ends its sessions; a later
+/// rotation issues a working token again.
+async fn revoke(
+    State(state): State<AppState>,
+    Extension(profile): Extension<AuthProfile>,
+    Path(id): Path<String>,
+) -> Response {
+    let id = match managed(&state, &profile, &id).await {
+        Ok(device) => device.id().value(),
+        Err(status) => return status.into_response(),
+    };
+    match device_token::revoke(&state.source, &state.events, profile.actor(), ID::new(id)).await {
+        Ok(()) => {
+            tracing::warn!(principal = %profile.principal, device = %id, "revoked device token");
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Err(gnify::Error::VersionConflict(_)) => StatusCode::CONFLICT.into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+/// Logs a user in on an approved device. The device authenticates with its
+/// token, the user with their credentials.
+async fn login(State(state): State<AppState>, Json(request): Json<LoginRequest>) -> Response {
+    let result = device_session::login(
+        &state.source,
+        &state.events,
+        &state.device_sessions,
+        request.device,
+        &request.username,
+        &request.password,
+    )
+    .await;
+    match result {
+        Ok(DeviceLogin { session, expiration }) => (
+            StatusCode::CREATED,
+            Json(SessionResponse {
+                session: Some(session),
+                expiration,
+            }),
+        )
+            .into_response(),
+        Err(error) => session_error(error),
+    }
+}
+
+async fn refresh(State(state): State<AppState>, Json(request): Json<SessionRequest>) -> Response {
+    let rules = &state.device_sessions;
+    match device_session::refresh(&state.source, &state.events, rules, request.device, &request.session).await {
+        Ok(expiration) => Json(SessionResponse {
+            session: None,
+            expiration,
+        })
+        .into_response(),
+        Err(error) => session_error(error),
+    }
+}
+
+async fn logout(State(state): State<AppState>, Json(request): Json<SessionRequest>) -> Response {
+    match device_session::logout(&state.source, &state.events, request.device, &request.session).await {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(error) => session_error(error),
+    }
+}
+
+fn session_error(error: SessionError) -> Response {
+    match error {
+        SessionError::InvalidCredentials => StatusCode::UNAUTHORIZED.into_response(),
+        SessionError::UnknownDevice => StatusCode::NOT_FOUND.into_response(),
+        SessionError::Refused(reason) => (StatusCode::CONFLICT, reason).into_response(),
+        SessionError::Failed(error) => {
+            tracing::warn!(%error, "device session operation failed");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
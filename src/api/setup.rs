@@ -0,0 +1,51 @@
+use axum::{extract::State, http::StatusCode, routing::post, Json, Router};
+use serde::Deserialize;
+
+use crate::{
+    application::AppState,
+    bootstrap::{self, Administrator},
+};
+
+pub fn router() -> Router<AppState> {
+    Router::new().route("/setup", post(setup))
+}
+
+#[derive(Deserialize)]
+struct SetupRequest {
+    token: String,
+    username: String,
+    password: String,
+    email: Option<String>,
+}
+
+/// Creates the first administrator, consuming the setup token issued at
+/// startup.
+async fn setup(State(state): State<AppState>, Json(request): Json<SetupRequest>) -> StatusCode {
+    let token = {
+        let mut setup_token = state.setup_token.lock().expect("setup token lock poisoned");
+        match setup_token.as_ref() {
+            Some(token) if token.matches(&request.token) => setup_token.take(),
+            _ => None,
+        }
+    };
+    let Some(token) = token else {
+        tracing::warn!("rejected setup attempt");
+        return StatusCode::FORBIDDEN;
+    };
+    let administrator = Administrator {
+        username: &request.username,
+        password: &request.password,
+        email: request.email.as_deref(),
+    };
+    match bootstrap::create_administrator(&state.source, &state.events, &state.bootstrap, administrator).await {
+        Ok(()) => {
+            tracing::info!(username = %request.username, "created initial administrator");
+            StatusCode::CREATED
+        }
+        Err(error) => {
+            tracing::warn!(%error, "setup failed");
+            *state.setup_token.lock().expect("setup token lock poisoned") = Some(token);
+            StatusCode::UNPROCESSABLE_ENTITY
+        }
+    }
+}
@@ -0,0 +1,47 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use gnify::source::Source;
+use gnify_core::{policy::Effect, privilege, user::GetUser};
+use ulid::Ulid;
+
+use crate::application::AppState;
+
+use super::auth::AuthSession;
+
+pub fn router() -> Router<AppState> {
+    Router::new().route("/users/:id", get(get_user))
+}
+
+async fn get_user(
+    State(state): State<AppState>,
+    session: AuthSession,
+    Path(id): Path<Ulid>,
+) -> Response {
+    let Some(profile) = session.user else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+    let user = match state
+        .source
+        .read(GetUser {
+            id: Some(id),
+            ..Default::default()
+        })
+        .await
+    {
+        Ok(Some(user)) => user,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+    match state.policies.authorize(&profile, privilege::GET_USER_DETAILS, &user) {
+        Effect::Allow => Json(user).into_response(),
+        Effect::Deny => {
+            tracing::warn!(principal = %profile.principal, target = %id, "forbidden user details");
+            StatusCode::FORBIDDEN.into_response()
+        }
+    }
+}
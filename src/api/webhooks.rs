@@ -0,0 +1,154 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    middleware::from_fn_with_state,
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Extension, Json, Router,
+};
+use chrono::Utc;
+use gnify::source::Source;
+use gnify_core::{
+    privilege,
+    webhook::{CreateWebhook, DeleteWebhook, GetWebhook, ListWebhookDeliveries, ListWebhooks, Webhook},
+};
+use serde::{Deserialize, Serialize};
+use ulid::Ulid;
+
+use crate::{
+    application::{AppState, AuthProfile},
+    webhook,
+};
+
+use super::auth::{require, Requirement};
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/webhooks", get(list_webhooks).post(create_webhook))
+        .route("/webhooks/:id", get(get_webhook).delete(delete_webhook))
+        .route("/webhooks/:id/deliveries", get(list_deliveries))
+        .route("/webhooks/:id/ping", post(ping_webhook))
+        .route_layer(from_fn_with_state(Requirement::privilege(privilege::MANAGE_WEBHOOKS), require))
+}
+
+#[derive(Deserialize)]
+struct CreateWebhookRequest {
+    url: String,
+    #[serde(default)]
+    events: Vec<String>,
+    /// Generated when absent.
+    secret: Option<String>,
+}
+
+#[derive(Serialize)]
+struct CreatedWebhook {
+    #[serde(flatten)]
+    webhook: Webhook,
+    secret: String,
+}
+
+#[derive(Deserialize)]
+struct DeliveriesQuery {
+    #[serde(default = "default_limit")]
+    limit: i64,
+}
+
+fn default_limit() -> i64 {
+    50
+}
+
+async fn list_webhooks(State(state): State<AppState>) -> Response {
+    match state.source.read(ListWebhooks).await {
+        Ok(webhooks) => Json(webhooks).into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+async fn create_webhook(
+    State(state): State<AppState>,
+    Extension(profile): Extension<AuthProfile>,
+    Json(request): Json<CreateWebhookRequest>,
+) -> Response {
+    if !(request.url.starts_with("http://") || request.url.starts_with("https://")) {
+        return (StatusCode::UNPROCESSABLE_ENTITY, "expected an http or https URL").into_response();
+    }
+    if let Some(Err(error)) = request.secret.as_deref().map(webhook::check_secret) {
+        return (StatusCode::UNPROCESSABLE_ENTITY, error).into_response();
+    }
+    let secret = request.secret.unwrap_or_else(webhook::generate_secret);
+    let webhook = Webhook {
+        id: Ulid::new(),
+        url: request.url,
+        events: request.events,
+        secret: secret.clone(),
+        active: true,
+        created_at: Utc::now().naive_utc(),
+    };
+    match state.source.write(CreateWebhook { webhook: webhook.clone() }).await {
+        Ok(()) => {
+            tracing::info!(principal = %profile.principal, webhook = %webhook.id, url = %webhook.url, "created webhook");
+            (StatusCode::CREATED, Json(CreatedWebhook { webhook, secret })).into_response()
+        }
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+async fn get_webhook(State(state): State<AppState>, Path(id): Path<Ulid>) -> Response {
+    match state.source.read(GetWebhook { id }).await {
+        Ok(Some(webhook)) => Json(webhook).into_response(),
+        Ok(None) => StatusCode::NOT_FOUND.into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+async fn delete_webhook(
+    State(state): State<AppState>,
+    Extension(profile): Extension<AuthProfile>,
+    Path(id): Path<Ulid>,
+) -> StatusCode {
+    match state.source.read(GetWebhook { id }).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return StatusCode::NOT_FOUND,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR,
+    }
+    match state.source.write(DeleteWebhook { id }).await {
+        Ok(()) => {
+            tracing::info!(principal = %profile.principal, webhook = %id, "deleted webhook");
+            StatusCode::NO_CONTENT
+        }
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+async fn list_deliveries(
+    State(state): State<AppState>,
+    Path(id): Path<Ulid>,
+    Query(query): Query<DeliveriesQuery>,
+) -> Response {
+    match state
+        .source
+        .read(ListWebhookDeliveries {
+            webhook_id: id,
+            limit: query.limit.clamp(1, 500),
+        })
+        .await
+    {
+        Ok(deliveries) => Json(deliveries).into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+/// Sends a signed test delivery and answers with its outcome, so partners
+/// can check their endpoint and signature verification.
+async fn ping_webhook(State(state): State<AppState>, Path(id): Path<Ulid>) -> Response {
+    let webhook = match state.source.read(GetWebhook { id }).await {
+        Ok(Some(webhook)) => webhook,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+    match state.webhooks.ping(&webhook).await {
+        Ok(Ok(status)) => Json(serde_json::json!({ "status": status })).into_response(),
+        Ok(Err(error)) => (StatusCode::BAD_GATEWAY, error).into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
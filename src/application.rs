@@ -1,62 +1,234 @@
-use std::{collections::HashSet, sync::Arc};
+use std::{
+    collections::HashSet,
+    fmt::Display,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
-use gnify::source::{PgSource, Source};
+use gnify::{
+    error::InvalidValue,
+    event::EventBus,
+    source::{PgSource, Source},
+    vo::ID,
+};
 use gnify_core::{
-    role::{GetRole, Role, WriteRole},
-    user::{GetUser, User, WriteUser},
+    actor::Actor,
+    device::{Device, DeviceView, HashLegacyDeviceTokens, SessionRules},
+    policy::{Attributes, PolicySet, Subject},
+    privilege::{self, EffectivePrivileges, PrivilegeGroups, SyncPrivileges, UnresolvedGrants},
+    role::RoleLevel,
+    user::{DetailedUserView, User},
+    Site,
 };
-use phf::{phf_map, Map};
+use once_cell::sync::Lazy;
 use ulid::Ulid;
 
-pub static PRIVILEGES: Map<&'static str, &'static [&'static str]> = phf_map! {
-    "MANAGE USERS" => &[
-        "REGISTER USER",
-        "GET USER DETAILS"
-    ],
-    "MANAGE ROLES" => &[
-        "REGISTER ROLES",
-        "GET USER DETAILS"
-    ]
+use crate::{
+    bootstrap::{self, SetupToken},
+    config::{BootstrapConfig, Config},
+    feed::ChangeFeed,
+    seed::Seed,
+    webhook::SubscriptionTarget,
 };
 
+pub static PRIVILEGE_GROUPS: Lazy<PrivilegeGroups> = Lazy::new(PrivilegeGroups::catalogue);
+
+/// Who a request is made by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Principal {
+    User(ID<User>),
+    /// A device calling with its own token while nobody is logged in on it.
+    Device(ID<Device>),
+}
+
+impl Principal {
+    pub fn user(&self) -> Option<ID<User>> {
+        match self {
+            Principal::User(id) => Some(*id),
+            Principal::Device(_) => None,
+        }
+    }
+
+    pub fn value(&self) -> Ulid {
+        match self {
+            Principal::User(id) => id.value(),
+            Principal::Device(id) => id.value(),
+        }
+    }
+}
+
+impl Display for Principal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Principal::User(id) => write!(f, "user {}", id.value()),
+            Principal::Device(id) => write!(f, "device {}", id.value()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct AuthProfile {
-    pub id: Ulid,
+    pub principal: Principal,
     pub privileges: HashSet<String>,
-    pub level: u8,
+    pub level: RoleLevel,
+    pub site: Option<Site>,
+    /// The device the request came through, for bearer-token callers.
+    pub device: Option<Ulid>,
+    pub(crate) auth_hash: Vec<u8>,
+}
+
+impl AuthProfile {
+    /// A device calling on its own behalf; it holds no privileges.
+    pub fn for_device(device: &DeviceView) -> Self {
+        Self {
+            principal: Principal::Device(device.id()),
+            privileges: HashSet::new(),
+            level: RoleLevel::GUEST,
+            site: device.site().cloned(),
+            device: Some(device.id().value()),
+            auth_hash: Vec::new(),
+        }
+    }
+
+    /// The session user's profile, seen through the device they're logged
+    /// in on.
+    pub fn on_device(self, device: &DeviceView) -> Self {
+        Self {
+            device: Some(device.id().value()),
+            ..self
+        }
+    }
+
+    /// Whether `privilege` is among the profile's effective privileges.
+    pub fn has_privilege(&self, privilege: &str) -> bool {
+        self.privileges.contains(privilege)
+    }
+
+    pub fn actor(&self) -> Actor {
+        match self.principal {
+            Principal::User(id) => Actor::User {
+                id,
+                level: self.level,
+                privileges: self
+                    .privileges
+                    .iter()
+                    .filter_map(|privilege| privilege.parse().ok())
+                    .collect(),
+                site: self.site.clone(),
+            },
+            Principal::Device(id) => Actor::Device(id),
+        }
+    }
+}
+
+impl Attributes for AuthProfile {
+    fn attribute(&self, name: &str) -> Option<String> {
+        match name {
+            "id" => self.principal.user().map(|id| id.value().to_string()),
+            "level" => Some(self.level.rank().to_string()),
+            "site" => self.site.as_ref().map(ToString::to_string),
+            "device" => self.device.map(|device| device.to_string()),
+            _ => None,
+        }
+    }
+}
+
+impl Subject for AuthProfile {
+    fn level(&self) -> RoleLevel {
+        self.level
+    }
+
+    fn holds(&self, privilege: &gnify_core::Privilege) -> bool {
+        self.has_privilege(privilege)
+    }
+}
+
+impl From<&DetailedUserView> for AuthProfile {
+    fn from(user: &DetailedUserView) -> Self {
+        let privileges = EffectivePrivileges::resolve(user, &PRIVILEGE_GROUPS)
+            .privileges()
+            .map(ToString::to_string)
+            .collect();
+        Self {
+            principal: Principal::User(user.id()),
+            privileges,
+            level: user.level(),
+            site: user.site().cloned(),
+            device: None,
+            auth_hash: user.password().to_string().into_bytes(),
+        }
+    }
 }
 
 #[derive(Clone)]
 pub struct AppState {
     pub source: Arc<PgSource>,
+    pub bootstrap: Arc<BootstrapConfig>,
+    pub setup_token: Arc<Mutex<Option<SetupToken>>>,
+    /// Domain events of records stored through a repository; subscribe here
+    /// to react to them in-process.
+    pub events: EventBus,
+    pub webhooks: Arc<SubscriptionTarget>,
+    /// Changes committed by any instance, for streaming to clients.
+    pub changes: ChangeFeed,
+    pub device_sessions: Arc<SessionRules>,
+    /// Default overlap of device token rotations.
+    pub rotation_overlap: Duration,
+    pub policies: Arc<PolicySet>,
 }
 
 impl AppState {
-    pub async fn init(url: &'static str) -> gnify::error::Result<AppState> {
-        let source = PgSource::new(url).await?;
-        let role = source.read(GetRole::by_name("DEVELOPER")).await?;
-        let role_id = if let Some(id) = role.map(|role| role.id()) {
-            id.value()
-        } else {
-            let id = Ulid::new();
-            let role = Role::new(id, "DEVELOPER", "Developer", HashSet::new(), Ulid::nil())?;
-            source.write(WriteRole { record: role }).await?;
-            id
-        };
-        let user = source.read(GetUser::by_username("developer")).await?;
-
-        if user.is_none() {
-            let user = User::new(
-                Ulid::new(),
-                "developer",
-                "1234",
-                None,
-                Some(role_id),
-                Ulid::nil(),
-            )?;
-            source.write(WriteUser { record: user }).await?;
+    pub async fn init(config: &Config) -> gnify::error::Result<AppState> {
+        privilege::validate_catalogue()?;
+        let bootstrap = config.bootstrap.clone();
+        let database = &config.database;
+        let source = PgSource::connect(database.url(), &database.pool_options()).await?;
+        sync_privileges(&source).await?;
+        source.write(HashLegacyDeviceTokens).await?;
+        let events = event_bus();
+        let setup_token = bootstrap::run(&source, &events, &bootstrap).await?;
+        if let Some(path) = &bootstrap.seed_file {
+            let report = Seed::load(path)?.apply(&source, Actor::System, &events).await?;
+            tracing::info!("Applied seed file {}\n{report}", path.display());
         }
+        let source = Arc::new(source);
+        let webhooks = SubscriptionTarget::new(source.clone(), config.outbox.timeout)
+            .map_err(|error| InvalidValue::new(format!("webhook client ({error})")))?;
         Ok(Self {
-            source: Arc::new(source),
+            source,
+            bootstrap: Arc::new(bootstrap),
+            setup_token: Arc::new(Mutex::new(setup_token)),
+            events,
+            webhooks: Arc::new(webhooks),
+            changes: ChangeFeed::default(),
+            device_sessions: Arc::new(config.device_session.rules()),
+            rotation_overlap: config.tokens.rotation_overlap,
+            policies: Arc::new(config.policies.load()?),
         })
     }
 }
+
+/// Mirrors the privilege catalogue into the database and warns about every
+/// grant of a privilege outside it.
+pub async fn sync_privileges(source: &PgSource) -> gnify::error::Result<()> {
+    source.write(SyncPrivileges).await?;
+    for grant in source.read(UnresolvedGrants).await? {
+        tracing::warn!(
+            holder = grant.holder,
+            id = %grant.holder_id,
+            privilege = grant.privilege,
+            "grant of a privilege outside the catalogue"
+        );
+    }
+    Ok(())
+}
+
+/// The bus domain events are published on, with the subscribers the server
+/// and the command line share.
+pub fn event_bus() -> EventBus {
+    let events = EventBus::new();
+    events.subscribe_all(|event| {
+        tracing::debug!(event = event.name(), aggregate = %event.aggregate(), "domain event");
+    });
+    events
+}
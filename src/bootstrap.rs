@@ -0,0 +1,225 @@
+use std::{collections::HashSet, io::Write, path::Path};
+
+use gnify::{
+    error::error,
+    event::EventBus,
+    repository::Repository,
+    source::{PgSource, Source},
+};
+use gnify_core::{
+    actor::Actor,
+    privilege::CATALOGUE,
+    role::{GetRole, GetRoleLadder, Role, RoleLevel},
+    user::{ExistsUserAtLevel, GetUser, User, UserUpdate},
+};
+use rand::{distributions::Alphanumeric, Rng};
+use ulid::Ulid;
+
+use crate::config::BootstrapConfig;
+
+/// Secret allowing a single call to the setup endpoint while no
+/// administrator exists.
+pub struct SetupToken(String);
+
+impl SetupToken {
+    fn generate() -> Self {
+        let token = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(48)
+            .map(char::from)
+            .collect();
+        Self(token)
+    }
+
+    /// Compares in constant time to avoid leaking the token through timing.
+    pub fn matches(&self, candidate: &str) -> bool {
+        let (expected, candidate) = (self.0.as_bytes(), candidate.as_bytes());
+        expected.len() == candidate.len()
+            && expected
+                .iter()
+                .zip(candidate)
+                .fold(0, |acc, (left, right)| acc | (left ^ right))
+                == 0
+    }
+}
+
+/// The account earlier versions created on every start with a well-known
+/// password.
+const LEGACY_USERNAME: &str = "developer";
+const LEGACY_PASSWORD: &str = "1234";
+
+pub struct Administrator<'a> {
+    pub username: &'a str,
+    pub password: &'a str,
+    pub email: Option<&'a str>,
+}
+
+/// Ensures the system can be administered. Does nothing once an
+/// administrator exists; otherwise creates one from the configured
+/// credentials or issues a setup token. The legacy account is disabled
+/// first while it still has its default password, so that it doesn't pass
+/// for the administrator.
+pub async fn run(
+    source: &PgSource,
+    events: &EventBus,
+    config: &BootstrapConfig,
+) -> gnify::error::Result<Option<SetupToken>> {
+    disable_legacy_account(source, events).await?;
+    if source
+        .read(ExistsUserAtLevel {
+            level: RoleLevel::ADMINISTRATOR,
+        })
+        .await?
+    {
+        return Ok(None);
+    }
+    if let (Some(username), Some(password)) = (&config.admin_username, &config.admin_password) {
+        let administrator = Administrator {
+            username,
+            password,
+            email: config.admin_email.as_deref(),
+        };
+        create_administrator(source, events, config, administrator).await?;
+        tracing::info!(%username, "created initial administrator");
+        return Ok(None);
+    }
+    let token = SetupToken::generate();
+    match &config.setup_token_file {
+        Some(path) => {
+            write_private(path, &token.0).map_err(|_| gnify::Error::Forbiden("Couldn't write setup token"))?;
+            tracing::warn!(path = %path.display(), "no administrator exists, setup token written to file");
+        }
+        None => tracing::warn!(token = %token.0, "no administrator exists, use this setup token"),
+    }
+    Ok(Some(token))
+}
+
+/// Replaces the legacy account's default password with a random one and
+/// takes its roles away. The account is kept for its history; an
+/// administrator may set a password and assign roles again.
+async fn disable_legacy_account(source: &PgSource, events: &EventBus) -> gnify::error::Result<()> {
+    let Some(user) = source.read(GetUser::by_username(LEGACY_USERNAME)).await? else {
+        return Ok(());
+    };
+    if !user.password().verify(LEGACY_PASSWORD) {
+        return Ok(());
+    }
+    let password = SetupToken::generate().0;
+    Repository::<_, User>::new(source, events)
+        .modify(user.id(), Actor::System, |update: &mut UserUpdate| {
+            update.set_password(&password)?;
+            update.roles.clear();
+            Ok(())
+        })
+        .await?;
+    tracing::warn!(username = LEGACY_USERNAME, "disabled the legacy account that still had its default password");
+    Ok(())
+}
+
+/// Writes `contents` to a file only its owner can read, replacing any earlier
+/// one.
+fn write_private(path: &Path, contents: &str) -> std::io::Result<()> {
+    use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+
+    let mut file = std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)?;
+    file.set_permissions(std::fs::Permissions::from_mode(0o600))?;
+    file.write_all(contents.as_bytes())
+}
+
+pub async fn create_administrator(
+    source: &PgSource,
+    events: &EventBus,
+    config: &BootstrapConfig,
+    administrator: Administrator<'_>,
+) -> gnify::error::Result<()> {
+    if source
+        .read(ExistsUserAtLevel {
+            level: RoleLevel::ADMINISTRATOR,
+        })
+        .await?
+    {
+        return error("An administrator already exists");
+    }
+    if source
+        .read(GetUser::by_username(administrator.username))
+        .await?
+        .is_some()
+    {
+        return error("Username already taken");
+    }
+    let role_id = match source.read(GetRole::by_name(&config.admin_role)).await? {
+        Some(role) if role.level() >= RoleLevel::ADMINISTRATOR => role.id().value(),
+        Some(_) => return error("Administrator role is below the administrator level"),
+        None => {
+            let ladder = source.read(GetRoleLadder).await?;
+            let level = ladder
+                .levels()
+                .last()
+                .map(|level| level.level)
+                .unwrap_or(RoleLevel::DEVELOPER);
+            let privileges = CATALOGUE.iter().map(|definition| definition.name).collect();
+            let id = Ulid::new();
+            let role = Role::new(id, &config.admin_role, level, privileges, HashSet::new(), Ulid::nil())?;
+            Repository::<_, Role>::new(source, events)
+                .save(role, Actor::System)
+                .await?;
+            id
+        }
+    };
+    let user = User::new(
+        Ulid::new(),
+        administrator.username,
+        administrator.password,
+        administrator.email,
+        HashSet::from([role_id]),
+        Ulid::nil(),
+    )?;
+    Repository::<_, User>::new(source, events)
+        .save(user, Actor::System)
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::PermissionsExt;
+
+    use super::*;
+
+    #[test]
+    fn setup_tokens_are_long_alphanumeric_and_unique() {
+        let (first, second) = (SetupToken::generate(), SetupToken::generate());
+        assert_eq!(first.0.len(), 48);
+        assert!(first.0.chars().all(|char| char.is_ascii_alphanumeric()));
+        assert_ne!(first.0, second.0);
+    }
+
+    #[test]
+    fn setup_tokens_only_match_themselves() {
+        let token = SetupToken::generate();
+        assert!(token.matches(&token.0.clone()));
+        assert!(!token.matches(&token.0[1..]));
+        let mut altered = token.0.clone();
+        let last = if altered.pop() == Some('a') { 'b' } else { 'a' };
+        altered.push(last);
+        assert!(!token.matches(&altered));
+        assert!(!token.matches(""));
+    }
+
+    #[test]
+    fn setup_token_files_are_readable_by_their_owner_only() {
+        let path = std::env::temp_dir().join(format!("gnify-setup-{}", Ulid::new()));
+        std::fs::write(&path, "previous contents that are longer").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+        write_private(&path, "token").unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        let contents = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(contents, "token");
+    }
+}
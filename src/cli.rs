@@ -0,0 +1,258 @@
+use std::{borrow::Borrow, io::BufRead, time::Duration};
+
+use clap::{Parser, Subcommand};
+use gnify::{
+    error::{error, InvalidValue},
+    event::EventBus,
+    source::{PgSource, Source},
+};
+use gnify_core::{
+    actor::Actor,
+    device::HashLegacyDeviceTokens,
+    policy::{PolicySet, Subject},
+    privilege,
+    user::GetUser,
+    Privilege,
+};
+use smol::Executor;
+
+use crate::{
+    api,
+    application::{self, PRIVILEGE_GROUPS},
+    config::Config,
+};
+
+mod corrupt;
+mod device;
+mod maintenance;
+mod outbox;
+mod output;
+mod role;
+mod user;
+mod webhook;
+
+use output::Format;
+
+static MIGRATOR: sqlx::migrate::Migrator = sqlx::migrate!("./migrations");
+
+/// Runs the server or administers the data behind it.
+#[derive(Debug, Parser)]
+#[command(version, about)]
+pub struct Cli {
+    #[arg(long, value_enum, default_value_t = Format::Table, global = true)]
+    format: Format,
+    /// The user to act as; `$USER` when absent.
+    #[arg(long = "as", global = true)]
+    operator: Option<String>,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Serves the HTTP API; the default without a command.
+    Serve,
+    /// Applies pending database migrations.
+    Migrate,
+    /// Manages webhook subscriptions or receives deliveries locally.
+    #[command(subcommand)]
+    Webhook(webhook::WebhookCommand),
+    #[command(flatten)]
+    Admin(AdminCommand),
+}
+
+/// Commands that work on the data of a migrated database.
+#[derive(Debug, Subcommand)]
+enum AdminCommand {
+    #[command(subcommand)]
+    User(user::UserCommand),
+    #[command(subcommand)]
+    Role(role::RoleCommand),
+    #[command(subcommand)]
+    Device(device::DeviceCommand),
+    #[command(subcommand)]
+    Corrupt(corrupt::CorruptCommand),
+    /// Runs the background maintenance tasks or shows their log.
+    #[command(subcommand)]
+    Maintenance(maintenance::MaintenanceCommand),
+    /// Inspects event delivery and requeues dead letters.
+    #[command(subcommand)]
+    Outbox(outbox::OutboxCommand),
+}
+
+pub async fn run<'ex>(ex: impl Borrow<Executor<'ex>> + Clone + Send + 'ex) -> Result<(), Box<dyn std::error::Error>> {
+    let cli = Cli::parse();
+    let format = cli.format;
+    match cli.command.unwrap_or(Command::Serve) {
+        Command::Serve => api::run(ex).await,
+        Command::Migrate => {
+            let config = Config::load()?;
+            let source = PgSource::connect(config.database.url(), &config.database.pool_options()).await?;
+            source.migrate(&MIGRATOR).await?;
+            println!("Migrations applied");
+            Ok(())
+        }
+        Command::Webhook(webhook::WebhookCommand::Listen { bind, secret, tolerance, fail }) => {
+            webhook::listen(ex, bind, secret, Duration::from_secs(tolerance), fail).await
+        }
+        Command::Webhook(webhook::WebhookCommand::Manage(command)) => {
+            let admin = Admin::connect().await?;
+            admin.operator_holding(cli.operator, privilege::MANAGE_WEBHOOKS).await?;
+            command.run(&admin.source, format).await
+        }
+        Command::Admin(command) => {
+            let admin = Admin::connect().await?;
+            match command {
+                AdminCommand::User(command) => command.run(&admin, admin.operator(cli.operator).await?, format).await,
+                AdminCommand::Role(command) => command.run(&admin, admin.operator(cli.operator).await?, format).await,
+                AdminCommand::Device(command) => {
+                    command.run(&admin, admin.operator(cli.operator).await?, format).await
+                }
+                AdminCommand::Corrupt(command) => {
+                    admin.operator_holding(cli.operator, privilege::OPERATE_SYSTEM).await?;
+                    command.run(&admin.source, format).await
+                }
+                AdminCommand::Maintenance(command) => {
+                    admin.operator_holding(cli.operator, privilege::OPERATE_SYSTEM).await?;
+                    command.run(&admin, format).await
+                }
+                AdminCommand::Outbox(command) => {
+                    admin.operator_holding(cli.operator, privilege::OPERATE_SYSTEM).await?;
+                    command.run(&admin.source, format).await
+                }
+            }
+        }
+    }
+}
+
+/// What the administrative commands run with.
+struct Admin {
+    config: Config,
+    source: PgSource,
+    /// Carries the events of the records the command stores.
+    events: EventBus,
+    policies: PolicySet,
+}
+
+impl Admin {
+    async fn connect() -> gnify::error::Result<Self> {
+        let config = Config::load()?;
+        privilege::validate_catalogue()?;
+        config.hashing.install()?;
+        config.tokens.install()?;
+        let source = PgSource::connect(config.database.url(), &config.database.pool_options()).await?;
+        application::sync_privileges(&source).await?;
+        source.write(HashLegacyDeviceTokens).await?;
+        let policies = config.policies.load()?;
+        Ok(Self {
+            config,
+            source,
+            events: application::event_bus(),
+            policies,
+        })
+    }
+
+    /// The user named by `--as`, or else by `$USER`, whose level and
+    /// privileges bound what the command may change.
+    async fn operator(&self, username: Option<String>) -> gnify::error::Result<Actor> {
+        let username = username
+            .or_else(|| std::env::var("USER").ok())
+            .ok_or_else(|| InvalidValue::new("Operator (pass --as or set $USER)"))?;
+        let user = self
+            .source
+            .read(GetUser::by_username(&username))
+            .await?
+            .ok_or_else(|| not_found("User", &username))?;
+        Ok(Actor::from_user(&user, &PRIVILEGE_GROUPS))
+    }
+
+    /// Like [`Admin::operator`], for commands only holders of `privilege`
+    /// may run.
+    async fn operator_holding(&self, username: Option<String>, privilege: &str) -> gnify::error::Result<Actor> {
+        permit(self.operator(username).await?, privilege)
+    }
+}
+
+fn permit(operator: Actor, privilege: &str) -> gnify::error::Result<Actor> {
+    if operator.holds(&Privilege::registered(privilege)?) {
+        Ok(operator)
+    } else {
+        error(gnify::Error::Forbiden("The operator lacks the privilege for this command"))
+    }
+}
+
+/// Takes the password from the command line or, when absent, from the first
+/// line of standard input so that it stays out of the shell history.
+fn password(value: Option<String>) -> Result<String, InvalidValue> {
+    if let Some(value) = value {
+        return Ok(value);
+    }
+    let mut line = String::new();
+    std::io::stdin()
+        .lock()
+        .read_line(&mut line)
+        .map_err(|_| InvalidValue::new("Password"))?;
+    let line = line.trim_end_matches(['\r', '\n']).to_string();
+    if line.is_empty() {
+        return Err(InvalidValue::new("Password"));
+    }
+    Ok(line)
+}
+
+fn not_found(what: &str, key: &str) -> InvalidValue {
+    InvalidValue::new(format!("{what} {key} (not found)"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use clap::CommandFactory;
+    use gnify::vo::ID;
+    use gnify_core::role::RoleLevel;
+    use ulid::Ulid;
+
+    use super::*;
+
+    fn operator(privileges: &[&str]) -> Actor {
+        Actor::User {
+            id: ID::new(Ulid::new()),
+            level: RoleLevel::ADMINISTRATOR,
+            privileges: privileges.iter().map(|privilege| privilege.parse().unwrap()).collect::<HashSet<_>>(),
+            site: None,
+        }
+    }
+
+    #[test]
+    fn the_command_line_is_consistent() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn the_operator_can_be_named_after_the_command() {
+        let cli = Cli::try_parse_from(["gnify-app", "outbox", "requeue", "7", "--as", "alice"]).unwrap();
+        assert_eq!(cli.operator.as_deref(), Some("alice"));
+        assert!(matches!(cli.command, Some(Command::Admin(AdminCommand::Outbox(_)))));
+        let cli = Cli::try_parse_from(["gnify-app", "--as", "bob", "webhook", "list"]).unwrap();
+        assert_eq!(cli.operator.as_deref(), Some("bob"));
+        assert!(matches!(cli.command, Some(Command::Webhook(webhook::WebhookCommand::Manage(_)))));
+    }
+
+    #[test]
+    fn serving_is_the_default() {
+        assert!(Cli::try_parse_from(["gnify-app"]).unwrap().command.is_none());
+    }
+
+    #[test]
+    fn operators_need_the_command_privilege() {
+        assert!(permit(operator(&[privilege::OPERATE_SYSTEM]), privilege::OPERATE_SYSTEM).is_ok());
+        assert!(permit(operator(&[privilege::MANAGE_WEBHOOKS]), privilege::OPERATE_SYSTEM).is_err());
+        assert!(permit(operator(&[]), privilege::MANAGE_WEBHOOKS).is_err());
+        assert!(permit(Actor::Device(ID::new(Ulid::new())), privilege::OPERATE_SYSTEM).is_err());
+    }
+
+    #[test]
+    fn passwords_given_on_the_command_line_are_used_as_is() {
+        assert_eq!(password(Some(String::from(" secret "))).unwrap(), " secret ");
+    }
+}
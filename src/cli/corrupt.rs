@@ -0,0 +1,45 @@
+use clap::Subcommand;
+use gnify::source::{CorruptRecord, ListCorruptRecords, PgSource, RepairCorruptRecord, Source};
+use ulid::Ulid;
+
+use super::output::{self, Format, Tabular};
+
+#[derive(Debug, Subcommand)]
+pub enum CorruptCommand {
+    /// Lists rows hidden because they failed validation.
+    List {
+        /// A table such as `core.role`.
+        #[arg(long)]
+        model: Option<String>,
+    },
+    /// Clears the mark so the rows are validated again on their next read.
+    Repair {
+        #[arg(required = true)]
+        ids: Vec<Ulid>,
+    },
+}
+
+impl CorruptCommand {
+    pub async fn run(self, source: &PgSource, format: Format) -> Result<(), Box<dyn std::error::Error>> {
+        match self {
+            CorruptCommand::List { model } => {
+                output::print(format, &source.read(ListCorruptRecords { model }).await?)?;
+            }
+            CorruptCommand::Repair { ids } => {
+                for id in ids {
+                    source.write(RepairCorruptRecord { id }).await?;
+                    println!("Repaired {id}");
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Tabular for CorruptRecord {
+    const HEADERS: &'static [&'static str] = &["ID", "MODEL", "DESCRIPTION"];
+
+    fn row(&self) -> Vec<String> {
+        vec![self.id.to_string(), self.model.clone(), self.description.clone()]
+    }
+}
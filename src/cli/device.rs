@@ -0,0 +1,200 @@
+use std::time::Duration;
+
+use clap::{Subcommand, ValueEnum};
+use gnify::{
+    repository::Repository,
+    source::{PgSource, Source},
+    vo::ID,
+};
+use gnify_core::{
+    actor::Actor,
+    device::{Device, DeviceStatus, DeviceUpdate, DeviceView, GetDevice, ListDevices},
+    policy::Effect,
+    privilege, Site,
+};
+use ulid::Ulid;
+
+use crate::device_token::{self, Rotation};
+
+use super::{
+    not_found,
+    output::{self, Format, Tabular},
+    Admin,
+};
+
+#[derive(Debug, Subcommand)]
+pub enum DeviceCommand {
+    List {
+        #[arg(long, value_enum)]
+        status: Option<Status>,
+    },
+    /// Registers a device pending approval. Its token is printed to stderr,
+    /// once; only a hash of it is kept.
+    Register {
+        name: String,
+        /// The site the device belongs to.
+        #[arg(long)]
+        site: Option<String>,
+    },
+    /// Approves a pending device or reinstates a suspended one.
+    Approve {
+        id: Ulid,
+    },
+    Reject {
+        id: Ulid,
+    },
+    Suspend {
+        id: Ulid,
+    },
+    Revoke {
+        id: Ulid,
+    },
+    /// Issues a device a new token and prints it; the previous one keeps
+    /// working for the overlap.
+    RotateToken {
+        id: Ulid,
+        /// Seconds; the configured overlap when absent, zero for none.
+        #[arg(long)]
+        overlap: Option<u64>,
+    },
+    /// Rotates the tokens of every device with the status, or of every device
+    /// that can still authenticate, and prints the new tokens.
+    RotateTokens {
+        #[arg(long, value_enum)]
+        status: Option<Status>,
+        /// Seconds; the configured overlap when absent, zero for none.
+        #[arg(long)]
+        overlap: Option<u64>,
+    },
+    /// Invalidates a device's tokens at once and ends its sessions.
+    RevokeToken {
+        id: Ulid,
+    },
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum Status {
+    Pending,
+    Approved,
+    Rejected,
+    Suspended,
+    Revoked,
+}
+
+impl From<Status> for DeviceStatus {
+    fn from(value: Status) -> Self {
+        match value {
+            Status::Pending => DeviceStatus::Pending,
+            Status::Approved => DeviceStatus::Approved,
+            Status::Rejected => DeviceStatus::Rejected,
+            Status::Suspended => DeviceStatus::Suspended,
+            Status::Revoked => DeviceStatus::Revoked,
+        }
+    }
+}
+
+impl DeviceCommand {
+    pub async fn run(self, admin: &Admin, actor: Actor, format: Format) -> Result<(), Box<dyn std::error::Error>> {
+        let source = &admin.source;
+        let events = &admin.events;
+        let devices = Repository::<_, Device>::new(source, events);
+        let overlap =
+            |seconds: Option<u64>| seconds.map_or(admin.config.tokens.rotation_overlap, Duration::from_secs);
+        let (id, status) = match self {
+            DeviceCommand::List { status } => {
+                let mut devices = source.read(ListDevices { status: status.map(Into::into) }).await?;
+                devices.retain(|device| {
+                    admin.policies.authorize(&actor, privilege::MANAGE_DEVICES, device) == Effect::Allow
+                });
+                output::print(format, &devices)?;
+                return Ok(());
+            }
+            DeviceCommand::Register { name, site } => {
+                let site = site.as_deref().map(str::parse::<Site>).transpose()?;
+                let (mut record, token) = Device::register(&name, actor.id())?;
+                record.update(actor.id(), |update: &mut DeviceUpdate| {
+                    update.site = site;
+                    Ok(())
+                })?;
+                admin.policies.ensure(&actor, privilege::MANAGE_DEVICES, &record)?;
+                let id = record.id().value();
+                devices.save(record, actor).await?;
+                output::print(format, &[get(source, id).await?])?;
+                eprintln!("Device token (not shown again): {token}");
+                return Ok(());
+            }
+            DeviceCommand::Approve { id } => (id, DeviceStatus::Approved),
+            DeviceCommand::Reject { id } => (id, DeviceStatus::Rejected),
+            DeviceCommand::Suspend { id } => (id, DeviceStatus::Suspended),
+            DeviceCommand::Revoke { id } => (id, DeviceStatus::Revoked),
+            DeviceCommand::RotateToken { id, overlap: seconds } => {
+                managed(admin, &actor, id).await?;
+                let rotation = device_token::rotate(source, events, actor, ID::new(id), overlap(seconds)).await?;
+                output::print(format, &[rotation])?;
+                return Ok(());
+            }
+            DeviceCommand::RotateTokens { status, overlap: seconds } => {
+                let status = status.map(Into::into);
+                let outcome =
+                    device_token::rotate_all(source, events, &admin.policies, actor, status, overlap(seconds)).await?;
+                output::print(format, &outcome.rotated)?;
+                for failure in &outcome.failed {
+                    eprintln!("Device {} not rotated: {}", failure.id.value(), failure.error);
+                }
+                return Ok(());
+            }
+            DeviceCommand::RevokeToken { id } => {
+                managed(admin, &actor, id).await?;
+                device_token::revoke(source, events, actor, ID::new(id)).await?;
+                output::print(format, &[get(source, id).await?])?;
+                return Ok(());
+            }
+        };
+        managed(admin, &actor, id).await?;
+        devices
+            .modify(ID::new(id), actor, |update: &mut DeviceUpdate| Ok(update.set_status(status)?))
+            .await?;
+        output::print(format, &[get(source, id).await?])?;
+        Ok(())
+    }
+}
+
+async fn get(source: &PgSource, id: Ulid) -> gnify::error::Result<DeviceView> {
+    Ok(source
+        .read(GetDevice::by_id(id))
+        .await?
+        .ok_or_else(|| not_found("Device", &id.to_string()))?)
+}
+
+/// Reads the device and checks the policies let the operator manage it.
+async fn managed(admin: &Admin, actor: &Actor, id: Ulid) -> gnify::error::Result<DeviceView> {
+    let device = get(&admin.source, id).await?;
+    admin.policies.ensure(actor, privilege::MANAGE_DEVICES, &device)?;
+    Ok(device)
+}
+
+impl Tabular for DeviceView {
+    const HEADERS: &'static [&'static str] = &["ID", "NAME", "STATUS", "SITE", "LOGGED IN"];
+
+    fn row(&self) -> Vec<String> {
+        vec![
+            self.id().value().to_string(),
+            self.name().to_string(),
+            self.status().as_str().to_string(),
+            self.site().map(ToString::to_string).unwrap_or_default(),
+            output::list(self.sessions().iter().map(|session| session.user_id.to_string())),
+        ]
+    }
+}
+
+impl Tabular for Rotation {
+    const HEADERS: &'static [&'static str] = &["ID", "TOKEN", "PREVIOUS VALID UNTIL"];
+
+    fn row(&self) -> Vec<String> {
+        vec![
+            self.id.value().to_string(),
+            self.token.to_string(),
+            self.retired_until.map(|until| until.to_string()).unwrap_or_default(),
+        ]
+    }
+}
@@ -0,0 +1,49 @@
+use clap::Subcommand;
+use gnify::source::{ListMaintenanceRuns, MaintenanceRun, Source};
+
+use super::{
+    output::{self, Format, Tabular},
+    Admin,
+};
+use crate::maintenance;
+
+#[derive(Debug, Subcommand)]
+pub enum MaintenanceCommand {
+    /// Runs every maintenance task once, as the server does on its interval.
+    Run,
+    /// Shows what recent runs did, newest first.
+    Log {
+        #[arg(long)]
+        task: Option<String>,
+        #[arg(long, default_value_t = 50)]
+        limit: i64,
+    },
+}
+
+impl MaintenanceCommand {
+    pub async fn run(self, admin: &Admin, format: Format) -> Result<(), Box<dyn std::error::Error>> {
+        let source = &admin.source;
+        let runs = match self {
+            MaintenanceCommand::Run => {
+                maintenance::sweep(source, &admin.events, &admin.config.maintenance).await;
+                ListMaintenanceRuns { task: None, limit: 3 }
+            }
+            MaintenanceCommand::Log { task, limit } => ListMaintenanceRuns { task, limit },
+        };
+        output::print(format, &source.read(runs).await?)?;
+        Ok(())
+    }
+}
+
+impl Tabular for MaintenanceRun {
+    const HEADERS: &'static [&'static str] = &["ID", "TASK", "AFFECTED", "AT"];
+
+    fn row(&self) -> Vec<String> {
+        vec![
+            self.id.to_string(),
+            self.task.clone(),
+            self.affected.to_string(),
+            self.ran_at.to_string(),
+        ]
+    }
+}
@@ -0,0 +1,71 @@
+use clap::{Subcommand, ValueEnum};
+use gnify::source::{ListOutbox, OutboxEntry, OutboxStatus, PgSource, RequeueOutbox, Source};
+
+use super::output::{self, Format, Tabular};
+
+#[derive(Debug, Subcommand)]
+pub enum OutboxCommand {
+    /// Lists the most recent entries, newest first.
+    List {
+        #[arg(long, value_enum)]
+        status: Option<Status>,
+        #[arg(long, default_value_t = 50)]
+        limit: i64,
+    },
+    /// Puts dead letters back in the queue.
+    Requeue {
+        #[arg(required = true)]
+        ids: Vec<i64>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum Status {
+    Pending,
+    Delivered,
+    Dead,
+}
+
+impl From<Status> for OutboxStatus {
+    fn from(status: Status) -> Self {
+        match status {
+            Status::Pending => OutboxStatus::Pending,
+            Status::Delivered => OutboxStatus::Delivered,
+            Status::Dead => OutboxStatus::Dead,
+        }
+    }
+}
+
+impl OutboxCommand {
+    pub async fn run(self, source: &PgSource, format: Format) -> Result<(), Box<dyn std::error::Error>> {
+        match self {
+            OutboxCommand::List { status, limit } => {
+                let status = status.map(OutboxStatus::from);
+                output::print(format, &source.read(ListOutbox { status, limit }).await?)?;
+            }
+            OutboxCommand::Requeue { ids } => {
+                for id in ids {
+                    source.write(RequeueOutbox { id }).await?;
+                    println!("Requeued {id}");
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Tabular for OutboxEntry {
+    const HEADERS: &'static [&'static str] = &["ID", "EVENT", "AGGREGATE", "VERSION", "STATUS", "ATTEMPTS", "ERROR"];
+
+    fn row(&self) -> Vec<String> {
+        vec![
+            self.id.to_string(),
+            self.event.clone(),
+            format!("{} {}", self.aggregate_type, self.aggregate_id),
+            self.version.timestamp().to_string(),
+            format!("{:?}", self.status),
+            self.attempts.to_string(),
+            self.last_error.clone().unwrap_or_default(),
+        ]
+    }
+}
@@ -0,0 +1,105 @@
+use std::collections::HashSet;
+
+use clap::Subcommand;
+use gnify::{
+    repository::Repository,
+    source::{PgSource, Source},
+};
+use gnify_core::{
+    actor::Actor,
+    privilege,
+    role::{DetailedRoleView, GetRole, GetRoleLadder, ListRoles, Role, RoleUpdate},
+    Privilege,
+};
+use ulid::Ulid;
+
+use super::{
+    not_found,
+    Admin,
+    output::{self, Format, Tabular},
+};
+
+#[derive(Debug, Subcommand)]
+pub enum RoleCommand {
+    /// Creates a role at a named level of the ladder.
+    Create {
+        name: String,
+        #[arg(long)]
+        level: String,
+        #[arg(long = "privilege")]
+        privileges: Vec<String>,
+        #[arg(long = "parent")]
+        parents: Vec<String>,
+    },
+    List,
+    /// Adds privileges to a role.
+    Grant {
+        name: String,
+        #[arg(required = true)]
+        privileges: Vec<String>,
+    },
+}
+
+impl RoleCommand {
+    pub async fn run(self, admin: &Admin, actor: Actor, format: Format) -> Result<(), Box<dyn std::error::Error>> {
+        let source = &admin.source;
+        let roles = Repository::<_, Role>::new(source, &admin.events);
+        let name = match self {
+            RoleCommand::Create { name, level, privileges, parents } => {
+                let level = source.read(GetRoleLadder).await?.level(&level)?;
+                let mut ids = HashSet::new();
+                for parent in &parents {
+                    ids.insert(get(source, parent).await?.id().value());
+                }
+                let privileges = privileges.iter().map(String::as_str).collect();
+                let record = Role::new(Ulid::new(), &name, level, privileges, ids, actor.id())?;
+                admin.policies.ensure(&actor, privilege::REGISTER_ROLE, &record)?;
+                roles.save(record, actor).await?;
+                name
+            }
+            RoleCommand::List => {
+                output::print(format, &source.read(ListRoles).await?)?;
+                return Ok(());
+            }
+            RoleCommand::Grant { name, privileges } => {
+                let privileges = privileges
+                    .iter()
+                    .map(|privilege| Privilege::registered(privilege))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let role = get(source, &name).await?;
+                admin.policies.ensure(&actor, privilege::MANAGE_ROLES, &role)?;
+                let id = role.id();
+                roles
+                    .modify(id, actor, |update: &mut RoleUpdate| {
+                        update.privileges.extend(privileges.iter().cloned());
+                        Ok(())
+                    })
+                    .await?;
+                name
+            }
+        };
+        output::print(format, &[get(source, &name).await?])?;
+        Ok(())
+    }
+}
+
+async fn get(source: &PgSource, name: &str) -> gnify::error::Result<DetailedRoleView> {
+    Ok(source
+        .read(GetRole::by_name(name))
+        .await?
+        .ok_or_else(|| not_found("Role", name))?)
+}
+
+impl Tabular for DetailedRoleView {
+    const HEADERS: &'static [&'static str] = &["ID", "NAME", "LEVEL", "PRIVILEGES", "INHERITED"];
+
+    fn row(&self) -> Vec<String> {
+        vec![
+            self.id().value().to_string(),
+            self.name().to_string(),
+            self.level().rank().to_string(),
+            output::list(self.privileges()),
+            output::list(self.inherited_privileges()),
+        ]
+    }
+}
@@ -0,0 +1,180 @@
+use std::collections::HashSet;
+
+use chrono::NaiveDateTime;
+use clap::Subcommand;
+use gnify::{
+    error::{InvalidValue, Result},
+    repository::Repository,
+    source::{PgSource, Source},
+    vo::ID,
+};
+use gnify_core::{
+    actor::Actor,
+    role::{GetRole, Role},
+    user::{DetailedUserView, GetUser, User, UserUpdate, Validity},
+    Privilege, Site,
+};
+use ulid::Ulid;
+
+use super::{
+    not_found,
+    output::{self, Format, Tabular},
+    password, Admin,
+};
+
+#[derive(Debug, Subcommand)]
+pub enum UserCommand {
+    /// Creates a user; the password is read from stdin unless given.
+    Create {
+        username: String,
+        #[arg(long)]
+        password: Option<String>,
+        #[arg(long)]
+        email: Option<String>,
+        /// The site the user belongs to.
+        #[arg(long)]
+        site: Option<String>,
+        #[arg(long = "role")]
+        roles: Vec<String>,
+    },
+    Get {
+        username: String,
+    },
+    /// Replaces the password; it is read from stdin unless given.
+    SetPassword {
+        username: String,
+        #[arg(long)]
+        password: Option<String>,
+    },
+    /// Grants privileges or assigns roles, optionally for a limited period.
+    Grant {
+        username: String,
+        #[arg(long = "privilege")]
+        privileges: Vec<String>,
+        #[arg(long = "role")]
+        roles: Vec<String>,
+        #[arg(long)]
+        from: Option<NaiveDateTime>,
+        #[arg(long)]
+        until: Option<NaiveDateTime>,
+    },
+    /// Takes back privileges or roles.
+    Revoke {
+        username: String,
+        #[arg(long = "privilege")]
+        privileges: Vec<String>,
+        #[arg(long = "role")]
+        roles: Vec<String>,
+    },
+}
+
+impl UserCommand {
+    pub async fn run(self, admin: &Admin, actor: Actor, format: Format) -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let source = &admin.source;
+        let users = Repository::<_, User>::new(source, &admin.events);
+        let username = match self {
+            UserCommand::Create { username, password: value, email, site, roles } => {
+                let password = password(value)?;
+                let site = site.as_deref().map(str::parse::<Site>).transpose()?;
+                let mut ids = HashSet::new();
+                for role in &roles {
+                    ids.insert(role_id(source, role).await?.value());
+                }
+                let mut record = User::new(Ulid::new(), &username, &password, email.as_deref(), ids, actor.id())?;
+                record.update(actor.id(), |update: &mut UserUpdate| {
+                    update.site = site;
+                    Ok(())
+                })?;
+                users.save(record, actor).await?;
+                username
+            }
+            UserCommand::Get { username } => username,
+            UserCommand::SetPassword { username, password: value } => {
+                let password = password(value)?;
+                let id = get(source, &username).await?.id();
+                users
+                    .modify(id, actor, |update: &mut UserUpdate| Ok(update.set_password(&password)?))
+                    .await?;
+                username
+            }
+            UserCommand::Grant { username, privileges, roles, from, until } => {
+                let validity = Validity::new(from, until)?;
+                let privileges = registered(&privileges)?;
+                let mut ids = Vec::new();
+                for role in &roles {
+                    ids.push(role_id(source, role).await?);
+                }
+                let id = get(source, &username).await?.id();
+                users
+                    .modify(id, actor, |update: &mut UserUpdate| {
+                        for privilege in &privileges {
+                            update.privileges.insert(privilege.clone(), validity);
+                        }
+                        for id in &ids {
+                            update.roles.insert(*id, validity);
+                        }
+                        Ok(())
+                    })
+                    .await?;
+                username
+            }
+            UserCommand::Revoke { username, privileges, roles } => {
+                let privileges = registered(&privileges)?;
+                let mut ids = Vec::new();
+                for role in &roles {
+                    ids.push(role_id(source, role).await?);
+                }
+                let id = get(source, &username).await?.id();
+                users
+                    .modify(id, actor, |update: &mut UserUpdate| {
+                        for privilege in &privileges {
+                            update.privileges.remove(privilege);
+                        }
+                        for id in &ids {
+                            update.roles.remove(id);
+                        }
+                        Ok(())
+                    })
+                    .await?;
+                username
+            }
+        };
+        output::print(format, &[get(source, &username).await?])?;
+        Ok(())
+    }
+}
+
+async fn get(source: &PgSource, username: &str) -> Result<DetailedUserView> {
+    Ok(source
+        .read(GetUser::by_username(username))
+        .await?
+        .ok_or_else(|| not_found("User", username))?)
+}
+
+async fn role_id(source: &PgSource, name: &str) -> Result<ID<Role>> {
+    let role = source
+        .read(GetRole::by_name(name))
+        .await?
+        .ok_or_else(|| not_found("Role", name))?;
+    Ok(role.id())
+}
+
+fn registered(privileges: &[String]) -> std::result::Result<Vec<Privilege>, InvalidValue> {
+    privileges.iter().map(|privilege| Privilege::registered(privilege)).collect()
+}
+
+impl Tabular for DetailedUserView {
+    const HEADERS: &'static [&'static str] = &["ID", "USERNAME", "EMAIL", "SITE", "LEVEL", "ROLES", "PRIVILEGES"];
+
+    fn row(&self) -> Vec<String> {
+        vec![
+            self.id().value().to_string(),
+            self.username().to_string(),
+            self.email().map(ToString::to_string).unwrap_or_default(),
+            self.site().map(ToString::to_string).unwrap_or_default(),
+            self.level().rank().to_string(),
+            output::list(self.roles().iter().map(|role| &role.name)),
+            output::list(self.privileges().keys()),
+        ]
+    }
+}
@@ -0,0 +1,188 @@
+use std::{
+    borrow::Borrow,
+    net::{SocketAddr, TcpListener},
+    time::Duration,
+};
+
+use axum::{
+    body::Bytes,
+    http::{HeaderMap, StatusCode},
+    Router,
+};
+use chrono::Utc;
+use clap::Subcommand;
+use gnify::{
+    error::InvalidValue,
+    source::{PgSource, Source},
+};
+use gnify_core::webhook::{
+    CreateWebhook, DeleteWebhook, GetWebhook, ListWebhookDeliveries, ListWebhooks, Webhook, WebhookDelivery,
+};
+use smol::{Async, Executor};
+use ulid::Ulid;
+
+use super::{
+    not_found,
+    output::{self, Format, Tabular},
+};
+use crate::webhook::{self, DELIVERY_HEADER, EVENT_HEADER, SIGNATURE_HEADER, TIMESTAMP_HEADER};
+
+#[derive(Debug, Subcommand)]
+pub enum WebhookCommand {
+    /// Runs a local stand-in receiver that prints deliveries and checks their
+    /// signatures.
+    Listen {
+        #[arg(long, default_value = "127.0.0.1:9000")]
+        bind: SocketAddr,
+        #[arg(long)]
+        secret: Option<String>,
+        /// Seconds a signature timestamp may be off by.
+        #[arg(long, default_value_t = 300)]
+        tolerance: u64,
+        /// Answers every delivery with an error to exercise retries.
+        #[arg(long)]
+        fail: bool,
+    },
+    #[command(flatten)]
+    Manage(ManageWebhook),
+}
+
+/// The webhook commands that work on stored subscriptions.
+#[derive(Debug, Subcommand)]
+pub enum ManageWebhook {
+    /// Subscribes a URL to events; every event when no filter is given.
+    Create {
+        url: String,
+        /// An event name such as `UserCreated` or an aggregate type such as `User`.
+        #[arg(long = "event")]
+        events: Vec<String>,
+        #[arg(long)]
+        secret: Option<String>,
+    },
+    List,
+    Delete {
+        id: Ulid,
+    },
+    Deliveries {
+        id: Ulid,
+        #[arg(long, default_value_t = 50)]
+        limit: i64,
+    },
+}
+
+impl ManageWebhook {
+    pub async fn run(self, source: &PgSource, format: Format) -> Result<(), Box<dyn std::error::Error>> {
+        match self {
+            ManageWebhook::Create { url, events, secret } => {
+                if let Some(Err(error)) = secret.as_deref().map(webhook::check_secret) {
+                    return Err(InvalidValue::new(format!("Secret ({error})")).into());
+                }
+                let secret = secret.unwrap_or_else(webhook::generate_secret);
+                let webhook = Webhook {
+                    id: Ulid::new(),
+                    url,
+                    events,
+                    secret: secret.clone(),
+                    active: true,
+                    created_at: Utc::now().naive_utc(),
+                };
+                let id = webhook.id;
+                source.write(CreateWebhook { webhook }).await?;
+                let webhook = source
+                    .read(GetWebhook { id })
+                    .await?
+                    .ok_or_else(|| not_found("Webhook", &id.to_string()))?;
+                output::print(format, &[webhook])?;
+                eprintln!("Secret: {secret}");
+            }
+            ManageWebhook::List => output::print(format, &source.read(ListWebhooks).await?)?,
+            ManageWebhook::Delete { id } => {
+                source
+                    .read(GetWebhook { id })
+                    .await?
+                    .ok_or_else(|| not_found("Webhook", &id.to_string()))?;
+                source.write(DeleteWebhook { id }).await?;
+                println!("Deleted {id}");
+            }
+            ManageWebhook::Deliveries { id, limit } => {
+                let deliveries = source.read(ListWebhookDeliveries { webhook_id: id, limit }).await?;
+                output::print(format, &deliveries)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+pub async fn listen<'ex>(
+    ex: impl Borrow<Executor<'ex>> + Clone + Send + 'ex,
+    bind: SocketAddr,
+    secret: Option<String>,
+    tolerance: Duration,
+    fail: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let app = Router::new().fallback(move |headers: HeaderMap, body: Bytes| {
+        let secret = secret.clone();
+        async move {
+            let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok()).unwrap_or_default();
+            let verified = secret.as_deref().map(|secret| {
+                let timestamp = header(TIMESTAMP_HEADER).parse().unwrap_or_default();
+                webhook::verify(secret, timestamp, &body, header(SIGNATURE_HEADER), tolerance)
+            });
+            println!(
+                "{} delivery={} signature={}",
+                header(EVENT_HEADER),
+                header(DELIVERY_HEADER),
+                match verified {
+                    Some(true) => "valid",
+                    Some(false) => "INVALID",
+                    None => "unchecked",
+                }
+            );
+            println!("{}", String::from_utf8_lossy(&body));
+            if verified == Some(false) {
+                StatusCode::UNAUTHORIZED
+            } else if fail {
+                StatusCode::SERVICE_UNAVAILABLE
+            } else {
+                StatusCode::OK
+            }
+        }
+    });
+    let listener = Async::<TcpListener>::bind(bind)?;
+    println!("receiving webhooks on http://{}", listener.get_ref().local_addr()?);
+    smol_axum::serve(ex, listener, app).await?;
+    Ok(())
+}
+
+impl Tabular for Webhook {
+    const HEADERS: &'static [&'static str] = &["ID", "URL", "EVENTS", "ACTIVE"];
+
+    fn row(&self) -> Vec<String> {
+        vec![
+            self.id.to_string(),
+            self.url.clone(),
+            if self.events.is_empty() {
+                String::from("*")
+            } else {
+                output::list(&self.events)
+            },
+            self.active.to_string(),
+        ]
+    }
+}
+
+impl Tabular for WebhookDelivery {
+    const HEADERS: &'static [&'static str] = &["ID", "EVENT", "ENTRY", "STATUS", "MS", "AT", "ERROR"];
+
+    fn row(&self) -> Vec<String> {
+        vec![
+            self.id.to_string(),
+            self.event.clone(),
+            self.outbox_id.map(|id| id.to_string()).unwrap_or_default(),
+            self.status_code.map(|status| status.to_string()).unwrap_or_default(),
+            self.duration_ms.to_string(),
+            self.attempted_at.to_string(),
+            self.error.clone().unwrap_or_default(),
+        ]
+    }
+}
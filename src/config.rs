@@ -0,0 +1,870 @@
+use std::{
+    env,
+    net::{Ipv4Addr, SocketAddr},
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+
+use axum::http::{HeaderName, HeaderValue, Method};
+use axum_login::tower_sessions::cookie::SameSite;
+use gnify::{error::InvalidValue, source::PoolOptions};
+use gnify_core::{
+    device::{DeviceUsers, SecondDevice, SessionRules, TokenKey},
+    policy::{Condition, Effect, Policy, PolicySet},
+    privilege,
+    user::HashingParams,
+    Privilege,
+};
+use serde::{de::DeserializeOwned, Deserialize, Deserializer};
+use tracing_subscriber::EnvFilter;
+
+/// Server settings, layered from the built-in defaults, an optional file
+/// named by `GNIFY_CONFIG` and `GNIFY_*` environment variables, in that order.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub database: DatabaseConfig,
+    pub server: ServerConfig,
+    pub maintenance: MaintenanceConfig,
+    pub log: LogConfig,
+    pub session: SessionConfig,
+    pub device_session: DeviceSessionConfig,
+    pub tokens: TokenConfig,
+    pub hashing: HashingParams,
+    pub cors: CorsConfig,
+    pub bootstrap: BootstrapConfig,
+    pub outbox: OutboxConfig,
+    pub policies: PolicyConfig,
+}
+
+impl Config {
+    pub fn load() -> Result<Self, InvalidValue> {
+        let mut config = match var::<PathBuf>("GNIFY_CONFIG")? {
+            Some(path) => Self::from_file(&path)?,
+            None => Self::default(),
+        };
+        config.apply_env()?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, InvalidValue> {
+        read_file(path, "config file")
+    }
+
+    fn apply_env(&mut self) -> Result<(), InvalidValue> {
+        self.database.apply_env()?;
+        self.server.apply_env()?;
+        self.maintenance.apply_env()?;
+        self.log.apply_env()?;
+        self.session.apply_env()?;
+        self.device_session.apply_env()?;
+        self.tokens.apply_env()?;
+        set(&mut self.hashing.memory_kib, "GNIFY_HASHING_MEMORY_KIB")?;
+        set(&mut self.hashing.iterations, "GNIFY_HASHING_ITERATIONS")?;
+        set(&mut self.hashing.parallelism, "GNIFY_HASHING_PARALLELISM")?;
+        self.cors.apply_env()?;
+        self.bootstrap.apply_env()?;
+        self.outbox.apply_env()?;
+        self.policies.apply_env()
+    }
+
+    pub fn validate(&self) -> Result<(), InvalidValue> {
+        self.database.validate()?;
+        self.maintenance.validate()?;
+        self.log.validate()?;
+        self.session.validate()?;
+        self.device_session.rules().validate()?;
+        self.tokens.validate()?;
+        self.hashing.validate()?;
+        self.cors.validate()?;
+        self.outbox.validate()?;
+        self.policies.validate()
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    pub url: Option<String>,
+    pub max_connections: u32,
+    pub min_connections: u32,
+    #[serde(deserialize_with = "seconds")]
+    pub acquire_timeout: Duration,
+    #[serde(deserialize_with = "optional_seconds")]
+    pub idle_timeout: Option<Duration>,
+    #[serde(deserialize_with = "optional_seconds")]
+    pub max_lifetime: Option<Duration>,
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        let pool = PoolOptions::default();
+        Self {
+            url: None,
+            max_connections: pool.max_connections,
+            min_connections: pool.min_connections,
+            acquire_timeout: pool.acquire_timeout,
+            idle_timeout: pool.idle_timeout,
+            max_lifetime: pool.max_lifetime,
+        }
+    }
+}
+
+impl DatabaseConfig {
+    fn apply_env(&mut self) -> Result<(), InvalidValue> {
+        if let Some(url) = var("GNIFY_DATABASE_URL")?.or(var("DATABASE_URL")?) {
+            self.url = Some(url);
+        }
+        set(&mut self.max_connections, "GNIFY_DATABASE_MAX_CONNECTIONS")?;
+        set(&mut self.min_connections, "GNIFY_DATABASE_MIN_CONNECTIONS")?;
+        set_seconds(&mut self.acquire_timeout, "GNIFY_DATABASE_ACQUIRE_TIMEOUT")?;
+        if let Some(seconds) = var::<u64>("GNIFY_DATABASE_IDLE_TIMEOUT")? {
+            self.idle_timeout = (seconds > 0).then(|| Duration::from_secs(seconds));
+        }
+        if let Some(seconds) = var::<u64>("GNIFY_DATABASE_MAX_LIFETIME")? {
+            self.max_lifetime = (seconds > 0).then(|| Duration::from_secs(seconds));
+        }
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<(), InvalidValue> {
+        match &self.url {
+            Some(url) if url.starts_with("postgres://") || url.starts_with("postgresql://") => {}
+            Some(_) => return Err(InvalidValue::new("database.url (expected a postgres:// URL)")),
+            None => return Err(InvalidValue::new("database.url (set GNIFY_DATABASE_URL)")),
+        }
+        if self.max_connections == 0 {
+            return Err(InvalidValue::new("database.max_connections (must be at least 1)"));
+        }
+        if self.min_connections > self.max_connections {
+            return Err(InvalidValue::new("database.min_connections (exceeds max_connections)"));
+        }
+        if self.acquire_timeout.is_zero() {
+            return Err(InvalidValue::new("database.acquire_timeout (must be positive)"));
+        }
+        Ok(())
+    }
+
+    pub fn url(&self) -> &str {
+        self.url.as_deref().unwrap_or_default()
+    }
+
+    pub fn pool_options(&self) -> PoolOptions {
+        PoolOptions {
+            max_connections: self.max_connections,
+            min_connections: self.min_connections,
+            acquire_timeout: self.acquire_timeout,
+            idle_timeout: self.idle_timeout,
+            max_lifetime: self.max_lifetime,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub bind: SocketAddr,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            bind: SocketAddr::from((Ipv4Addr::LOCALHOST, 3000)),
+        }
+    }
+}
+
+impl ServerConfig {
+    fn apply_env(&mut self) -> Result<(), InvalidValue> {
+        set(&mut self.bind, "GNIFY_BIND")
+    }
+}
+
+/// The background sweep of expired sessions, grants, stale devices and
+/// delivered outbox entries.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MaintenanceConfig {
+    #[serde(deserialize_with = "seconds")]
+    pub interval: Duration,
+    /// How long a device may stay pending before it is rejected; never when
+    /// absent.
+    #[serde(deserialize_with = "optional_seconds")]
+    pub pending_device_ttl: Option<Duration>,
+    /// How long a delivered outbox entry is kept; forever when absent.
+    #[serde(deserialize_with = "optional_seconds")]
+    pub delivered_outbox_ttl: Option<Duration>,
+}
+
+impl Default for MaintenanceConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(60),
+            pending_device_ttl: Some(Duration::from_secs(7 * 24 * 60 * 60)),
+            delivered_outbox_ttl: Some(Duration::from_secs(7 * 24 * 60 * 60)),
+        }
+    }
+}
+
+impl MaintenanceConfig {
+    fn apply_env(&mut self) -> Result<(), InvalidValue> {
+        set_seconds(&mut self.interval, "GNIFY_MAINTENANCE_INTERVAL")?;
+        if let Some(seconds) = var::<u64>("GNIFY_MAINTENANCE_PENDING_DEVICE_TTL")? {
+            self.pending_device_ttl = (seconds > 0).then(|| Duration::from_secs(seconds));
+        }
+        if let Some(seconds) = var::<u64>("GNIFY_MAINTENANCE_DELIVERED_OUTBOX_TTL")? {
+            self.delivered_outbox_ttl = (seconds > 0).then(|| Duration::from_secs(seconds));
+        }
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<(), InvalidValue> {
+        if self.interval.is_zero() {
+            return Err(InvalidValue::new("maintenance.interval (must be positive)"));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+    /// A `tracing` filter directive such as `info,sqlx=warn`.
+    pub filter: String,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            filter: String::from("axum_login=debug,tower_sessions=debug,sqlx=warn,tower_http=debug"),
+        }
+    }
+}
+
+impl LogConfig {
+    fn apply_env(&mut self) -> Result<(), InvalidValue> {
+        set(&mut self.filter, "RUST_LOG")?;
+        set(&mut self.filter, "GNIFY_LOG")
+    }
+
+    fn validate(&self) -> Result<(), InvalidValue> {
+        self.env_filter().map(|_| ())
+    }
+
+    pub fn env_filter(&self) -> Result<EnvFilter, InvalidValue> {
+        EnvFilter::try_new(&self.filter).map_err(|error| InvalidValue::new(format!("log.filter ({error})")))
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SessionConfig {
+    pub cookie_name: String,
+    pub secure: bool,
+    #[serde(deserialize_with = "same_site")]
+    pub same_site: SameSite,
+    #[serde(deserialize_with = "seconds")]
+    pub max_age: Duration,
+    #[serde(deserialize_with = "seconds")]
+    pub cleanup_interval: Duration,
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        Self {
+            cookie_name: String::from("gnify.sid"),
+            secure: true,
+            same_site: SameSite::Strict,
+            max_age: Duration::from_secs(8 * 60 * 60),
+            cleanup_interval: Duration::from_secs(60),
+        }
+    }
+}
+
+impl SessionConfig {
+    fn apply_env(&mut self) -> Result<(), InvalidValue> {
+        set(&mut self.cookie_name, "GNIFY_SESSION_COOKIE")?;
+        set(&mut self.secure, "GNIFY_SESSION_SECURE")?;
+        if let Some(value) = var::<SameSiteValue>("GNIFY_SESSION_SAME_SITE")? {
+            self.same_site = value.0;
+        }
+        set_seconds(&mut self.max_age, "GNIFY_SESSION_MAX_AGE")?;
+        set_seconds(&mut self.cleanup_interval, "GNIFY_SESSION_CLEANUP_INTERVAL")
+    }
+
+    fn validate(&self) -> Result<(), InvalidValue> {
+        if self.cookie_name.is_empty() {
+            return Err(InvalidValue::new("session.cookie_name (must not be empty)"));
+        }
+        if self.same_site == SameSite::None && !self.secure {
+            return Err(InvalidValue::new("session.same_site (None requires secure cookies)"));
+        }
+        if self.max_age.is_zero() || self.cleanup_interval.is_zero() {
+            return Err(InvalidValue::new("session (max_age and cleanup_interval must be positive)"));
+        }
+        Ok(())
+    }
+}
+
+/// Cross-origin access to the API. Without allowed origins no CORS headers
+/// are sent.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CorsConfig {
+    /// Origins such as `https://admin.example.com`, or `*` for any.
+    pub allowed_origins: Vec<String>,
+    pub allowed_methods: Vec<String>,
+    pub allowed_headers: Vec<String>,
+    pub allow_credentials: bool,
+    #[serde(deserialize_with = "seconds")]
+    pub max_age: Duration,
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        Self {
+            allowed_origins: Vec::new(),
+            allowed_methods: ["GET", "POST", "PUT", "DELETE"].map(String::from).to_vec(),
+            allowed_headers: ["content-type", "authorization"].map(String::from).to_vec(),
+            allow_credentials: true,
+            max_age: Duration::from_secs(60 * 60),
+        }
+    }
+}
+
+impl CorsConfig {
+    fn apply_env(&mut self) -> Result<(), InvalidValue> {
+        set_list(&mut self.allowed_origins, "GNIFY_CORS_ORIGINS")?;
+        set_list(&mut self.allowed_methods, "GNIFY_CORS_METHODS")?;
+        set_list(&mut self.allowed_headers, "GNIFY_CORS_HEADERS")?;
+        set(&mut self.allow_credentials, "GNIFY_CORS_CREDENTIALS")?;
+        set_seconds(&mut self.max_age, "GNIFY_CORS_MAX_AGE")
+    }
+
+    fn validate(&self) -> Result<(), InvalidValue> {
+        if self.any_origin() && self.allow_credentials {
+            return Err(InvalidValue::new("cors.allowed_origins (* cannot be combined with credentials)"));
+        }
+        self.origins()?;
+        self.methods()?;
+        self.headers()?;
+        Ok(())
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.allowed_origins.is_empty()
+    }
+
+    pub fn any_origin(&self) -> bool {
+        self.allowed_origins.iter().any(|origin| origin == "*")
+    }
+
+    pub fn origins(&self) -> Result<Vec<HeaderValue>, InvalidValue> {
+        self.allowed_origins
+            .iter()
+            .filter(|origin| *origin != "*")
+            .map(|origin| match origin.split_once("://") {
+                Some(("http" | "https", host)) if !host.is_empty() && !host.contains('/') => {
+                    HeaderValue::from_str(origin).map_err(|_| InvalidValue::new(format!("cors.allowed_origins ({origin})")))
+                }
+                _ => Err(InvalidValue::new(format!("cors.allowed_origins ({origin})"))),
+            })
+            .collect()
+    }
+
+    pub fn methods(&self) -> Result<Vec<Method>, InvalidValue> {
+        self.allowed_methods
+            .iter()
+            .map(|method| {
+                Method::from_str(&method.to_uppercase())
+                    .map_err(|_| InvalidValue::new(format!("cors.allowed_methods ({method})")))
+            })
+            .collect()
+    }
+
+    pub fn headers(&self) -> Result<Vec<HeaderName>, InvalidValue> {
+        self.allowed_headers
+            .iter()
+            .map(|header| {
+                HeaderName::from_str(header).map_err(|_| InvalidValue::new(format!("cors.allowed_headers ({header})")))
+            })
+            .collect()
+    }
+}
+
+/// How the first administrator gets created. With credentials the account
+/// is created at startup; otherwise a one-time setup token is issued.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BootstrapConfig {
+    pub admin_username: Option<String>,
+    pub admin_password: Option<String>,
+    pub admin_email: Option<String>,
+    pub admin_role: String,
+    pub setup_token_file: Option<PathBuf>,
+    /// Roles, users and devices to create or update at startup.
+    pub seed_file: Option<PathBuf>,
+}
+
+impl Default for BootstrapConfig {
+    fn default() -> Self {
+        Self {
+            admin_username: None,
+            admin_password: None,
+            admin_email: None,
+            admin_role: String::from("ADMINISTRATOR"),
+            setup_token_file: None,
+            seed_file: None,
+        }
+    }
+}
+
+impl BootstrapConfig {
+    fn apply_env(&mut self) -> Result<(), InvalidValue> {
+        set_some(&mut self.admin_username, "GNIFY_ADMIN_USERNAME")?;
+        set_some(&mut self.admin_password, "GNIFY_ADMIN_PASSWORD")?;
+        set_some(&mut self.admin_email, "GNIFY_ADMIN_EMAIL")?;
+        set(&mut self.admin_role, "GNIFY_ADMIN_ROLE")?;
+        set_some(&mut self.setup_token_file, "GNIFY_SETUP_TOKEN_FILE")?;
+        set_some(&mut self.seed_file, "GNIFY_SEED_FILE")
+    }
+}
+
+/// Where the authorization policies come from. Without a file the built-in
+/// ones apply.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PolicyConfig {
+    pub file: Option<PathBuf>,
+}
+
+/// The layout of a policy file.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PolicyFile {
+    policies: Vec<Policy>,
+}
+
+impl PolicyConfig {
+    fn apply_env(&mut self) -> Result<(), InvalidValue> {
+        set_some(&mut self.file, "GNIFY_POLICY_FILE")
+    }
+
+    fn validate(&self) -> Result<(), InvalidValue> {
+        self.load().map(|_| ())
+    }
+
+    pub fn load(&self) -> Result<PolicySet, InvalidValue> {
+        let policies = match &self.file {
+            Some(path) => read_file::<PolicyFile>(path, "policy file")?.policies,
+            None => builtin_policies()?,
+        };
+        PolicySet::new(policies)
+    }
+}
+
+/// Each action is allowed to whoever holds the privilege of the same name,
+/// on every site, and users may read their own details.
+fn builtin_policies() -> Result<Vec<Policy>, InvalidValue> {
+    let mut policies = [
+        privilege::GET_USER_DETAILS,
+        privilege::REGISTER_ROLE,
+        privilege::MANAGE_ROLES,
+        privilege::MANAGE_DEVICES,
+    ]
+    .into_iter()
+    .map(|action| {
+        Ok(Policy {
+            action: String::from(action),
+            effect: Effect::Allow,
+            privilege: Some(Privilege::registered(action)?),
+            conditions: Vec::new(),
+            scopes: Vec::new(),
+        })
+    })
+    .collect::<Result<Vec<_>, InvalidValue>>()?;
+    policies.push(Policy {
+        action: String::from(privilege::GET_USER_DETAILS),
+        effect: Effect::Allow,
+        privilege: None,
+        conditions: vec![Condition::SameAttribute {
+            subject: String::from("id"),
+            resource: String::from("id"),
+        }],
+        scopes: Vec::new(),
+    });
+    Ok(policies)
+}
+
+/// Delivery of the event outbox. Besides the webhooks subscribed through the
+/// API, entries can go to one fixed URL and to a file.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OutboxConfig {
+    pub webhook_url: Option<String>,
+    pub file: Option<PathBuf>,
+    #[serde(deserialize_with = "seconds")]
+    pub poll_interval: Duration,
+    pub batch_size: i64,
+    /// How long a claimed entry is hidden from other dispatchers.
+    #[serde(deserialize_with = "seconds")]
+    pub lease: Duration,
+    pub max_attempts: u32,
+    #[serde(deserialize_with = "seconds")]
+    pub backoff_base: Duration,
+    #[serde(deserialize_with = "seconds")]
+    pub backoff_max: Duration,
+    #[serde(deserialize_with = "seconds")]
+    pub timeout: Duration,
+}
+
+impl Default for OutboxConfig {
+    fn default() -> Self {
+        Self {
+            webhook_url: None,
+            file: None,
+            poll_interval: Duration::from_secs(1),
+            batch_size: 50,
+            lease: Duration::from_secs(60),
+            max_attempts: 10,
+            backoff_base: Duration::from_secs(2),
+            backoff_max: Duration::from_secs(60 * 60),
+            timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+impl OutboxConfig {
+    fn apply_env(&mut self) -> Result<(), InvalidValue> {
+        set_some(&mut self.webhook_url, "GNIFY_OUTBOX_WEBHOOK_URL")?;
+        set_some(&mut self.file, "GNIFY_OUTBOX_FILE")?;
+        set_seconds(&mut self.poll_interval, "GNIFY_OUTBOX_POLL_INTERVAL")?;
+        set(&mut self.batch_size, "GNIFY_OUTBOX_BATCH_SIZE")?;
+        set_seconds(&mut self.lease, "GNIFY_OUTBOX_LEASE")?;
+        set(&mut self.max_attempts, "GNIFY_OUTBOX_MAX_ATTEMPTS")?;
+        set_seconds(&mut self.backoff_base, "GNIFY_OUTBOX_BACKOFF_BASE")?;
+        set_seconds(&mut self.backoff_max, "GNIFY_OUTBOX_BACKOFF_MAX")?;
+        set_seconds(&mut self.timeout, "GNIFY_OUTBOX_TIMEOUT")
+    }
+
+    fn validate(&self) -> Result<(), InvalidValue> {
+        if let Some(url) = &self.webhook_url {
+            if !(url.starts_with("http://") || url.starts_with("https://")) {
+                return Err(InvalidValue::new("outbox.webhook_url (expected an http or https URL)"));
+            }
+        }
+        if self.batch_size < 1 || self.max_attempts < 1 {
+            return Err(InvalidValue::new("outbox (batch_size and max_attempts must be at least 1)"));
+        }
+        if self.poll_interval.is_zero() || self.lease.is_zero() || self.timeout.is_zero() {
+            return Err(InvalidValue::new("outbox (poll_interval, lease and timeout must be positive)"));
+        }
+        if self.lease <= self.timeout {
+            return Err(InvalidValue::new("outbox.lease (must exceed timeout)"));
+        }
+        if self.backoff_base > self.backoff_max {
+            return Err(InvalidValue::new("outbox.backoff_base (exceeds backoff_max)"));
+        }
+        Ok(())
+    }
+}
+
+/// Sessions that users open on devices, as opposed to the cookie sessions of
+/// the API.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DeviceSessionConfig {
+    #[serde(deserialize_with = "seconds")]
+    pub idle: Duration,
+    #[serde(deserialize_with = "seconds")]
+    pub lifetime: Duration,
+    pub users: DeviceUsers,
+    pub second_device: SecondDevice,
+}
+
+impl Default for DeviceSessionConfig {
+    fn default() -> Self {
+        Self {
+            idle: Duration::from_secs(30 * 60),
+            lifetime: Duration::from_secs(12 * 60 * 60),
+            users: DeviceUsers::Single,
+            second_device: SecondDevice::Allow,
+        }
+    }
+}
+
+impl DeviceSessionConfig {
+    fn apply_env(&mut self) -> Result<(), InvalidValue> {
+        set_seconds(&mut self.idle, "GNIFY_DEVICE_SESSION_IDLE")?;
+        set_seconds(&mut self.lifetime, "GNIFY_DEVICE_SESSION_LIFETIME")?;
+        set(&mut self.users, "GNIFY_DEVICE_SESSION_USERS")?;
+        set(&mut self.second_device, "GNIFY_DEVICE_SESSION_SECOND_DEVICE")
+    }
+
+    pub fn rules(&self) -> SessionRules {
+        SessionRules {
+            idle: self.idle,
+            lifetime: self.lifetime,
+            users: self.users,
+            second_device: self.second_device,
+        }
+    }
+}
+
+/// Hashing and rotation of the device and session tokens kept in the
+/// database.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TokenConfig {
+    /// At least 32 bytes; rotating it invalidates every issued token.
+    pub key: Option<TokenKey>,
+    /// How long a device's previous token keeps working after a rotation
+    /// unless the rotation asks for another overlap.
+    #[serde(deserialize_with = "seconds")]
+    pub rotation_overlap: Duration,
+}
+
+impl Default for TokenConfig {
+    fn default() -> Self {
+        Self {
+            key: None,
+            rotation_overlap: Duration::from_secs(24 * 60 * 60),
+        }
+    }
+}
+
+impl TokenConfig {
+    fn apply_env(&mut self) -> Result<(), InvalidValue> {
+        set_some(&mut self.key, "GNIFY_TOKEN_KEY")?;
+        set_seconds(&mut self.rotation_overlap, "GNIFY_TOKEN_ROTATION_OVERLAP")
+    }
+
+    fn validate(&self) -> Result<(), InvalidValue> {
+        match self.key {
+            Some(_) => Ok(()),
+            None => Err(InvalidValue::new("tokens.key (GNIFY_TOKEN_KEY is required)")),
+        }
+    }
+
+    pub fn install(&self) -> Result<(), InvalidValue> {
+        self.validate()?;
+        if let Some(key) = &self.key {
+            key.clone().install();
+        }
+        Ok(())
+    }
+}
+
+struct SameSiteValue(SameSite);
+
+impl FromStr for SameSiteValue {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_ref() {
+            "strict" => Ok(Self(SameSite::Strict)),
+            "lax" => Ok(Self(SameSite::Lax)),
+            "none" => Ok(Self(SameSite::None)),
+            _ => Err(()),
+        }
+    }
+}
+
+fn same_site<'de, D: Deserializer<'de>>(deserializer: D) -> Result<SameSite, D::Error> {
+    let value = String::deserialize(deserializer)?;
+    value
+        .parse::<SameSiteValue>()
+        .map(|value| value.0)
+        .map_err(|_| serde::de::Error::custom(format!("unknown same_site value {value}")))
+}
+
+fn seconds<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+    u64::deserialize(deserializer).map(Duration::from_secs)
+}
+
+/// Zero or a missing value disables the timeout.
+fn optional_seconds<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Duration>, D::Error> {
+    Ok(Option::<u64>::deserialize(deserializer)?
+        .filter(|seconds| *seconds > 0)
+        .map(Duration::from_secs))
+}
+
+/// Reads a TOML, YAML or JSON file, telling the format by its extension.
+fn read_file<T: DeserializeOwned>(path: &Path, what: &str) -> Result<T, InvalidValue> {
+    let invalid = |error: &dyn std::fmt::Display| InvalidValue::new(format!("{what} {}: {error}", path.display()));
+    let content = std::fs::read_to_string(path).map_err(|error| invalid(&error))?;
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("toml") => toml::from_str(&content).map_err(|error| invalid(&error)),
+        Some("yaml" | "yml") => serde_yaml::from_str(&content).map_err(|error| invalid(&error)),
+        Some("json") => serde_json::from_str(&content).map_err(|error| invalid(&error)),
+        _ => Err(invalid(&"unsupported format")),
+    }
+}
+
+fn var<T: FromStr>(key: &'static str) -> Result<Option<T>, InvalidValue> {
+    match env::var(key) {
+        Ok(value) => value.parse().map(Some).map_err(|_| InvalidValue::new(key)),
+        Err(_) => Ok(None),
+    }
+}
+
+fn set<T: FromStr>(field: &mut T, key: &'static str) -> Result<(), InvalidValue> {
+    if let Some(value) = var(key)? {
+        *field = value;
+    }
+    Ok(())
+}
+
+fn set_some<T: FromStr>(field: &mut Option<T>, key: &'static str) -> Result<(), InvalidValue> {
+    if let Some(value) = var(key)? {
+        *field = Some(value);
+    }
+    Ok(())
+}
+
+fn set_seconds(field: &mut Duration, key: &'static str) -> Result<(), InvalidValue> {
+    if let Some(seconds) = var(key)? {
+        *field = Duration::from_secs(seconds);
+    }
+    Ok(())
+}
+
+/// Reads a comma separated list; an empty value clears it.
+fn set_list(field: &mut Vec<String>, key: &'static str) -> Result<(), InvalidValue> {
+    if let Some(value) = var::<String>(key)? {
+        *field = value
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(String::from)
+            .collect();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use ulid::Ulid;
+
+    use super::*;
+
+    fn valid() -> Config {
+        let mut config = Config::default();
+        config.database.url = Some(String::from("postgresql://localhost/gnify"));
+        config.tokens.key = Some(TokenKey::new(&"k".repeat(32)).unwrap());
+        config
+    }
+
+    fn read(extension: &str, content: &str) -> Result<Config, InvalidValue> {
+        let path = env::temp_dir().join(format!("gnify-config-{}.{extension}", Ulid::new()));
+        std::fs::write(&path, content).unwrap();
+        let config = Config::from_file(&path);
+        std::fs::remove_file(&path).unwrap();
+        config
+    }
+
+    #[test]
+    fn defaults_need_only_a_database_url_and_token_key() {
+        assert!(valid().validate().is_ok());
+        let mut config = valid();
+        config.database.url = None;
+        assert!(config.validate().is_err());
+        let mut config = valid();
+        config.tokens.key = None;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn files_override_only_the_settings_they_name() {
+        let config = read("toml", "[server]\nbind = \"0.0.0.0:8080\"\n\n[outbox]\nbatch_size = 5\nlease = 120\n").unwrap();
+        assert_eq!(config.server.bind, SocketAddr::from(([0, 0, 0, 0], 8080)));
+        assert_eq!(config.outbox.batch_size, 5);
+        assert_eq!(config.outbox.lease, Duration::from_secs(120));
+        assert_eq!(config.outbox.max_attempts, OutboxConfig::default().max_attempts);
+        let config = read("yaml", "database:\n  max_connections: 3\n  idle_timeout: 0\n").unwrap();
+        assert_eq!(config.database.max_connections, 3);
+        assert_eq!(config.database.idle_timeout, None);
+        let config = read("json", r#"{"session": {"same_site": "lax"}}"#).unwrap();
+        assert_eq!(config.session.same_site, SameSite::Lax);
+    }
+
+    #[test]
+    fn files_with_unknown_settings_are_rejected() {
+        assert!(read("toml", "[server]\nport = 8080\n").is_err());
+        assert!(read("toml", "[servers]\n").is_err());
+        assert!(read("ini", "").is_err());
+    }
+
+    #[test]
+    fn inconsistent_settings_are_rejected() {
+        let mut config = valid();
+        config.database.url = Some(String::from("mysql://localhost/gnify"));
+        assert!(config.validate().is_err());
+        let mut config = valid();
+        config.database.min_connections = config.database.max_connections + 1;
+        assert!(config.validate().is_err());
+        let mut config = valid();
+        config.session.same_site = SameSite::None;
+        config.session.secure = false;
+        assert!(config.validate().is_err());
+        let mut config = valid();
+        config.cors.allowed_origins = vec![String::from("*")];
+        assert!(config.validate().is_err());
+        config.cors.allow_credentials = false;
+        assert!(config.validate().is_ok());
+        let mut config = valid();
+        config.outbox.lease = config.outbox.timeout;
+        assert!(config.validate().is_err());
+        let mut config = valid();
+        config.log.filter = String::from("[");
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn maintenance_tasks_with_a_zero_ttl_are_disabled() {
+        let config = read("toml", "[maintenance]\ninterval = 30\npending_device_ttl = 0\n").unwrap();
+        assert_eq!(config.maintenance.interval, Duration::from_secs(30));
+        assert_eq!(config.maintenance.pending_device_ttl, None);
+        assert_eq!(config.maintenance.delivered_outbox_ttl, MaintenanceConfig::default().delivered_outbox_ttl);
+        let mut config = valid();
+        config.maintenance.interval = Duration::ZERO;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn same_site_values_ignore_case() {
+        assert_eq!(" Strict ".parse::<SameSiteValue>().unwrap().0, SameSite::Strict);
+        assert_eq!("NONE".parse::<SameSiteValue>().unwrap().0, SameSite::None);
+        assert!("sometimes".parse::<SameSiteValue>().is_err());
+    }
+
+    #[test]
+    fn environment_lists_are_comma_separated() {
+        let mut list = vec![String::from("kept")];
+        set_list(&mut list, "GNIFY_TEST_UNSET_LIST").unwrap();
+        assert_eq!(list, ["kept"]);
+        env::set_var("GNIFY_TEST_LIST", " https://a.example, ,https://b.example ");
+        set_list(&mut list, "GNIFY_TEST_LIST").unwrap();
+        assert_eq!(list, ["https://a.example", "https://b.example"]);
+        env::set_var("GNIFY_TEST_LIST", "");
+        set_list(&mut list, "GNIFY_TEST_LIST").unwrap();
+        assert!(list.is_empty());
+    }
+
+    #[test]
+    fn unparsable_environment_values_name_the_variable() {
+        env::set_var("GNIFY_TEST_NUMBER", "many");
+        let mut value = 1_u32;
+        let error = set(&mut value, "GNIFY_TEST_NUMBER").unwrap_err();
+        assert!(format!("{error:?}").contains("GNIFY_TEST_NUMBER"));
+        assert_eq!(value, 1);
+    }
+
+    #[test]
+    fn built_in_policies_are_valid() {
+        assert!(PolicyConfig::default().load().is_ok());
+        assert!(PolicyConfig { file: Some(PathBuf::from("policies.ini")) }.load().is_err());
+    }
+}
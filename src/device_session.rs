@@ -0,0 +1,150 @@
+use gnify::{
+    error::{InvalidValue, PersistenceError},
+    event::EventBus,
+    repository::Repository,
+    source::{PgSource, Source},
+    vo::ID,
+};
+use gnify_core::{
+    actor::Actor,
+    device::{
+        Device, DeviceStatus, DeviceToken, DeviceUpdate, ExpirationTimestamp, GetDevice, ListDevices, SecondDevice,
+        SessionRules, SessionToken,
+    },
+    user::{GetUser, User},
+};
+
+/// Why a device session operation was refused.
+#[derive(Debug)]
+pub enum SessionError {
+    InvalidCredentials,
+    UnknownDevice,
+    /// The device isn't approved, the session is gone or the rules forbid
+    /// the login.
+    Refused(String),
+    Failed(gnify::Error),
+}
+
+impl From<gnify::Error> for SessionError {
+    fn from(error: gnify::Error) -> Self {
+        SessionError::Failed(error)
+    }
+}
+
+impl From<PersistenceError> for SessionError {
+    fn from(error: PersistenceError) -> Self {
+        SessionError::Failed(error.into())
+    }
+}
+
+pub struct DeviceLogin {
+    pub session: SessionToken,
+    pub expiration: ExpirationTimestamp,
+}
+
+/// Logs a user in on an approved device, applying `rules` to the user's
+/// sessions on other devices. Those are only ended once the new session
+/// started.
+pub async fn login(
+    source: &PgSource,
+    events: &EventBus,
+    rules: &SessionRules,
+    device: DeviceToken,
+    username: &str,
+    password: &str,
+) -> Result<DeviceLogin, SessionError> {
+    let user = source
+        .read(GetUser::by_username(username))
+        .await?
+        .filter(|user| user.password().verify(password))
+        .ok_or(SessionError::InvalidCredentials)?;
+    let user_id: ID<User> = user.id();
+    let device = device_id(source, &device).await?;
+    let devices = Repository::<_, Device>::new(source, events);
+    let elsewhere: Vec<ID<Device>> = source
+        .read(ListDevices {
+            status: Some(DeviceStatus::Approved),
+        })
+        .await?
+        .into_iter()
+        .filter(|other| {
+            other.id() != device && other.sessions().iter().any(|session| session.user_id == user_id)
+        })
+        .map(|other| other.id())
+        .collect();
+    if rules.second_device == SecondDevice::Reject && !elsewhere.is_empty() {
+        return Err(SessionError::Refused(String::from("logged in on another device")));
+    }
+    let (_, login) = devices
+        .modify(device, Actor::System, |update: &mut DeviceUpdate| {
+            let token = update.login(user_id, rules)?;
+            let hash = token.hash()?;
+            let expiration = update
+                .sessions()
+                .iter()
+                .find(|session| session.token_hash == hash)
+                .map(|session| session.expiration)
+                .ok_or_else(|| InvalidValue::new("Session (not started)"))?;
+            Ok(DeviceLogin { session: token, expiration })
+        })
+        .await
+        .map_err(refused)?;
+    if rules.second_device == SecondDevice::EndOthers {
+        for other in elsewhere {
+            devices
+                .modify(other, Actor::System, |update: &mut DeviceUpdate| Ok(update.end_sessions_of(&user_id)))
+                .await?;
+        }
+    }
+    Ok(login)
+}
+
+/// Extends a session by another idle period, within its lifetime.
+pub async fn refresh(
+    source: &PgSource,
+    events: &EventBus,
+    rules: &SessionRules,
+    device: DeviceToken,
+    session: &SessionToken,
+) -> Result<ExpirationTimestamp, SessionError> {
+    let device = device_id(source, &device).await?;
+    let (_, expiration) = Repository::<_, Device>::new(source, events)
+        .modify(device, Actor::System, |update: &mut DeviceUpdate| Ok(update.refresh(session, rules)?))
+        .await
+        .map_err(refused)?;
+    Ok(expiration)
+}
+
+pub async fn logout(
+    source: &PgSource,
+    events: &EventBus,
+    device: DeviceToken,
+    session: &SessionToken,
+) -> Result<(), SessionError> {
+    let device = device_id(source, &device).await?;
+    let (_, ended) = Repository::<_, Device>::new(source, events)
+        .modify(device, Actor::System, |update: &mut DeviceUpdate| Ok(update.logout(session)?))
+        .await
+        .map_err(refused)?;
+    if ended {
+        Ok(())
+    } else {
+        Err(SessionError::Refused(String::from("no such session")))
+    }
+}
+
+/// Resolves the device presenting `token` through the token's hash.
+async fn device_id(source: &PgSource, token: &DeviceToken) -> Result<ID<Device>, SessionError> {
+    source
+        .read(GetDevice::by_token(token).map_err(gnify::Error::from)?)
+        .await?
+        .map(|device| device.id())
+        .ok_or(SessionError::UnknownDevice)
+}
+
+fn refused(error: gnify::Error) -> SessionError {
+    match error {
+        gnify::Error::InvalidValue(error) => SessionError::Refused(error.to_string()),
+        error => SessionError::Failed(error),
+    }
+}
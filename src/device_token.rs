@@ -0,0 +1,108 @@
+use std::time::Duration;
+
+use gnify::{
+    event::EventBus,
+    repository::Repository,
+    source::{PgSource, Source},
+    vo::ID,
+};
+use gnify_core::{
+    actor::Actor,
+    device::{Device, DeviceStatus, DeviceToken, DeviceUpdate, ExpirationTimestamp, ListDevices},
+    policy::{Effect, PolicySet},
+    privilege,
+};
+use serde::Serialize;
+
+/// A device's new token, shown this once.
+#[derive(Debug, Serialize)]
+pub struct Rotation {
+    pub id: ID<Device>,
+    pub token: DeviceToken,
+    /// Until when the previous token keeps working; absent when it stopped
+    /// at once.
+    pub retired_until: Option<ExpirationTimestamp>,
+}
+
+/// Outcome of [`rotate_all`]. Rotations that went through are kept even
+/// when others fail, since their tokens can't be recovered afterwards.
+#[derive(Debug, Default, Serialize)]
+pub struct BulkRotation {
+    pub rotated: Vec<Rotation>,
+    pub failed: Vec<RotationFailure>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RotationFailure {
+    pub id: ID<Device>,
+    pub error: String,
+}
+
+/// Issues `id` a new token; the current one keeps working for `overlap`. A
+/// zero overlap also ends the device's sessions.
+pub async fn rotate(
+    source: &PgSource,
+    events: &EventBus,
+    actor: Actor,
+    id: ID<Device>,
+    overlap: Duration,
+) -> gnify::error::Result<Rotation> {
+    let (_, (token, retired_until)) = Repository::<_, Device>::new(source, events)
+        .modify(id, actor, |update: &mut DeviceUpdate| {
+            let token = update.rotate_token(overlap)?;
+            Ok((token, update.retired_token().map(|retired| retired.until)))
+        })
+        .await?;
+    Ok(Rotation { id, token, retired_until })
+}
+
+/// Rotates the token of every device with `status`, or of every device that
+/// can still authenticate when no status is given, among those `policies`
+/// let `actor` manage. Meant for a suspected leak, where a zero `overlap`
+/// cuts off the old tokens at once.
+pub async fn rotate_all(
+    source: &PgSource,
+    events: &EventBus,
+    policies: &PolicySet,
+    actor: Actor,
+    status: Option<DeviceStatus>,
+    overlap: Duration,
+) -> gnify::error::Result<BulkRotation> {
+    let devices = source.read(ListDevices { status }).await?;
+    let mut outcome = BulkRotation::default();
+    for device in devices {
+        if status.is_none() && matches!(device.status(), DeviceStatus::Rejected | DeviceStatus::Revoked) {
+            continue;
+        }
+        if policies.authorize(&actor, privilege::MANAGE_DEVICES, &device) == Effect::Deny {
+            continue;
+        }
+        match rotate(source, events, actor.clone(), device.id(), overlap).await {
+            Ok(rotation) => outcome.rotated.push(rotation),
+            Err(error) => {
+                tracing::warn!(device = %device.id().value(), %error, "couldn't rotate device token");
+                outcome.failed.push(RotationFailure {
+                    id: device.id(),
+                    error: error.to_string(),
+                });
+            }
+        }
+    }
+    tracing::info!(
+        rotated = outcome.rotated.len(),
+        failed = outcome.failed.len(),
+        overlap = overlap.as_secs(),
+        "rotated device tokens"
+    );
+    Ok(outcome)
+}
+
+/// Invalidates the device's tokens and ends its sessions right away.
+pub async fn revoke(source: &PgSource, events: &EventBus, actor: Actor, id: ID<Device>) -> gnify::error::Result<()> {
+    Repository::<_, Device>::new(source, events)
+        .modify(id, actor, |update: &mut DeviceUpdate| {
+            Ok(update.revoke_token()?)
+        })
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,330 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use gnify::{
+    event::Change,
+    source::{ChangesSince, PgSource, Source},
+    Model,
+};
+use gnify_core::{
+    device::Device,
+    privilege,
+    role::Role,
+    user::{GetUser, User},
+};
+use smol::{
+    channel::{self, Receiver, Sender, TrySendError},
+    Timer,
+};
+
+use crate::application::AuthProfile;
+
+/// Changes a subscriber may fall behind by before it is dropped.
+const BUFFER: usize = 256;
+
+/// Wait before listening again after the connection was lost.
+const RETRY: Duration = Duration::from_secs(5);
+
+/// Changes read at once when replaying those a subscriber missed.
+const REPLAY_PAGE: i64 = 256;
+
+/// Age after which a subscriber's privileges are resolved again.
+const REFRESH: Duration = Duration::from_secs(60);
+
+/// Fans the changes committed by any app instance out to the subscribers of
+/// this one.
+#[derive(Debug, Clone, Default)]
+pub struct ChangeFeed {
+    subscribers: Arc<Mutex<Vec<Sender<Arc<Change>>>>>,
+}
+
+impl ChangeFeed {
+    pub fn subscribe(&self) -> Receiver<Arc<Change>> {
+        let (sender, receiver) = channel::bounded(BUFFER);
+        self.subscribers.lock().expect("change feed poisoned").push(sender);
+        receiver
+    }
+
+    fn broadcast(&self, change: Change) {
+        let change = Arc::new(change);
+        self.subscribers
+            .lock()
+            .expect("change feed poisoned")
+            .retain(|subscriber| match subscriber.try_send(change.clone()) {
+                Ok(()) => true,
+                Err(TrySendError::Full(_)) => {
+                    tracing::warn!("dropped a change feed subscriber that fell behind");
+                    false
+                }
+                Err(TrySendError::Closed(_)) => false,
+            });
+    }
+
+    /// Listens for changes and broadcasts them, reconnecting after errors.
+    pub async fn run(self, source: Arc<PgSource>) {
+        loop {
+            let mut listener = match source.changes().await {
+                Ok(listener) => listener,
+                Err(error) => {
+                    tracing::warn!(%error, "couldn't listen for changes");
+                    Timer::after(RETRY).await;
+                    continue;
+                }
+            };
+            loop {
+                match listener.next().await {
+                    Ok(change) => self.broadcast(change),
+                    Err(error) => {
+                        tracing::warn!(%error, "lost the change listener");
+                        break;
+                    }
+                }
+            }
+            Timer::after(RETRY).await;
+        }
+    }
+}
+
+/// A client following the feed: replays the changes stored after the last
+/// one it saw, then passes on live ones it may see, resolving its privileges
+/// again when they may have changed.
+pub struct Subscription {
+    source: Arc<PgSource>,
+    profile: AuthProfile,
+    resolved: Instant,
+    live: Receiver<Arc<Change>>,
+    replay: VecDeque<Arc<Change>>,
+    /// The last change replayed; live ones up to it were already sent.
+    replayed: Option<i64>,
+    caught_up: bool,
+}
+
+impl Subscription {
+    /// Subscribes `profile`, resuming after the change `last_seen` if given.
+    pub fn new(feed: &ChangeFeed, source: Arc<PgSource>, profile: AuthProfile, last_seen: Option<i64>) -> Self {
+        Self {
+            source,
+            profile,
+            resolved: Instant::now(),
+            live: feed.subscribe(),
+            replay: VecDeque::new(),
+            replayed: last_seen,
+            caught_up: last_seen.is_none(),
+        }
+    }
+
+    /// The next change the subscriber may see, or `None` once the feed
+    /// dropped it or its user is gone or logged in anew.
+    pub async fn next(&mut self) -> Option<Arc<Change>> {
+        loop {
+            let change = match self.next_replayed().await {
+                Ok(Some(change)) => change,
+                Ok(None) => {
+                    let change = self.live.recv().await.ok()?;
+                    if self.replayed.is_some_and(|replayed| change.id <= replayed) {
+                        continue;
+                    }
+                    change
+                }
+                Err(error) => {
+                    tracing::warn!(%error, "couldn't replay missed changes");
+                    return None;
+                }
+            };
+            if self.resolved.elapsed() >= REFRESH || self.may_change_profile(&change) {
+                match self.refresh().await {
+                    Ok(true) => {}
+                    Ok(false) => return None,
+                    Err(error) => {
+                        tracing::warn!(%error, "couldn't resolve a change feed subscriber again");
+                        return None;
+                    }
+                }
+            }
+            if visible(&self.profile, &change) {
+                return Some(change);
+            }
+        }
+    }
+
+    async fn next_replayed(&mut self) -> gnify::error::Result<Option<Arc<Change>>> {
+        if self.replay.is_empty() && !self.caught_up {
+            let after = self.replayed.unwrap_or_default();
+            let page = self.source.read(ChangesSince { after, limit: REPLAY_PAGE }).await?;
+            self.caught_up = (page.len() as i64) < REPLAY_PAGE;
+            self.replay.extend(page.into_iter().map(Arc::new));
+        }
+        let change = self.replay.pop_front();
+        if let Some(change) = &change {
+            self.replayed = Some(change.id);
+        }
+        Ok(change)
+    }
+
+    /// Role changes may alter inherited privileges; user changes only
+    /// matter for the subscriber's own user.
+    fn may_change_profile(&self, change: &Change) -> bool {
+        let aggregate_type = change.aggregate_type.as_str();
+        aggregate_type == <Role as Model>::NAME
+            || (aggregate_type == <User as Model>::NAME
+                && self
+                    .profile
+                    .principal
+                    .user()
+                    .is_some_and(|id| change.aggregate == id.value().to_string()))
+    }
+
+    /// Resolves the profile from the stored user, telling whether the
+    /// subscriber may go on.
+    async fn refresh(&mut self) -> gnify::error::Result<bool> {
+        self.resolved = Instant::now();
+        let Some(id) = self.profile.principal.user() else {
+            return Ok(true);
+        };
+        let Some(user) = self
+            .source
+            .read(GetUser {
+                id: Some(id.value()),
+                ..Default::default()
+            })
+            .await?
+        else {
+            return Ok(false);
+        };
+        let profile = AuthProfile::from(&user);
+        if profile.auth_hash != self.profile.auth_hash {
+            return Ok(false);
+        }
+        self.profile = AuthProfile {
+            device: self.profile.device,
+            ..profile
+        };
+        Ok(true)
+    }
+}
+
+/// Whether `profile` may see `change`: users see their own changes and, with
+/// the matching privilege, those of all records of a type.
+pub fn visible(profile: &AuthProfile, change: &Change) -> bool {
+    let aggregate_type = change.aggregate_type.as_str();
+    if aggregate_type == <User as Model>::NAME {
+        profile
+            .principal
+            .user()
+            .is_some_and(|id| change.aggregate == id.value().to_string())
+            || profile.has_privilege(privilege::GET_USER_DETAILS)
+    } else if aggregate_type == <Role as Model>::NAME {
+        profile.has_privilege(privilege::MANAGE_ROLES)
+    } else if aggregate_type == <Device as Model>::NAME {
+        profile.has_privilege(privilege::MANAGE_DEVICES)
+    } else {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use gnify::vo::{Version, ID};
+    use gnify_core::role::RoleLevel;
+    use ulid::Ulid;
+
+    use crate::application::Principal;
+
+    use super::*;
+
+    fn profile(principal: Principal, privileges: &[&str]) -> AuthProfile {
+        AuthProfile {
+            principal,
+            privileges: privileges.iter().map(ToString::to_string).collect::<HashSet<_>>(),
+            level: RoleLevel::GUEST,
+            site: None,
+            device: None,
+            auth_hash: Vec::new(),
+        }
+    }
+
+    fn change(aggregate_type: &str, aggregate: Ulid) -> Change {
+        Change {
+            id: 1,
+            aggregate_type: aggregate_type.to_string(),
+            aggregate: aggregate.to_string(),
+            event: "Changed".to_string(),
+            version: Version::now(Ulid::new()),
+            payload: None,
+        }
+    }
+
+    #[test]
+    fn users_see_their_own_changes_only_without_the_privilege() {
+        let id = Ulid::new();
+        let user = profile(Principal::User(ID::new(id)), &[]);
+        assert!(visible(&user, &change(<User as Model>::NAME, id)));
+        assert!(!visible(&user, &change(<User as Model>::NAME, Ulid::new())));
+
+        let viewer = profile(Principal::User(ID::new(Ulid::new())), &[privilege::GET_USER_DETAILS]);
+        assert!(visible(&viewer, &change(<User as Model>::NAME, id)));
+    }
+
+    #[test]
+    fn devices_see_no_user_changes_even_with_their_own_id() {
+        let id = Ulid::new();
+        let device = profile(Principal::Device(ID::new(id)), &[]);
+        assert!(!visible(&device, &change(<User as Model>::NAME, id)));
+    }
+
+    #[test]
+    fn role_and_device_changes_need_their_privileges() {
+        let nobody = profile(Principal::User(ID::new(Ulid::new())), &[]);
+        let manager = profile(
+            Principal::User(ID::new(Ulid::new())),
+            &[privilege::MANAGE_ROLES, privilege::MANAGE_DEVICES],
+        );
+        for aggregate_type in [<Role as Model>::NAME, <Device as Model>::NAME] {
+            let change = change(aggregate_type, Ulid::new());
+            assert!(!visible(&nobody, &change));
+            assert!(visible(&manager, &change));
+        }
+    }
+
+    #[test]
+    fn changes_to_other_models_are_never_visible() {
+        let everything = profile(
+            Principal::User(ID::new(Ulid::new())),
+            &[privilege::GET_USER_DETAILS, privilege::MANAGE_ROLES, privilege::MANAGE_DEVICES],
+        );
+        assert!(!visible(&everything, &change("core.webhook", Ulid::new())));
+    }
+
+    #[test]
+    fn broadcast_reaches_every_subscriber() {
+        let feed = ChangeFeed::default();
+        let first = feed.subscribe();
+        let second = feed.subscribe();
+        feed.broadcast(change(<Role as Model>::NAME, Ulid::new()));
+        assert_eq!(first.try_recv().unwrap().id, 1);
+        assert_eq!(second.try_recv().unwrap().id, 1);
+    }
+
+    #[test]
+    fn broadcast_drops_closed_and_lagging_subscribers() {
+        let feed = ChangeFeed::default();
+        let reading = feed.subscribe();
+        let lagging = feed.subscribe();
+        drop(feed.subscribe());
+        for _ in 0..BUFFER {
+            feed.broadcast(change(<Role as Model>::NAME, Ulid::new()));
+            reading.try_recv().unwrap();
+        }
+        assert_eq!(feed.subscribers.lock().unwrap().len(), 2);
+
+        feed.broadcast(change(<Role as Model>::NAME, Ulid::new()));
+        assert_eq!(feed.subscribers.lock().unwrap().len(), 1);
+        assert!(lagging.is_closed());
+        assert!(reading.try_recv().is_ok());
+    }
+}
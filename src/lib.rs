@@ -1,2 +1,12 @@
 pub(crate) mod application;
-pub mod api;
\ No newline at end of file
+pub(crate) mod bootstrap;
+pub(crate) mod config;
+pub(crate) mod device_session;
+pub(crate) mod device_token;
+pub(crate) mod feed;
+pub(crate) mod maintenance;
+pub(crate) mod outbox;
+pub(crate) mod seed;
+pub(crate) mod webhook;
+pub mod api;
+pub mod cli;
@@ -1,10 +1,10 @@
 use std::sync::Arc;
 
-use gnify_app::api;
+use gnify_app::cli;
 
 smol_macros::main! {
     async fn main(ex: &Arc<smol_macros::Executor<'_>>) -> Result<(), Box<dyn std::error::Error>>  {
-        api::run(ex.clone()).await?;
+        cli::run(ex.clone()).await?;
         Ok(())
     }
 }
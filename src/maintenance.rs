@@ -0,0 +1,82 @@
+use std::sync::Arc;
+
+use chrono::Utc;
+use gnify::{
+    event::EventBus,
+    repository::Repository,
+    source::{PgSource, PruneOutbox, RecordMaintenanceRun, Source},
+};
+use gnify_core::{
+    actor::Actor,
+    device::{Device, DeviceStatus, DeviceUpdate, ExpireSessions, ListDevices},
+    user::ExpireGrants,
+};
+use smol::{stream::StreamExt, Timer};
+
+use crate::config::MaintenanceConfig;
+
+pub const REJECT_STALE_DEVICES: &str = "reject stale devices";
+
+/// Sweeps every `config.interval` until the server stops.
+pub async fn run(source: Arc<PgSource>, events: EventBus, config: MaintenanceConfig) {
+    let mut interval = Timer::interval(config.interval);
+    while interval.next().await.is_some() {
+        sweep(&source, &events, &config).await;
+    }
+}
+
+/// Runs each task once. A failing task is logged and doesn't keep the others
+/// from running; each records what it did in the maintenance log.
+pub async fn sweep(source: &PgSource, events: &EventBus, config: &MaintenanceConfig) {
+    if let Err(error) = source.write(ExpireSessions).await {
+        tracing::warn!(%error, task = ExpireSessions::TASK, "maintenance task failed");
+    }
+    if let Err(error) = source.write(ExpireGrants).await {
+        tracing::warn!(%error, task = ExpireGrants::TASK, "maintenance task failed");
+    }
+    if let Some(ttl) = config.pending_device_ttl {
+        if let Err(error) = reject_stale_devices(source, events, ttl).await {
+            tracing::warn!(%error, task = REJECT_STALE_DEVICES, "maintenance task failed");
+        }
+    }
+    if let Some(ttl) = config.delivered_outbox_ttl {
+        if let Err(error) = source.write(PruneOutbox { ttl }).await {
+            tracing::warn!(%error, task = PruneOutbox::TASK, "maintenance task failed");
+        }
+    }
+}
+
+/// Rejects devices that have been pending for longer than `ttl`, through
+/// the lifecycle so that each rejection is versioned and announced.
+async fn reject_stale_devices(
+    source: &PgSource,
+    events: &EventBus,
+    ttl: std::time::Duration,
+) -> gnify::error::Result<()> {
+    let cutoff = Utc::now().naive_utc() - ttl;
+    let stale: Vec<_> = source
+        .read(ListDevices {
+            status: Some(DeviceStatus::Pending),
+        })
+        .await?
+        .into_iter()
+        .filter(|device| device.first_version().timestamp() <= cutoff)
+        .map(|device| device.id())
+        .collect();
+    let devices = Repository::<_, Device>::new(source, events);
+    let mut rejected = 0;
+    for id in stale {
+        devices
+            .modify(id, Actor::Maintenance, |update: &mut DeviceUpdate| {
+                Ok(update.set_status(DeviceStatus::Rejected)?)
+            })
+            .await?;
+        rejected += 1;
+    }
+    source
+        .write(RecordMaintenanceRun {
+            task: REJECT_STALE_DEVICES.to_string(),
+            affected: rejected,
+        })
+        .await
+}
@@ -0,0 +1,294 @@
+use std::{
+    io::Write as _,
+    path::PathBuf,
+    sync::Arc,
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use chrono::Utc;
+use gnify::{
+    error::PersistenceError,
+    source::{
+        AddOutboxDeliveries, ClaimOutbox, ClaimedOutbox, ListOutboxDeliveries, MarkOutboxDelivered, MarkOutboxFailed,
+        OutboxEntry, OutboxStatus, PgSource, SettleOutbox, Source,
+    },
+    vo::Version,
+};
+use isahc::{config::Configurable, AsyncReadResponseExt, HttpClient, Request};
+use serde::Serialize;
+use smol::Timer;
+use ulid::Ulid;
+
+use crate::config::OutboxConfig;
+
+/// What a target receives for each entry.
+#[derive(Debug, Serialize)]
+pub struct Envelope<'e> {
+    pub id: i64,
+    pub aggregate_type: &'e str,
+    pub aggregate_id: &'e str,
+    pub event: &'e str,
+    pub version: Version,
+    pub payload: &'e serde_json::Value,
+}
+
+impl<'e> From<&'e OutboxEntry> for Envelope<'e> {
+    fn from(entry: &'e OutboxEntry) -> Self {
+        Self {
+            id: entry.id,
+            aggregate_type: &entry.aggregate_type,
+            aggregate_id: &entry.aggregate_id,
+            event: &entry.event,
+            version: entry.version,
+            payload: &entry.payload,
+        }
+    }
+}
+
+/// Somewhere outbox entries are delivered to. Delivery is at least once and
+/// tracked per recipient: a failing one is retried, and eventually
+/// dead-lettered, without resending to the others.
+#[async_trait]
+pub trait Target: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// Who within the target receives `entry`, asked once per entry. By
+    /// default the target is its only recipient, named by an empty string.
+    async fn recipients(&self, _entry: &OutboxEntry) -> Result<Vec<String>, String> {
+        Ok(vec![String::new()])
+    }
+
+    async fn deliver(&self, recipient: &str, entry: &OutboxEntry) -> Result<(), String>;
+}
+
+/// Posts each entry as JSON to a URL; any non-2xx answer is a failure.
+pub struct WebhookTarget {
+    url: String,
+    client: HttpClient,
+    timeout: Duration,
+}
+
+impl WebhookTarget {
+    pub fn new(url: impl Into<String>, timeout: Duration) -> Result<Self, isahc::Error> {
+        Ok(Self {
+            url: url.into(),
+            client: HttpClient::new()?,
+            timeout,
+        })
+    }
+}
+
+#[async_trait]
+impl Target for WebhookTarget {
+    fn name(&self) -> &str {
+        &self.url
+    }
+
+    async fn deliver(&self, _recipient: &str, entry: &OutboxEntry) -> Result<(), String> {
+        let body = serde_json::to_vec(&Envelope::from(entry)).map_err(|error| error.to_string())?;
+        let request = Request::post(&self.url)
+            .header("content-type", "application/json")
+            .timeout(self.timeout)
+            .body(body)
+            .map_err(|error| error.to_string())?;
+        let mut response = self.client.send_async(request).await.map_err(|error| error.to_string())?;
+        if response.status().is_success() {
+            let _ = response.consume().await;
+            Ok(())
+        } else {
+            Err(format!("{} answered {}", self.url, response.status()))
+        }
+    }
+}
+
+/// Appends each entry as a line of JSON to a file.
+pub struct FileTarget {
+    path: PathBuf,
+}
+
+impl FileTarget {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+#[async_trait]
+impl Target for FileTarget {
+    fn name(&self) -> &str {
+        "file"
+    }
+
+    async fn deliver(&self, _recipient: &str, entry: &OutboxEntry) -> Result<(), String> {
+        let mut line = serde_json::to_vec(&Envelope::from(entry)).map_err(|error| error.to_string())?;
+        line.push(b'\n');
+        let path = self.path.clone();
+        smol::unblock(move || {
+            std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)?
+                .write_all(&line)
+        })
+        .await
+        .map_err(|error| error.to_string())
+    }
+}
+
+#[async_trait]
+impl<T: Target + ?Sized> Target for Arc<T> {
+    fn name(&self) -> &str {
+        (**self).name()
+    }
+
+    async fn recipients(&self, entry: &OutboxEntry) -> Result<Vec<String>, String> {
+        (**self).recipients(entry).await
+    }
+
+    async fn deliver(&self, recipient: &str, entry: &OutboxEntry) -> Result<(), String> {
+        (**self).deliver(recipient, entry).await
+    }
+}
+
+/// Delivers outbox entries to its targets. Each aggregate's entries go out
+/// in order; failed deliveries are retried with exponential backoff until
+/// `max_attempts`, after which they become dead letters.
+pub struct Dispatcher {
+    source: Arc<PgSource>,
+    config: OutboxConfig,
+    targets: Vec<Arc<dyn Target>>,
+}
+
+impl Dispatcher {
+    pub fn new(source: Arc<PgSource>, config: OutboxConfig) -> Self {
+        Self {
+            source,
+            config,
+            targets: Vec::new(),
+        }
+    }
+
+    /// Adds the targets named in the configuration.
+    pub fn with_configured_targets(mut self) -> Result<Self, isahc::Error> {
+        if let Some(url) = &self.config.webhook_url {
+            let target = WebhookTarget::new(url.clone(), self.config.timeout)?;
+            self.targets.push(Arc::new(target));
+        }
+        if let Some(path) = &self.config.file {
+            self.targets.push(Arc::new(FileTarget::new(path.clone())));
+        }
+        Ok(self)
+    }
+
+    pub fn with_target(mut self, target: impl Target + 'static) -> Self {
+        self.targets.push(Arc::new(target));
+        self
+    }
+
+    pub async fn run(self) {
+        loop {
+            match self.dispatch().await {
+                Ok(0) => {
+                    Timer::after(self.config.poll_interval).await;
+                }
+                Ok(_) => {}
+                Err(error) => {
+                    tracing::warn!(%error, "couldn't dispatch outbox");
+                    Timer::after(self.config.poll_interval).await;
+                }
+            }
+        }
+    }
+
+    /// Delivers one batch and returns how many entries it held.
+    async fn dispatch(&self) -> gnify::error::Result<usize> {
+        let claim = Ulid::new();
+        self.source
+            .write(ClaimOutbox {
+                claim,
+                limit: self.config.batch_size,
+                lease: self.config.lease,
+            })
+            .await?;
+        let entries = self.source.read(ClaimedOutbox { claim }).await?;
+        for entry in &entries {
+            self.deliver(entry).await?;
+            self.source.write(SettleOutbox { id: entry.id }).await?;
+        }
+        Ok(entries.len())
+    }
+
+    /// Attempts the entry's due deliveries, first fanning it out to the
+    /// recipients of every target.
+    async fn deliver(&self, entry: &OutboxEntry) -> gnify::error::Result<()> {
+        let mut deliveries = self.source.read(ListOutboxDeliveries { id: entry.id }).await?;
+        if deliveries.is_empty() {
+            let mut recipients = Vec::new();
+            for target in &self.targets {
+                let names = target
+                    .recipients(entry)
+                    .await
+                    .map_err(|error| PersistenceError::new(format!("{}: {error}", target.name())))?;
+                recipients.extend(names.into_iter().map(|name| (target.name().to_string(), name)));
+            }
+            self.source
+                .write(AddOutboxDeliveries {
+                    id: entry.id,
+                    recipients,
+                })
+                .await?;
+            deliveries = self.source.read(ListOutboxDeliveries { id: entry.id }).await?;
+        }
+        let now = Utc::now().naive_utc();
+        for delivery in deliveries
+            .into_iter()
+            .filter(|delivery| delivery.status == OutboxStatus::Pending && delivery.next_attempt_at <= now)
+        {
+            let result = match self.targets.iter().find(|target| target.name() == delivery.target) {
+                Some(target) => target.deliver(&delivery.recipient, entry).await,
+                None => Err(String::from("target no longer configured")),
+            };
+            match result {
+                Ok(()) => {
+                    self.source
+                        .write(MarkOutboxDelivered {
+                            id: entry.id,
+                            target: delivery.target,
+                            recipient: delivery.recipient,
+                        })
+                        .await?
+                }
+                Err(error) => {
+                    let attempts = delivery.attempts as u32 + 1;
+                    let retry_at = (attempts < self.config.max_attempts)
+                        .then(|| Utc::now().naive_utc() + self.backoff(attempts));
+                    let target = delivery.target.as_str();
+                    let recipient = delivery.recipient.as_str();
+                    if retry_at.is_none() {
+                        tracing::error!(id = entry.id, event = %entry.event, target, recipient, %error, "outbox delivery moved to dead letters");
+                    } else {
+                        tracing::warn!(id = entry.id, event = %entry.event, target, recipient, attempts, %error, "outbox delivery failed");
+                    }
+                    self.source
+                        .write(MarkOutboxFailed {
+                            id: entry.id,
+                            target: delivery.target,
+                            recipient: delivery.recipient,
+                            error,
+                            retry_at,
+                        })
+                        .await?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn backoff(&self, attempts: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempts.saturating_sub(1));
+        self.config
+            .backoff_base
+            .saturating_mul(factor)
+            .min(self.config.backoff_max)
+    }
+}
@@ -0,0 +1,367 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    hash::Hash,
+    path::Path,
+};
+
+use gnify::{
+    error::{InvalidValue, Result},
+    event::EventBus,
+    source::{PgSource, Source, Transaction},
+};
+use gnify_core::{
+    actor::Actor,
+    device::{Device, DeviceName, DeviceStatus, DeviceToken, DeviceUpdate, GetDevice, WriteDevice},
+    role::{GetRole, GetRoleLadder, Role, RoleUpdate, WriteRole},
+    user::{Email, GetUser, User, UserUpdate, Validity, WriteUser},
+    Privilege, Site,
+};
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use ulid::Ulid;
+
+/// Roles, users and devices every deployment should have, read from a JSON,
+/// YAML or TOML file.
+#[derive(Debug, Default, Deserialize)]
+pub struct Seed {
+    #[serde(default)]
+    pub roles: Vec<RoleSeed>,
+    #[serde(default)]
+    pub users: Vec<UserSeed>,
+    #[serde(default)]
+    pub devices: Vec<DeviceSeed>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RoleSeed {
+    pub name: String,
+    pub level: String,
+    #[serde(default)]
+    pub privileges: Vec<String>,
+    /// Names of parent roles, declared earlier in the file or already stored.
+    #[serde(default)]
+    pub parents: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UserSeed {
+    pub username: String,
+    /// Only used when the user is created; existing passwords are kept.
+    pub password: Option<String>,
+    pub email: Option<String>,
+    pub site: Option<String>,
+    #[serde(default)]
+    pub roles: Vec<GrantSeed>,
+    #[serde(default)]
+    pub privileges: Vec<GrantSeed>,
+}
+
+/// A role or privilege given to a user, either by name alone or with the
+/// period it is valid in. Without a stated period an existing grant keeps
+/// its own and a new one is valid indefinitely.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum GrantSeed {
+    Name(String),
+    Timed {
+        name: String,
+        valid_from: Option<NaiveDateTime>,
+        valid_until: Option<NaiveDateTime>,
+    },
+}
+
+impl GrantSeed {
+    pub fn name(&self) -> &str {
+        match self {
+            GrantSeed::Name(name) | GrantSeed::Timed { name, .. } => name,
+        }
+    }
+
+    pub fn validity(&self) -> std::result::Result<Option<Validity>, InvalidValue> {
+        match self {
+            GrantSeed::Timed {
+                valid_from,
+                valid_until,
+                ..
+            } if valid_from.is_some() || valid_until.is_some() => Validity::new(*valid_from, *valid_until).map(Some),
+            _ => Ok(None),
+        }
+    }
+}
+
+/// Keeps the validity of a grant the seed does not state one for.
+fn with_validity<K: Eq + Hash + Clone>(
+    stated: &HashMap<K, Option<Validity>>,
+    current: &HashMap<K, Validity>,
+) -> HashMap<K, Validity> {
+    stated
+        .iter()
+        .map(|(key, validity)| {
+            let validity = validity.or_else(|| current.get(key).copied()).unwrap_or_default();
+            (key.clone(), validity)
+        })
+        .collect()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeviceSeed {
+    /// Matched against stored devices through its hash.
+    pub token: DeviceToken,
+    pub name: String,
+    pub status: DeviceStatus,
+    pub site: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct SeedReport {
+    pub created: Vec<String>,
+    pub updated: Vec<String>,
+    pub unchanged: Vec<String>,
+}
+
+impl fmt::Display for SeedReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (label, entries) in [
+            ("created", &self.created),
+            ("updated", &self.updated),
+            ("unchanged", &self.unchanged),
+        ] {
+            for entry in entries {
+                writeln!(f, "{label:>9} {entry}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Seed {
+    pub fn load(path: &Path) -> Result<Seed> {
+        let invalid = |error: &dyn fmt::Display| InvalidValue::new(format!("seed file {}: {error}", path.display()));
+        let content = std::fs::read_to_string(path).map_err(|error| invalid(&error))?;
+        let seed = match path.extension().and_then(|extension| extension.to_str()) {
+            Some("json") => serde_json::from_str(&content).map_err(|error| invalid(&error))?,
+            Some("yaml" | "yml") => serde_yaml::from_str(&content).map_err(|error| invalid(&error))?,
+            Some("toml") => toml::from_str(&content).map_err(|error| invalid(&error))?,
+            _ => return Err(invalid(&"unsupported format").into()),
+        };
+        Ok(seed)
+    }
+
+    /// Creates or updates every entry in a single transaction, leaving
+    /// entries that already match untouched. The resulting events are
+    /// published once the transaction committed.
+    pub async fn apply(self, source: &PgSource, actor: Actor, events: &EventBus) -> Result<SeedReport> {
+        let mut report = SeedReport::default();
+        let mut pending = Vec::new();
+        let mut tx = source.begin().await?;
+        let ladder = tx.read(GetRoleLadder).await?;
+
+        for seed in self.roles {
+            let label = format!("role {}", seed.name);
+            let level = ladder.level(&seed.level)?;
+            let privileges = seed
+                .privileges
+                .iter()
+                .map(|privilege| Privilege::registered(privilege))
+                .collect::<std::result::Result<HashSet<_>, InvalidValue>>()?;
+            let mut parents = HashSet::new();
+            for parent in &seed.parents {
+                let parent = tx
+                    .read(GetRole::by_name(parent))
+                    .await?
+                    .ok_or_else(|| InvalidValue::new(format!("Role {parent}")))?;
+                parents.insert(parent.id());
+            }
+            match tx.read(GetRole::by_name(&seed.name)).await? {
+                Some(view) => {
+                    let mut record = view.as_record();
+                    let changed = record.update(actor.id(), |update: &mut RoleUpdate| {
+                        update.level = level;
+                        update.privileges = privileges.clone();
+                        update.parents = parents.clone();
+                        Ok(())
+                    })?;
+                    if changed {
+                        pending.extend(record.events().iter().cloned());
+                        tx.write(WriteRole { record, actor: actor.clone() }).await?;
+                        report.updated.push(label);
+                    } else {
+                        report.unchanged.push(label);
+                    }
+                }
+                None => {
+                    let record = Role::new(
+                        Ulid::new(),
+                        &seed.name,
+                        level,
+                        seed.privileges.iter().map(String::as_str).collect(),
+                        parents.iter().map(|parent| parent.value()).collect(),
+                        actor.id(),
+                    )?;
+                    pending.extend(record.events().iter().cloned());
+                    tx.write(WriteRole { record, actor: actor.clone() }).await?;
+                    report.created.push(label);
+                }
+            }
+        }
+
+        for seed in self.users {
+            let label = format!("user {}", seed.username);
+            let mut roles = HashMap::new();
+            for grant in &seed.roles {
+                let role = tx
+                    .read(GetRole::by_name(grant.name()))
+                    .await?
+                    .ok_or_else(|| InvalidValue::new(format!("Role {}", grant.name())))?;
+                roles.insert(role.id(), grant.validity()?);
+            }
+            let privileges = seed
+                .privileges
+                .iter()
+                .map(|grant| Ok((Privilege::registered(grant.name())?, grant.validity()?)))
+                .collect::<std::result::Result<HashMap<_, _>, InvalidValue>>()?;
+            let email: Option<Email> = seed.email.as_deref().map(str::parse).transpose()?;
+            let site: Option<Site> = seed.site.as_deref().map(str::parse).transpose()?;
+            let (mut record, created) = match tx.read(GetUser::by_username(&seed.username)).await? {
+                Some(view) => (view.as_record(), false),
+                None => {
+                    let password = seed
+                        .password
+                        .as_deref()
+                        .ok_or_else(|| InvalidValue::new(format!("Password for {}", seed.username)))?;
+                    let record = User::new(Ulid::new(), &seed.username, password, None, HashSet::new(), actor.id())?;
+                    (record, true)
+                }
+            };
+            let changed = record.update(actor.id(), |update: &mut UserUpdate| {
+                update.email = email.clone();
+                update.site = site.clone();
+                update.roles = with_validity(&roles, &update.roles);
+                update.privileges = with_validity(&privileges, &update.privileges);
+                Ok(())
+            })?;
+            if created || changed {
+                pending.extend(record.events().iter().cloned());
+                tx.write(WriteUser { record, actor: actor.clone() }).await?;
+            }
+            match (created, changed) {
+                (true, _) => report.created.push(label),
+                (false, true) => report.updated.push(label),
+                (false, false) => report.unchanged.push(label),
+            }
+        }
+
+        for seed in self.devices {
+            let label = format!("device {}", seed.name);
+            let name: DeviceName = seed.name.parse()?;
+            let site: Option<Site> = seed.site.as_deref().map(str::parse).transpose()?;
+            let (mut record, created) = match tx.read(GetDevice::by_token(&seed.token)?).await? {
+                Some(view) => (view.as_record(), false),
+                None => {
+                    let record = Device::new(Ulid::new(), &seed.token, &seed.name, seed.status, actor.id())?;
+                    (record, true)
+                }
+            };
+            let changed = record.update(actor.id(), |update: &mut DeviceUpdate| {
+                update.name = name.clone();
+                update.site = site.clone();
+                Ok(update.set_status(seed.status)?)
+            })?;
+            if created || changed {
+                pending.extend(record.events().iter().cloned());
+                tx.write(WriteDevice { record }).await?;
+            }
+            match (created, changed) {
+                (true, _) => report.created.push(label),
+                (false, true) => report.updated.push(label),
+                (false, false) => report.unchanged.push(label),
+            }
+        }
+
+        tx.commit().await?;
+        events.publish(&pending);
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn instant(value: &str) -> NaiveDateTime {
+        value.parse().unwrap()
+    }
+
+    fn load(extension: &str, content: &str) -> Result<Seed> {
+        let path = std::env::temp_dir().join(format!("gnify-seed-{}.{extension}", Ulid::new()));
+        std::fs::write(&path, content).unwrap();
+        let seed = Seed::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        seed
+    }
+
+    #[test]
+    fn seeds_load_from_json_yaml_and_toml() {
+        let json = load("json", r#"{"roles": [{"name": "Cashier", "level": "Operator"}]}"#).unwrap();
+        assert_eq!(json.roles[0].name, "Cashier");
+        let yaml = load("yaml", "users:\n  - username: alice\n    roles: [Cashier]\n").unwrap();
+        assert_eq!(yaml.users[0].roles[0].name(), "Cashier");
+        let toml = load("toml", "[[roles]]\nname = \"Cashier\"\nlevel = \"Operator\"\nparents = [\"Staff\"]\n").unwrap();
+        assert_eq!(toml.roles[0].parents, ["Staff"]);
+        assert!(toml.users.is_empty() && toml.devices.is_empty());
+    }
+
+    #[test]
+    fn seeds_in_other_formats_are_rejected() {
+        assert!(load("ini", "roles =").is_err());
+        assert!(load("json", "{").is_err());
+    }
+
+    #[test]
+    fn grants_are_given_by_name_or_with_a_period() {
+        let grants: Vec<GrantSeed> = serde_json::from_str(
+            r#"["Cashier", {"name": "Manager"}, {"name": "Auditor", "valid_until": "2027-01-01T00:00:00"}]"#,
+        )
+        .unwrap();
+        let names: Vec<&str> = grants.iter().map(GrantSeed::name).collect();
+        assert_eq!(names, ["Cashier", "Manager", "Auditor"]);
+        assert_eq!(grants[0].validity().unwrap(), None);
+        assert_eq!(grants[1].validity().unwrap(), None);
+        let validity = grants[2].validity().unwrap().unwrap();
+        assert_eq!(validity.valid_until(), Some(instant("2027-01-01T00:00:00")));
+    }
+
+    #[test]
+    fn grants_with_an_inverted_period_are_rejected() {
+        let grant = GrantSeed::Timed {
+            name: String::from("Cashier"),
+            valid_from: Some(instant("2027-01-01T00:00:00")),
+            valid_until: Some(instant("2026-01-01T00:00:00")),
+        };
+        assert!(grant.validity().is_err());
+    }
+
+    #[test]
+    fn unstated_periods_keep_the_current_one() {
+        let current_period = Validity::new(None, Some(instant("2027-01-01T00:00:00"))).unwrap();
+        let stated_period = Validity::new(Some(instant("2026-01-01T00:00:00")), None).unwrap();
+        let current = HashMap::from([("kept", current_period), ("restated", current_period)]);
+        let stated = HashMap::from([("kept", None), ("restated", Some(stated_period)), ("new", None)]);
+        let merged = with_validity(&stated, &current);
+        assert_eq!(merged.len(), 3);
+        assert_eq!(merged["kept"], current_period);
+        assert_eq!(merged["restated"], stated_period);
+        assert_eq!(merged["new"], Validity::default());
+    }
+
+    #[test]
+    fn reports_list_entries_by_outcome() {
+        let report = SeedReport {
+            created: vec![String::from("role Cashier")],
+            updated: vec![String::from("user alice")],
+            unchanged: Vec::new(),
+        };
+        assert_eq!(report.to_string(), "  created role Cashier\n  updated user alice\n");
+    }
+}
@@ -0,0 +1,238 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use chrono::Utc;
+use gnify::source::{OutboxEntry, PgSource, Source};
+use gnify_core::webhook::{GetWebhook, ListWebhooks, LogWebhookDelivery, Webhook};
+use hmac::{Hmac, Mac};
+use isahc::{config::Configurable, AsyncReadResponseExt, HttpClient, Request};
+use rand::RngCore;
+use sha2::Sha256;
+
+use crate::outbox::{Envelope, Target};
+
+pub const SIGNATURE_HEADER: &str = "x-gnify-signature";
+pub const TIMESTAMP_HEADER: &str = "x-gnify-timestamp";
+pub const EVENT_HEADER: &str = "x-gnify-event";
+pub const DELIVERY_HEADER: &str = "x-gnify-delivery";
+
+/// Event name of the deliveries sent by [`SubscriptionTarget::ping`].
+pub const PING: &str = "Ping";
+
+/// The shortest signing secret a webhook may be created with.
+pub const MIN_SECRET_LENGTH: usize = 32;
+
+/// Checks that a chosen signing secret is long enough to resist guessing.
+pub fn check_secret(secret: &str) -> Result<(), String> {
+    if secret.len() < MIN_SECRET_LENGTH {
+        return Err(format!("secret must be at least {MIN_SECRET_LENGTH} characters"));
+    }
+    Ok(())
+}
+
+/// A fresh signing secret for a new webhook.
+pub fn generate_secret() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    format!("whsec_{}", hex::encode(bytes))
+}
+
+/// Signs `timestamp.body` with HMAC-SHA256; receivers recompute it to check
+/// the delivery came from us and reject stale timestamps to stop replays.
+pub fn sign(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    format!("sha256={}", hex::encode(mac(secret, timestamp, body).finalize().into_bytes()))
+}
+
+/// Checks a signature in constant time and that `timestamp` is within
+/// `tolerance` of now.
+pub fn verify(secret: &str, timestamp: i64, body: &[u8], signature: &str, tolerance: Duration) -> bool {
+    let age = Utc::now().timestamp().abs_diff(timestamp);
+    if age > tolerance.as_secs() {
+        return false;
+    }
+    let Some(signature) = signature.strip_prefix("sha256=").and_then(|hex| hex::decode(hex).ok()) else {
+        return false;
+    };
+    mac(secret, timestamp, body).verify_slice(&signature).is_ok()
+}
+
+fn mac(secret: &str, timestamp: i64, body: &[u8]) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any size");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    mac
+}
+
+/// Delivers outbox entries to every active webhook whose filters match,
+/// logging each attempt. Each webhook is a recipient of its own, so a
+/// failing partner is retried and dead-lettered apart from the others.
+pub struct SubscriptionTarget {
+    source: Arc<PgSource>,
+    client: HttpClient,
+    timeout: Duration,
+}
+
+impl SubscriptionTarget {
+    pub fn new(source: Arc<PgSource>, timeout: Duration) -> Result<Self, isahc::Error> {
+        Ok(Self {
+            source,
+            client: HttpClient::new()?,
+            timeout,
+        })
+    }
+
+    /// Sends a signed test delivery to `webhook`, whether or not it is active.
+    pub async fn ping(&self, webhook: &Webhook) -> gnify::error::Result<Result<u16, String>> {
+        let body = serde_json::json!({ "event": PING, "webhook": webhook.id }).to_string();
+        self.send(webhook, None, PING, body.into_bytes()).await
+    }
+
+    async fn send(
+        &self,
+        webhook: &Webhook,
+        outbox_id: Option<i64>,
+        event: &str,
+        body: Vec<u8>,
+    ) -> gnify::error::Result<Result<u16, String>> {
+        let started = Instant::now();
+        let result = self.post(webhook, outbox_id, event, body).await;
+        let (status_code, error) = match &result {
+            Ok(status) => (Some(*status as i32), None),
+            Err((status, error)) => (status.map(i32::from), Some(error.clone())),
+        };
+        self.source
+            .write(LogWebhookDelivery {
+                webhook_id: webhook.id,
+                outbox_id,
+                event: event.to_string(),
+                status_code,
+                error,
+                duration_ms: started.elapsed().as_millis().min(i32::MAX as u128) as i32,
+            })
+            .await?;
+        Ok(result.map_err(|(_, error)| error))
+    }
+
+    async fn post(
+        &self,
+        webhook: &Webhook,
+        outbox_id: Option<i64>,
+        event: &str,
+        body: Vec<u8>,
+    ) -> Result<u16, (Option<u16>, String)> {
+        let timestamp = Utc::now().timestamp();
+        let signature = sign(&webhook.secret, timestamp, &body);
+        let delivery = outbox_id.map(|id| id.to_string()).unwrap_or_default();
+        let request = Request::post(&webhook.url)
+            .header("content-type", "application/json")
+            .header(SIGNATURE_HEADER, signature)
+            .header(TIMESTAMP_HEADER, timestamp.to_string())
+            .header(EVENT_HEADER, event)
+            .header(DELIVERY_HEADER, delivery)
+            .timeout(self.timeout)
+            .body(body)
+            .map_err(|error| (None, error.to_string()))?;
+        let mut response = self
+            .client
+            .send_async(request)
+            .await
+            .map_err(|error| (None, error.to_string()))?;
+        let status = response.status();
+        let _ = response.consume().await;
+        if status.is_success() {
+            Ok(status.as_u16())
+        } else {
+            Err((Some(status.as_u16()), format!("answered {status}")))
+        }
+    }
+}
+
+#[async_trait]
+impl Target for SubscriptionTarget {
+    fn name(&self) -> &str {
+        "webhooks"
+    }
+
+    async fn recipients(&self, entry: &OutboxEntry) -> Result<Vec<String>, String> {
+        let webhooks = self.source.read(ListWebhooks).await.map_err(|error| error.to_string())?;
+        Ok(webhooks
+            .into_iter()
+            .filter(|webhook| webhook.active && webhook.matches(&entry.event, &entry.aggregate_type))
+            .map(|webhook| webhook.id.to_string())
+            .collect())
+    }
+
+    /// Skips webhooks deleted or deactivated since the entry was fanned out.
+    async fn deliver(&self, recipient: &str, entry: &OutboxEntry) -> Result<(), String> {
+        let id = recipient.parse().map_err(|_| format!("invalid webhook id {recipient}"))?;
+        let webhook = match self.source.read(GetWebhook { id }).await {
+            Ok(Some(webhook)) if webhook.active => webhook,
+            Ok(_) => return Ok(()),
+            Err(error) => return Err(error.to_string()),
+        };
+        let body = serde_json::to_vec(&Envelope::from(entry)).map_err(|error| error.to_string())?;
+        match self.send(&webhook, Some(entry.id), &entry.event, body).await {
+            Ok(Ok(_)) => Ok(()),
+            Ok(Err(error)) => Err(format!("{}: {error}", webhook.url)),
+            Err(error) => Err(format!("{}: {error}", webhook.url)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "whsec_0123456789abcdef0123456789abcdef";
+    const TOLERANCE: Duration = Duration::from_secs(300);
+
+    #[test]
+    fn verify_accepts_its_own_signature() {
+        let now = Utc::now().timestamp();
+        let signature = sign(SECRET, now, b"{}");
+        assert!(signature.starts_with("sha256="));
+        assert!(verify(SECRET, now, b"{}", &signature, TOLERANCE));
+    }
+
+    #[test]
+    fn verify_rejects_another_secret() {
+        let now = Utc::now().timestamp();
+        let signature = sign("whsec_another_secret_of_enough_length", now, b"{}");
+        assert!(!verify(SECRET, now, b"{}", &signature, TOLERANCE));
+    }
+
+    #[test]
+    fn verify_rejects_a_tampered_body_or_timestamp() {
+        let now = Utc::now().timestamp();
+        let signature = sign(SECRET, now, b"{}");
+        assert!(!verify(SECRET, now, b"{ }", &signature, TOLERANCE));
+        assert!(!verify(SECRET, now - 1, b"{}", &signature, TOLERANCE));
+    }
+
+    #[test]
+    fn verify_rejects_stale_timestamps() {
+        let stale = Utc::now().timestamp() - 301;
+        let signature = sign(SECRET, stale, b"{}");
+        assert!(!verify(SECRET, stale, b"{}", &signature, TOLERANCE));
+    }
+
+    #[test]
+    fn verify_rejects_malformed_signatures() {
+        let now = Utc::now().timestamp();
+        let signature = sign(SECRET, now, b"{}");
+        let bare = signature.trim_start_matches("sha256=");
+        assert!(!verify(SECRET, now, b"{}", bare, TOLERANCE));
+        assert!(!verify(SECRET, now, b"{}", "sha256=not-hex", TOLERANCE));
+    }
+
+    #[test]
+    fn secrets_must_be_long_enough() {
+        assert!(check_secret(&"s".repeat(MIN_SECRET_LENGTH - 1)).is_err());
+        assert!(check_secret(&"s".repeat(MIN_SECRET_LENGTH)).is_ok());
+        assert!(check_secret(&generate_secret()).is_ok());
+    }
+}